@@ -0,0 +1,601 @@
+use shared::config::{Config, ConfigError, ConfigSection, FromValue, SectionReader};
+use shared::locate::Locator;
+use net::Identity;
+use session::SavedSession;
+use input::{Button, Key};
+use slog::Level;
+use toml::Value;
+use std::collections::HashSet;
+use std::env;
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+/// The shipped configuration file, used as the defaults for every key.
+pub const DEFAULT_CONFIG: &'static str = include_str!("../config/client.toml");
+
+/// Returns a locator for the client configuration file.
+///
+/// It can be overriden with `--config <path>` or `YASSS_CLIENT_CONFIG`.
+pub fn locator() -> Locator {
+    Locator::new("client configuration", "client.toml")
+        .with_flag("--config")
+        .with_env("YASSS_CLIENT_CONFIG")
+}
+
+/// Loads the client configuration with all its override layers.
+///
+/// If no configuration file exists yet, the default one is written first, either to the
+/// explicitly given path or next to the executable. Returns the path of the file.
+pub fn load(config: &mut Config) -> Result<PathBuf, ConfigError> {
+    let args: Vec<String> = env::args().collect();
+    let locator = locator();
+    let path = match locator.locate_with_args(&args) {
+        Ok(located) => located.path,
+        Err(_) => {
+            let path = match locator.explicit(&args) {
+                Some(located) => located.path,
+                None => {
+                    locator.candidates()
+                        .into_iter()
+                        .next()
+                        .map(|(_, path)| path)
+                        .unwrap_or_else(|| PathBuf::from("client.toml"))
+                }
+            };
+            write_default(&path)?;
+            path
+        }
+    };
+
+    config.load_defaults(DEFAULT_CONFIG).expect("Built-in default configuration is invalid.");
+    config.load_file(&path)?;
+    config.load_env();
+    config.load_args(&args)?;
+    Ok(path)
+}
+
+fn write_default(path: &Path) -> Result<(), ConfigError> {
+    let parent = path.parent().unwrap_or_else(|| Path::new(""));
+    fs::create_dir_all(parent)
+        .and_then(|_| File::create(path))
+        .and_then(|mut file| file.write_all(DEFAULT_CONFIG.as_bytes()))
+        .map_err(|error| ConfigError::io(path, error))
+}
+
+/// The `[server]` section.
+#[derive(Debug)]
+pub struct ServerConfig {
+    /// The address of the server to connect to, as `host:port`.
+    pub address: String,
+
+    /// Seconds to wait for the server before giving up on the connection.
+    pub timeout: u64,
+}
+
+impl Default for ServerConfig {
+    fn default() -> ServerConfig {
+        ServerConfig {
+            address: String::from("127.0.0.1:40000"),
+            timeout: 30,
+        }
+    }
+}
+
+impl ConfigSection for ServerConfig {
+    fn name() -> &'static str {
+        "server"
+    }
+
+    fn read(&mut self, reader: &SectionReader) -> Result<(), ConfigError> {
+        reader.read("address", &mut self.address)?;
+        reader.read_range("timeout", &mut self.timeout, 1, 3600)
+    }
+}
+
+/// The `[player]` section.
+#[derive(Debug)]
+pub struct PlayerConfig {
+    pub nickname: String,
+}
+
+impl Default for PlayerConfig {
+    fn default() -> PlayerConfig {
+        PlayerConfig { nickname: String::from("Player") }
+    }
+}
+
+impl ConfigSection for PlayerConfig {
+    fn name() -> &'static str {
+        "player"
+    }
+
+    fn read(&mut self, reader: &SectionReader) -> Result<(), ConfigError> {
+        reader.read("nickname", &mut self.nickname)?;
+        if self.nickname.trim().is_empty() {
+            return Err(reader.error("nickname", String::from("expected a non-empty string")));
+        }
+        Ok(())
+    }
+}
+
+/// The `[account]` section.
+#[derive(Debug, Default)]
+pub struct AccountConfig {
+    /// The account to log in with. Without one, the player connects as a guest.
+    pub name: String,
+    pub password: String,
+}
+
+impl AccountConfig {
+    /// How to log in with these settings, preferring a session saved for the server at `address`.
+    pub fn identity(&self, address: &str, saved: Option<&SavedSession>) -> Identity {
+        if self.name.is_empty() {
+            return Identity::Guest;
+        }
+        match saved {
+            Some(session) if session.is_for(address, &self.name) => {
+                Identity::Session(session.token.clone())
+            }
+            _ => self.password_identity(),
+        }
+    }
+
+    /// How to log in with the password, ignoring any saved session.
+    pub fn password_identity(&self) -> Identity {
+        Identity::Account {
+            name: self.name.clone(),
+            password: self.password.clone(),
+        }
+    }
+}
+
+impl ConfigSection for AccountConfig {
+    fn name() -> &'static str {
+        "account"
+    }
+
+    fn read(&mut self, reader: &SectionReader) -> Result<(), ConfigError> {
+        reader.read("name", &mut self.name)?;
+        reader.read("password", &mut self.password)
+    }
+}
+
+/// The `[window]` section.
+#[derive(Debug)]
+pub struct WindowConfig {
+    pub width: u32,
+    pub height: u32,
+
+    /// Everything drawn is scaled by this factor.
+    pub scale: f64,
+}
+
+impl Default for WindowConfig {
+    fn default() -> WindowConfig {
+        WindowConfig {
+            width: 640,
+            height: 480,
+            scale: 1.0,
+        }
+    }
+}
+
+impl ConfigSection for WindowConfig {
+    fn name() -> &'static str {
+        "window"
+    }
+
+    fn read(&mut self, reader: &SectionReader) -> Result<(), ConfigError> {
+        reader.read_range("width", &mut self.width, 320, 16384)?;
+        reader.read_range("height", &mut self.height, 240, 16384)?;
+        reader.read_range("scale", &mut self.scale, 0.25, 16.0)
+    }
+}
+
+/// The `[assets]` section.
+#[derive(Debug, Default)]
+pub struct AssetsConfig {
+    /// Megabytes of decoded sprites and fonts to keep in memory, or 0 for no limit.
+    pub memory_budget: usize,
+
+    /// Megabytes of sprite textures to keep on the GPU, or 0 for no limit.
+    pub texture_budget: usize,
+}
+
+impl AssetsConfig {
+    /// The memory budget for the asset manager, in bytes.
+    pub fn memory_budget_bytes(&self) -> Option<usize> {
+        megabytes(self.memory_budget)
+    }
+
+    /// The texture budget for the renderer, in bytes.
+    pub fn texture_budget_bytes(&self) -> Option<usize> {
+        megabytes(self.texture_budget)
+    }
+}
+
+fn megabytes(budget: usize) -> Option<usize> {
+    match budget {
+        0 => None,
+        budget => Some(budget * 1024 * 1024),
+    }
+}
+
+impl ConfigSection for AssetsConfig {
+    fn name() -> &'static str {
+        "assets"
+    }
+
+    fn read(&mut self, reader: &SectionReader) -> Result<(), ConfigError> {
+        reader.read_range("memory_budget", &mut self.memory_budget, 0, 1024 * 1024)?;
+        reader.read_range("texture_budget", &mut self.texture_budget, 0, 1024 * 1024)
+    }
+}
+
+/// The `[controls]` section.
+#[derive(Debug)]
+pub struct ControlsConfig {
+    /// How fast the camera moves, in pixels per second.
+    pub camera_speed: f64,
+}
+
+impl Default for ControlsConfig {
+    fn default() -> ControlsConfig {
+        ControlsConfig { camera_speed: 300.0 }
+    }
+}
+
+impl ConfigSection for ControlsConfig {
+    fn name() -> &'static str {
+        "controls"
+    }
+
+    fn read(&mut self, reader: &SectionReader) -> Result<(), ConfigError> {
+        reader.read_range("camera_speed", &mut self.camera_speed, 0.0, 100000.0)
+    }
+}
+
+/// The keys bound to a single action.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Binding(pub Vec<Key>);
+
+impl Binding {
+    /// Returns whether any of the bound keys is held down.
+    pub fn is_held(&self, held: &HashSet<Button>) -> bool {
+        self.0.iter().any(|key| held.contains(&Button::Keyboard(*key)))
+    }
+}
+
+impl FromValue for Binding {
+    /// Reads either a single key name or a list of them.
+    fn from_value(value: &Value) -> Result<Binding, String> {
+        let names = match *value {
+            Value::String(ref name) => vec![name.clone()],
+            _ => {
+                Vec::<String>::from_value(value)
+                    .map_err(|_| String::from("expected a key name or a list of key names"))?
+            }
+        };
+
+        let mut keys = Vec::with_capacity(names.len());
+        for name in names {
+            match parse_key(&name) {
+                Some(key) => keys.push(key),
+                None => return Err(format!("unknown key name `{}`", name)),
+            }
+        }
+        Ok(Binding(keys))
+    }
+}
+
+/// The `[keys]` section.
+#[derive(Debug)]
+pub struct KeysConfig {
+    pub up: Binding,
+    pub down: Binding,
+    pub left: Binding,
+    pub right: Binding,
+}
+
+impl Default for KeysConfig {
+    fn default() -> KeysConfig {
+        KeysConfig {
+            up: Binding(vec![Key::Up, Key::W]),
+            down: Binding(vec![Key::Down, Key::S]),
+            left: Binding(vec![Key::Left, Key::A]),
+            right: Binding(vec![Key::Right, Key::D]),
+        }
+    }
+}
+
+impl ConfigSection for KeysConfig {
+    fn name() -> &'static str {
+        "keys"
+    }
+
+    fn read(&mut self, reader: &SectionReader) -> Result<(), ConfigError> {
+        reader.read("up", &mut self.up)?;
+        reader.read("down", &mut self.down)?;
+        reader.read("left", &mut self.left)?;
+        reader.read("right", &mut self.right)
+    }
+}
+
+/// The `[log]` section.
+#[derive(Debug)]
+pub struct LogConfig {
+    pub level: Level,
+}
+
+impl Default for LogConfig {
+    fn default() -> LogConfig {
+        LogConfig { level: Level::Info }
+    }
+}
+
+impl ConfigSection for LogConfig {
+    fn name() -> &'static str {
+        "log"
+    }
+
+    fn read(&mut self, reader: &SectionReader) -> Result<(), ConfigError> {
+        let mut name = String::new();
+        reader.read("level", &mut name)?;
+        if name.is_empty() {
+            return Ok(());
+        }
+
+        self.level = match &*name.to_lowercase() {
+            "critical" => Level::Critical,
+            "error" => Level::Error,
+            "warning" => Level::Warning,
+            "info" => Level::Info,
+            "debug" => Level::Debug,
+            "trace" => Level::Trace,
+            _ => {
+                let reason = format!("unknown log level `{}`", name);
+                return Err(reader.error("level", reason));
+            }
+        };
+        Ok(())
+    }
+}
+
+/// Every section of the client configuration.
+#[derive(Debug, Default)]
+pub struct ClientConfig {
+    pub server: ServerConfig,
+    pub player: PlayerConfig,
+    pub account: AccountConfig,
+    pub window: WindowConfig,
+    pub assets: AssetsConfig,
+    pub controls: ControlsConfig,
+    pub keys: KeysConfig,
+    pub log: LogConfig,
+}
+
+impl ClientConfig {
+    /// Reads every section out of a loaded config.
+    pub fn from_config(config: &Config) -> Result<ClientConfig, ConfigError> {
+        Ok(ClientConfig {
+            server: config.section()?,
+            player: config.section()?,
+            account: config.section()?,
+            window: config.section()?,
+            assets: config.section()?,
+            controls: config.section()?,
+            keys: config.section()?,
+            log: config.section()?,
+        })
+    }
+}
+
+/// Parses a key name as used in the `[keys]` section.
+///
+/// Letters, digits, arrow keys, function keys and the common named keys are supported,
+/// ignoring case.
+pub fn parse_key(name: &str) -> Option<Key> {
+    let key = match &*name.to_lowercase() {
+        "a" => Key::A,
+        "b" => Key::B,
+        "c" => Key::C,
+        "d" => Key::D,
+        "e" => Key::E,
+        "f" => Key::F,
+        "g" => Key::G,
+        "h" => Key::H,
+        "i" => Key::I,
+        "j" => Key::J,
+        "k" => Key::K,
+        "l" => Key::L,
+        "m" => Key::M,
+        "n" => Key::N,
+        "o" => Key::O,
+        "p" => Key::P,
+        "q" => Key::Q,
+        "r" => Key::R,
+        "s" => Key::S,
+        "t" => Key::T,
+        "u" => Key::U,
+        "v" => Key::V,
+        "w" => Key::W,
+        "x" => Key::X,
+        "y" => Key::Y,
+        "z" => Key::Z,
+        "0" => Key::D0,
+        "1" => Key::D1,
+        "2" => Key::D2,
+        "3" => Key::D3,
+        "4" => Key::D4,
+        "5" => Key::D5,
+        "6" => Key::D6,
+        "7" => Key::D7,
+        "8" => Key::D8,
+        "9" => Key::D9,
+        "f1" => Key::F1,
+        "f2" => Key::F2,
+        "f3" => Key::F3,
+        "f4" => Key::F4,
+        "f5" => Key::F5,
+        "f6" => Key::F6,
+        "f7" => Key::F7,
+        "f8" => Key::F8,
+        "f9" => Key::F9,
+        "f10" => Key::F10,
+        "f11" => Key::F11,
+        "f12" => Key::F12,
+        "up" => Key::Up,
+        "down" => Key::Down,
+        "left" => Key::Left,
+        "right" => Key::Right,
+        "space" => Key::Space,
+        "return" | "enter" => Key::Return,
+        "escape" | "esc" => Key::Escape,
+        "tab" => Key::Tab,
+        "backspace" => Key::Backspace,
+        "delete" => Key::Delete,
+        "insert" => Key::Insert,
+        "home" => Key::Home,
+        "end" => Key::End,
+        "pageup" => Key::PageUp,
+        "pagedown" => Key::PageDown,
+        "lshift" => Key::LShift,
+        "rshift" => Key::RShift,
+        "lctrl" => Key::LCtrl,
+        "rctrl" => Key::RCtrl,
+        "lalt" => Key::LAlt,
+        "ralt" => Key::RAlt,
+        _ => return None,
+    };
+    Some(key)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use shared::config::parse_value;
+    use std::io::Read;
+    use std::process;
+
+    /// Read the client config from the shipped defaults with `file` on top.
+    fn settings(file: &str) -> Result<ClientConfig, ConfigError> {
+        let mut config = Config::new();
+        config.load_defaults(DEFAULT_CONFIG).unwrap();
+        config.load(file).unwrap();
+        ClientConfig::from_config(&config)
+    }
+
+    fn invalid_key(result: Result<ClientConfig, ConfigError>) -> String {
+        match result {
+            Err(ConfigError::Invalid { key, .. }) => key,
+            other => panic!("Unexpected result: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_parse_key() {
+        assert_eq!(parse_key("w"), Some(Key::W));
+        assert_eq!(parse_key("W"), Some(Key::W));
+        assert_eq!(parse_key("7"), Some(Key::D7));
+        assert_eq!(parse_key("F12"), Some(Key::F12));
+        assert_eq!(parse_key("Up"), Some(Key::Up));
+        assert_eq!(parse_key("Enter"), parse_key("return"));
+        assert_eq!(parse_key("PageDown"), Some(Key::PageDown));
+
+        for name in &["", "f13", "page down", "ctrl", "ww", " w"] {
+            assert_eq!(parse_key(name), None, "{:?}", name);
+        }
+    }
+
+    #[test]
+    fn test_binding() {
+        let single = Binding::from_value(&parse_value("\"Space\"")).unwrap();
+        assert_eq!(single, Binding(vec![Key::Space]));
+        let list = Binding::from_value(&parse_value("[\"Up\", \"w\"]")).unwrap();
+        assert_eq!(list, Binding(vec![Key::Up, Key::W]));
+
+        assert_eq!(Binding::from_value(&parse_value("[\"Up\", \"Hyper\"]")),
+                   Err(String::from("unknown key name `Hyper`")));
+        assert_eq!(Binding::from_value(&parse_value("5")),
+                   Err(String::from("expected a key name or a list of key names")));
+        assert!(Binding::from_value(&parse_value("[1, 2]")).is_err());
+
+        let mut held = HashSet::new();
+        assert!(!list.is_held(&held));
+        held.insert(Button::Keyboard(Key::W));
+        assert!(list.is_held(&held));
+        assert!(!single.is_held(&held));
+    }
+
+    #[test]
+    fn test_defaults() {
+        // The shipped file matches the built-in defaults.
+        let shipped = settings("").unwrap();
+        let default = ClientConfig::default();
+        assert_eq!(shipped.server.address, default.server.address);
+        assert_eq!(shipped.server.timeout, default.server.timeout);
+        assert_eq!(shipped.player.nickname, default.player.nickname);
+        assert_eq!(shipped.window.width, default.window.width);
+        assert_eq!(shipped.window.scale, default.window.scale);
+        assert_eq!(shipped.assets.memory_budget_bytes(), None);
+        assert_eq!(shipped.assets.texture_budget_bytes(), None);
+        assert_eq!(shipped.keys.up, default.keys.up);
+        assert_eq!(shipped.keys.right, default.keys.right);
+        assert_eq!(shipped.log.level, Level::Info);
+        assert_eq!(shipped.account.identity("127.0.0.1:40000", None), Identity::Guest);
+    }
+
+    #[test]
+    fn test_sections() {
+        let settings = settings("[window]\nwidth = 1024\nscale = 2.0\n\
+                                 [assets]\ntexture_budget = 64\n\
+                                 [keys]\nup = \"k\"\n\
+                                 [log]\nlevel = \"DEBUG\"\n")
+            .unwrap();
+        assert_eq!(settings.window.width, 1024);
+        assert_eq!(settings.window.height, 480);
+        assert_eq!(settings.window.scale, 2.0);
+        assert_eq!(settings.assets.texture_budget_bytes(), Some(64 * 1024 * 1024));
+        assert_eq!(settings.keys.up, Binding(vec![Key::K]));
+        assert_eq!(settings.keys.down, Binding(vec![Key::Down, Key::S]));
+        assert_eq!(settings.log.level, Level::Debug);
+    }
+
+    #[test]
+    fn test_invalid_sections() {
+        assert_eq!(invalid_key(settings("[window]\nwidth = 100\n")), "window.width");
+        assert_eq!(invalid_key(settings("[window]\nheight = 20000\n")), "window.height");
+        assert_eq!(invalid_key(settings("[window]\nscale = 0.1\n")), "window.scale");
+        assert_eq!(invalid_key(settings("[window]\nscale = 32\n")), "window.scale");
+        assert_eq!(invalid_key(settings("[server]\ntimeout = 0\n")), "server.timeout");
+        assert_eq!(invalid_key(settings("[player]\nnickname = \"  \"\n")), "player.nickname");
+        assert_eq!(invalid_key(settings("[keys]\nleft = \"Hyper\"\n")), "keys.left");
+        assert_eq!(invalid_key(settings("[log]\nlevel = \"loud\"\n")), "log.level");
+        assert_eq!(invalid_key(settings("[assets]\nmemory_budget = -1\n")),
+                   "assets.memory_budget");
+        assert_eq!(invalid_key(settings("window = 5\n")), "window");
+
+        let error = settings("[log]\nlevel = \"loud\"\n").unwrap_err();
+        assert!(format!("{}", error).ends_with("unknown log level `loud`"));
+    }
+
+    #[test]
+    fn test_write_default() {
+        let dir = env::temp_dir().join(format!("yasss_client_config_test_{}", process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        // Missing directories are created on the way.
+        let path = dir.join("nested").join("client.toml");
+        write_default(&path).unwrap();
+
+        let mut written = String::new();
+        File::open(&path).unwrap().read_to_string(&mut written).unwrap();
+        assert_eq!(written, DEFAULT_CONFIG);
+
+        let mut config = Config::new();
+        config.load_file(&path).unwrap();
+        assert_eq!(config.get_str("server.address"), Some("127.0.0.1:40000"));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -1,9 +1,16 @@
-use slog::{Logger, DrainExt};
+use slog::{Logger, DrainExt, Level, level_filter};
 use slog_term::streamer;
+use std::sync::Mutex;
 
 lazy_static! {
+    /// The least severe level that gets logged.
+    ///
+    /// This only has an effect if it is set before `LOGGER` is first used.
+    pub static ref LOG_LEVEL: Mutex<Level> = Mutex::new(Level::Info);
+
     pub static ref LOGGER: Logger = {
-        let drain = streamer().build().fuse();
+        let level = *LOG_LEVEL.lock().unwrap();
+        let drain = level_filter(level, streamer().build()).fuse();
 
         Logger::root(drain, None)
     };
@@ -13,26 +13,58 @@ extern crate bytes;
 extern crate tokio_io;
 extern crate tokio_core;
 extern crate futures;
+extern crate toml;
 
+mod config;
 mod logs;
+mod net;
 mod rendering;
+mod session;
 
 use piston_window::*;
-use logs::LOGGER;
-use shared::assets::load_from_dir;
-use std::env;
-use shared::entities::{WORLD, make_builder};
+use logs::{LOGGER, LOG_LEVEL};
+use config::ClientConfig;
+use net::{Connection, ConnectionState, Identity};
+use session::SavedSession;
+use shared::net::NetPacket;
+use shared::config::CONFIG;
+use shared::assets::{ASSET_MANAGER, load_from_dir};
+use shared::locate;
+use std::process;
+use shared::entities::{World, WORLD};
+use shared::entities::replication::{self, REPLICATION};
 use self::rendering::{RenderableComponent, Renderer};
 use shared::entities::components::PositionComponent;
-use shared::entities::components::position::Positional;
 use std::path::Path;
 use std::collections::HashSet;
-use input::{Key, Button, UpdateArgs};
+use std::time::Duration;
+use input::UpdateArgs;
 use shared::rsi::{RsiRef, StateId};
-use nalgebra::core::Vector2;
+
+/// The font used for text drawn by the client itself.
+const DEBUG_FONT: &'static str = "fonts/small.font";
 
 fn main() {
+    // The config is loaded before anything is logged, so the log level applies.
+    let (config_path, settings) = {
+        let mut cfg = CONFIG.write().unwrap();
+        let loaded = config::load(&mut cfg)
+            .and_then(|path| ClientConfig::from_config(&cfg).map(|settings| (path, settings)));
+        match loaded {
+            Ok(loaded) => loaded,
+            Err(error) => {
+                crit!(LOGGER, "Failed to load configuration."; "error" => format!("{}", error));
+                process::exit(1);
+            }
+        }
+    };
+    *LOG_LEVEL.lock().unwrap() = settings.log.level;
+
     info!(LOGGER, "Starting client"; "version" => env!("CARGO_PKG_VERSION"));
+    info!(LOGGER, "Loaded configuration"; "path" => format!("{:?}", config_path),
+        "nickname" => settings.player.nickname.clone(),
+        "account" => settings.account.name.clone(),
+        "server" => settings.server.address.clone());
 
     {
         // Register client components with the ECS world.
@@ -40,55 +72,159 @@ fn main() {
         world.register_component::<RenderableComponent>();
     }
 
-    // Asset dir is next to the executable, under "data".
-    let mut asset_dir = env::current_exe().expect("Unable to find executable path.");
-    // Go by data dir in the main project right now.
-    // TODO: Fix this for packaged release builds or something.
-    asset_dir.pop();
-    asset_dir.pop();
-    asset_dir.pop();
-    asset_dir.push("data");
+    let asset_dir = match locate::assets().locate() {
+        Ok(located) => located,
+        Err(error) => {
+            crit!(LOGGER, "Failed to find assets."; "error" => format!("{}", error));
+            process::exit(1);
+        }
+    };
+
+    info!(LOGGER, "Loading asset directory";
+        "directory" => format!("{:?}", asset_dir.path), "source" => format!("{}", asset_dir.source));
+    ASSET_MANAGER.write().unwrap().set_budget(settings.assets.memory_budget_bytes());
+    let report = load_from_dir(asset_dir.path).expect("Failed to load assets.");
+    if !report.is_ok() {
+        warn!(LOGGER, "Some assets failed to load."; "report" => format!("{}", report));
+    }
 
-    info!(LOGGER, "Loading asset directory"; "directory" => format!("{:?}", asset_dir));
-    load_from_dir(asset_dir).expect("Failed to load assets.");
+    let stats = ASSET_MANAGER.read().unwrap().stats();
+    info!(LOGGER, "Asset memory usage";
+        "resident_bytes" => stats.resident_bytes, "total_bytes" => stats.total_bytes,
+        "resident_assets" => stats.resident_assets, "assets" => stats.assets,
+        "budget" => format!("{:?}", stats.budget));
 
     let mut window: PistonWindow =
-        WindowSettings::new("YASSS13RTWCF", [640, 480]).exit_on_esc(true).build().unwrap();
-
-    make_builder(&WORLD)
-        .with_component(PositionComponent::empty())
-        .with_component(RenderableComponent::new(&Path::new("renderingtest.rsi"),
-                                                 &RsiRef::new(&StateId::new("toolbox"), 0, 0)));
-
-    make_builder(&WORLD)
-        .with_component(PositionComponent::new(Positional::new(Vector2::new(100.0, 100.0), 0)))
-        .with_component(RenderableComponent::new(&Path::new("renderingtest.rsi"),
-                                                 &RsiRef::new(&StateId::new("Ytoolbox"), 0, 0)));
-
+        WindowSettings::new("YASSS13RTWCF", [settings.window.width, settings.window.height])
+            .exit_on_esc(true)
+            .build()
+            .unwrap();
+    let scale = settings.window.scale;
 
     let mut renderer = Renderer::new();
-    renderer.load_textures(&mut window.factory);
+    renderer.texture_budget = settings.assets.texture_budget_bytes();
+    renderer.load_fonts(&mut window.factory, &ASSET_MANAGER.read().unwrap());
 
     let mut keys = HashSet::new();
 
+    let session_path = session::path(&config_path);
+    let saved = session::load(&session_path);
+    let mut identity = settings.account.identity(&settings.server.address, saved.as_ref());
+    let mut saved_token = saved.map(|x| x.token);
+    let connect = |identity: &Identity| {
+        Connection::connect(&settings.server.address,
+                            &settings.player.nickname,
+                            identity.clone(),
+                            Duration::from_secs(settings.server.timeout))
+    };
+    let mut connection = connect(&identity);
+    let mut shown_state = None;
+
     while let Some(e) = window.next() {
-        window.draw_2d(&e, |c, g| renderer.render(c, g));
+        for packet in connection.poll() {
+            match packet {
+                NetPacket::Chat { sender, message } => {
+                    info!(LOGGER, "Chat"; "sender" => sender, "message" => message);
+                }
+                packet => {
+                    let mut world = WORLD.write().unwrap();
+                    match replication::apply(&REPLICATION.read().unwrap(), &mut world, &packet) {
+                        Ok(true) => add_sprites(&mut world),
+                        Ok(false) => {
+                            debug!(LOGGER, "Received packet."; "packet" => format!("{:?}", packet))
+                        }
+                        Err(error) => {
+                            warn!(LOGGER, "Failed to apply world update.";
+                                "error" => format!("{}", error))
+                        }
+                    }
+                }
+            }
+        }
+        if let Some(token) = connection.get_session() {
+            if saved_token.as_ref().map(|x| x.as_str()) != Some(token) {
+                let session = SavedSession {
+                    address: settings.server.address.clone(),
+                    account: settings.account.name.clone(),
+                    token: token.to_owned(),
+                };
+                if let Err(error) = session::save(&session_path, &session) {
+                    warn!(LOGGER, "Failed to save the session.";
+                        "path" => format!("{:?}", session_path), "error" => format!("{}", error));
+                }
+                saved_token = Some(token.to_owned());
+            }
+        }
+        // Saved sessions expire, so fall back to the password if there is one.
+        let session_rejected = match (connection.get_state(), &identity) {
+            (&ConnectionState::Rejected(_), &Identity::Session(_)) => true,
+            _ => false,
+        };
+        if session_rejected && !settings.account.password.is_empty() {
+            info!(LOGGER, "Saved session was refused, logging in with the password.");
+            identity = settings.account.password_identity();
+            connection = connect(&identity);
+        }
+        if shown_state.as_ref() != Some(connection.get_state()) {
+            window.set_title(format!("YASSS13RTWCF - {} - {}",
+                                     connection.get_address(),
+                                     connection.get_state()));
+            shown_state = Some(connection.get_state().clone());
+        }
+
+        e.render(|_| renderer.prepare(&mut window.factory));
+        window.draw_2d(&e, |c, g| {
+            renderer.render(c.scale(scale, scale), g);
+            let (textures, texture_bytes) = renderer.texture_stats();
+            let overlay = format!("YASSS13RTWCF {}\nTextures: {} ({} KiB)\n{}: {}\nNickname: {}",
+                                  env!("CARGO_PKG_VERSION"),
+                                  textures,
+                                  texture_bytes / 1024,
+                                  connection.get_address(),
+                                  connection.get_state(),
+                                  connection.get_nickname());
+            renderer.draw_text(Path::new(DEBUG_FONT),
+                               &overlay,
+                               None,
+                               [2.0, 2.0],
+                               c.scale(2.0 * scale, 2.0 * scale),
+                               g);
+        });
 
         e.press(|x| keys.insert(x));
         e.release(|x| keys.remove(&x));
 
         e.update(|&UpdateArgs { dt: delta }| {
-            if keys.contains(&Button::Keyboard(Key::Up)) {
-                renderer.camera[(1, 0)] -= 300.0 * delta;
-            } else if keys.contains(&Button::Keyboard(Key::Down)) {
-                renderer.camera[(1, 0)] += 300.0 * delta;
+            let bindings = &settings.keys;
+            let speed = settings.controls.camera_speed;
+            if bindings.up.is_held(&keys) {
+                renderer.camera[(1, 0)] -= speed * delta;
+            } else if bindings.down.is_held(&keys) {
+                renderer.camera[(1, 0)] += speed * delta;
             }
 
-            if keys.contains(&Button::Keyboard(Key::Left)) {
-                renderer.camera[(0, 0)] -= 300.0 * delta;
-            } else if keys.contains(&Button::Keyboard(Key::Right)) {
-                renderer.camera[(0, 0)] += 300.0 * delta;
+            if bindings.left.is_held(&keys) {
+                renderer.camera[(0, 0)] -= speed * delta;
+            } else if bindings.right.is_held(&keys) {
+                renderer.camera[(0, 0)] += speed * delta;
             }
         });
     }
+
+    connection.close("Quit.");
+}
+
+/// Give entities from the server that have a position something to draw.
+///
+/// Sprites aren't replicated yet, so every one of them is a toolbox.
+fn add_sprites(world: &mut World) {
+    let missing: Vec<_> = world.iter_components::<PositionComponent>()
+        .map(|(id, _)| id)
+        .filter(|id| world.get_component::<RenderableComponent>(*id).is_none())
+        .collect();
+    for id in missing {
+        world.add_component(id,
+                            RenderableComponent::new(&Path::new("renderingtest.rsi"),
+                                                     &RsiRef::new(&StateId::new("toolbox"), 0, 0)));
+    }
 }
@@ -0,0 +1,362 @@
+use std::fmt;
+use std::io;
+use std::net::ToSocketAddrs;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::thread;
+use std::cell::{Cell, RefCell};
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+use futures::{future, stream, Future, Sink, Stream};
+use futures::sync::mpsc::{unbounded, UnboundedReceiver, UnboundedSender};
+use tokio_core::net::TcpStream;
+use tokio_core::reactor::{Core, Interval, Timeout};
+use tokio_io::AsyncRead;
+use tokio_io::codec::Framed;
+use shared::net::{ClientCodec, ClientMessage, Handshake, Login, NetCodec, NetPacket,
+                  ServerMessage};
+use shared::net::auth;
+use logs::LOGGER;
+
+/// Where a connection to the server is at, for showing to the player.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ConnectionState {
+    /// Connecting and handshaking.
+    Connecting,
+    Connected,
+    /// The server refused the connection, with the message to show.
+    Rejected(String),
+    /// The connection failed or was closed, with a description why.
+    Lost(String),
+    /// The connection was closed on purpose by either side, with the reason.
+    Disconnected(String),
+}
+
+impl fmt::Display for ConnectionState {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ConnectionState::Connecting => write!(f, "Connecting..."),
+            ConnectionState::Connected => write!(f, "Connected"),
+            ConnectionState::Rejected(ref message) => write!(f, "Rejected: {}", message),
+            ConnectionState::Lost(ref message) => write!(f, "Connection lost: {}", message),
+            ConnectionState::Disconnected(ref reason) => write!(f, "Disconnected: {}", reason),
+        }
+    }
+}
+
+/// How to log in when connecting.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Identity {
+    Guest,
+    Account { name: String, password: String },
+    /// A session token from an earlier connection, see `Connection::get_session`.
+    Session(String),
+}
+
+impl Identity {
+    fn login(&self) -> Login {
+        match *self {
+            Identity::Guest => Login::Guest,
+            Identity::Account { ref name, .. } => Login::Account(name.clone()),
+            Identity::Session(ref token) => Login::Session(token.clone()),
+        }
+    }
+}
+
+/// Sent from the network thread to the game loop.
+enum Event {
+    State(ConnectionState),
+    /// The nickname the server accepted the player under.
+    Nickname(String),
+    /// The session token the server gave us for logging into an account.
+    Session(String),
+    Packet(NetPacket),
+}
+
+/// A connection to a server, running on a background thread.
+pub struct Connection {
+    address: String,
+    nickname: String,
+    session: Option<String>,
+    state: ConnectionState,
+    events: Receiver<Event>,
+    outgoing: UnboundedSender<NetPacket>,
+    thread: Option<thread::JoinHandle<()>>,
+}
+
+impl Connection {
+    /// Start connecting to a server at `host:port`, logging in as `identity`.
+    ///
+    /// The connection is lost if connecting takes longer than `timeout`, or if nothing is
+    /// received from the server for that long afterwards.
+    pub fn connect(address: &str,
+                   nickname: &str,
+                   identity: Identity,
+                   timeout: Duration)
+                   -> Connection {
+        let (events, receiver) = mpsc::channel();
+        let (outgoing, outgoing_receiver) = unbounded();
+
+        let thread_address = address.to_owned();
+        let thread_nickname = nickname.to_owned();
+        let spawned = thread::Builder::new().name(String::from("net")).spawn(move || {
+            let state = match run(&thread_address,
+                                  &thread_nickname,
+                                  &identity,
+                                  timeout,
+                                  &events,
+                                  outgoing_receiver) {
+                Ok(state) => state,
+                Err(error) => ConnectionState::Lost(format!("{}", error)),
+            };
+            let _ = events.send(Event::State(state));
+        });
+
+        let (state, thread) = match spawned {
+            Ok(thread) => (ConnectionState::Connecting, Some(thread)),
+            Err(error) => (ConnectionState::Lost(format!("{}", error)), None),
+        };
+        info!(LOGGER, "Connecting to server."; "address" => address.to_owned());
+
+        Connection {
+            address: address.to_owned(),
+            nickname: nickname.to_owned(),
+            session: None,
+            state: state,
+            events: receiver,
+            outgoing: outgoing,
+            thread: thread,
+        }
+    }
+
+    pub fn get_address(&self) -> &str {
+        &self.address
+    }
+
+    /// The player's nickname, which the server may change when accepting the connection.
+    pub fn get_nickname(&self) -> &str {
+        &self.nickname
+    }
+
+    /// The token to log back in with, once connected to an account.
+    pub fn get_session(&self) -> Option<&str> {
+        self.session.as_ref().map(|x| x.as_str())
+    }
+
+    pub fn get_state(&self) -> &ConnectionState {
+        &self.state
+    }
+
+    /// Queue a packet to be sent to the server.
+    ///
+    /// Returns false if the connection is gone.
+    pub fn send(&self, packet: NetPacket) -> bool {
+        self.outgoing.unbounded_send(packet).is_ok()
+    }
+
+    /// Tell the server we're leaving, and wait until it was told.
+    ///
+    /// Doesn't wait for connections that aren't connected yet.
+    pub fn close(mut self, reason: &str) {
+        self.poll();
+        if self.state != ConnectionState::Connected {
+            return;
+        }
+        self.send(NetPacket::Disconnect { reason: reason.to_owned() });
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+
+    /// Returns every packet received since the last call, and updates the state.
+    ///
+    /// This never blocks, so it can be called every frame.
+    pub fn poll(&mut self) -> Vec<NetPacket> {
+        let mut packets = Vec::new();
+        while let Ok(event) = self.events.try_recv() {
+            match event {
+                Event::Packet(packet) => packets.push(packet),
+                Event::Nickname(nickname) => {
+                    if nickname != self.nickname {
+                        info!(LOGGER, "The server changed our nickname.";
+                            "requested" => self.nickname.clone(), "nickname" => nickname.clone());
+                    }
+                    self.nickname = nickname;
+                }
+                Event::Session(token) => self.session = Some(token),
+                Event::State(state) => {
+                    info!(LOGGER, "Connection state changed.";
+                        "address" => self.address.clone(), "state" => format!("{}", state));
+                    self.state = state;
+                }
+            }
+        }
+        packets
+    }
+}
+
+type ClientFramed = Framed<TcpStream, ClientCodec>;
+type HandshakeFuture = Box<Future<Item = (Option<ServerMessage>, ClientFramed), Error = io::Error>>;
+type StateFuture = Box<Future<Item = ConnectionState, Error = io::Error>>;
+
+/// Connect, handshake and pass packets along until the connection closes.
+///
+/// Returns the final state of the connection.
+fn run(address: &str,
+       nickname: &str,
+       identity: &Identity,
+       timeout: Duration,
+       events: &Sender<Event>,
+       outgoing: UnboundedReceiver<NetPacket>)
+       -> io::Result<ConnectionState> {
+    let address = match address.to_socket_addrs()?.next() {
+        Some(address) => address,
+        None => {
+            let message = format!("{} did not resolve to any address", address);
+            return Ok(ConnectionState::Lost(message));
+        }
+    };
+
+    let mut core = Core::new()?;
+    let handle = core.handle();
+    let handshake = Handshake::connect_as(env!("CARGO_PKG_VERSION"), nickname, identity.login());
+    let password = match *identity {
+        Identity::Account { ref password, .. } => Some(password.clone()),
+        _ => None,
+    };
+    let connecting = TcpStream::connect(&address, &handle)
+        .and_then(|socket| {
+            socket.framed(ClientCodec::new(NetCodec::new()))
+                .send(ClientMessage::Handshake(handshake))
+        })
+        .and_then(|framed| framed.into_future().map_err(|(e, _)| e))
+        .and_then(move |(message, framed)| -> HandshakeFuture {
+            // Answer the login challenge, if the server sent one.
+            let challenge = match message {
+                Some(ServerMessage::Challenge(challenge)) => challenge,
+                message => return Box::new(future::ok((message, framed))),
+            };
+            let password = match password {
+                Some(password) => password,
+                None => {
+                    let message = "the server sent a login challenge without being asked to";
+                    return Box::new(future::err(io::Error::new(io::ErrorKind::InvalidData,
+                                                               message)));
+                }
+            };
+            let proof = auth::proof(&password, &challenge);
+            Box::new(framed.send(ClientMessage::Proof(proof))
+                .and_then(|framed| framed.into_future().map_err(|(e, _)| e)))
+        });
+    let connect_timeout = Timeout::new(timeout, &handle)?
+        .then(|_| -> Result<(Option<ServerMessage>, ClientFramed), _> {
+            Err(io::Error::new(io::ErrorKind::TimedOut, "timed out connecting to the server"))
+        });
+    let connecting = connecting.select(connect_timeout).map(|(x, _)| x).map_err(|(e, _)| e);
+
+    let reply = match core.run(connecting)? {
+        (Some(ServerMessage::Reply(reply)), framed) => (reply, framed),
+        (None, _) => {
+            let message = String::from("The server closed the connection during the handshake.");
+            return Ok(ConnectionState::Lost(message));
+        }
+        (Some(ServerMessage::Challenge(_)), _) => {
+            let message = "the server sent more than one login challenge";
+            return Err(io::Error::new(io::ErrorKind::InvalidData, message));
+        }
+        // The codec always produces the reply first.
+        (Some(_), _) => unreachable!(),
+    };
+    let (reply, framed) = reply;
+
+    if !reply.is_accepted() {
+        let message = match (reply.explain_mismatch(), reply.rejection) {
+            (Some(explanation), _) => explanation,
+            (None, Some(rejection)) => rejection.message,
+            (None, None) => unreachable!(),
+        };
+        return Ok(ConnectionState::Rejected(message));
+    }
+    let _ = events.send(Event::Nickname(reply.nickname));
+    if !reply.session.is_empty() {
+        let _ = events.send(Event::Session(reply.session));
+    }
+    let _ = events.send(Event::State(ConnectionState::Connected));
+
+    let (sink, stream) = framed.split();
+    let (pongs, pong_receiver) = unbounded();
+    // Stop writing once the game drops its `Connection`, even though pongs could still be sent.
+    // A `Disconnect` is sent last, after which the connection closes.
+    let closing = Rc::new(RefCell::new(None));
+    let stash = closing.clone();
+    let writer = outgoing.map(Some)
+        .chain(stream::once(Ok(None)))
+        .select(pong_receiver.map(Some))
+        .take_while(move |packet| match *packet {
+            Some(NetPacket::Disconnect { ref reason }) => {
+                *stash.borrow_mut() = Some(reason.clone());
+                Ok(false)
+            }
+            Some(_) => Ok(true),
+            None => Ok(false),
+        })
+        .filter_map(|packet| packet.map(ClientMessage::Packet))
+        .map_err(|()| io::Error::new(io::ErrorKind::Other, "outgoing packet channel failed"))
+        .forward(sink)
+        .and_then(move |(_, sink)| -> StateFuture {
+            match closing.borrow_mut().take() {
+                Some(reason) => {
+                    let packet = NetPacket::Disconnect { reason: reason.clone() };
+                    Box::new(sink.send(ClientMessage::Packet(packet))
+                        .map(|_| ConnectionState::Disconnected(reason)))
+                }
+                None => {
+                    let reason = String::from("The connection was closed.");
+                    Box::new(future::ok(ConnectionState::Disconnected(reason)))
+                }
+            }
+        });
+
+    let last_received = Rc::new(Cell::new(Instant::now()));
+    let last_checked = last_received.clone();
+    let idle_check = Interval::new(Duration::from_secs(1), &handle)?.for_each(move |()| {
+        if last_checked.get().elapsed() >= timeout {
+            return Err(io::Error::new(io::ErrorKind::TimedOut, "the server stopped responding"));
+        }
+        Ok(())
+    });
+
+    let packet_events = events.clone();
+    let reader = stream.and_then(move |message| {
+            last_received.set(Instant::now());
+            match message {
+                ServerMessage::Packet(NetPacket::Ping { token }) => {
+                    let _ = pongs.unbounded_send(NetPacket::Pong { token: token });
+                    Ok(None)
+                }
+                ServerMessage::Packet(NetPacket::Pong { .. }) => Ok(None),
+                ServerMessage::Packet(NetPacket::Disconnect { reason }) => Ok(Some(reason)),
+                ServerMessage::Packet(packet) => {
+                    packet_events.send(Event::Packet(packet))
+                        .map(|_| None)
+                        .map_err(|_| io::Error::new(io::ErrorKind::Other, "game loop stopped"))
+                }
+                _ => {
+                    Err(io::Error::new(io::ErrorKind::InvalidData, "unexpected handshake message"))
+                }
+            }
+        })
+        .filter_map(|reason| reason)
+        .into_future()
+        .map(|(reason, _)| match reason {
+            Some(reason) => ConnectionState::Disconnected(reason),
+            None => ConnectionState::Lost(String::from("The server closed the connection.")),
+        })
+        .map_err(|(e, _)| e);
+
+    core.run(reader.select(writer)
+        .map(|(state, _)| state)
+        .map_err(|(e, _)| e)
+        .select(idle_check.map(|()| unreachable!()))
+        .map(|(state, _)| state)
+        .map_err(|(e, _)| e))
+}
@@ -1,12 +1,16 @@
 use std::path::{PathBuf, Path};
 use shared::entities::components::Component;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
 use piston_window::*;
 use gfx_device_gl::{Resources, Factory};
-use shared::assets::{ASSET_MANAGER, Asset};
+use image::GenericImage;
+use logs::LOGGER;
+use shared::assets::{AssetManager, Asset, get_asset};
 use nalgebra::Vector2;
 use shared::entities::WORLD;
 use shared::entities::components::PositionComponent;
+use shared::font::TextLayout;
 use shared::rsi::RsiRef;
 
 #[derive(Debug)]
@@ -26,42 +30,150 @@ impl RenderableComponent {
     }
 }
 
+/// A sprite texture uploaded to the GPU.
+struct CachedTexture {
+    texture: Texture<Resources>,
+
+    /// Roughly how many bytes of GPU memory the texture uses.
+    size: usize,
+
+    /// The last frame this texture was needed in.
+    last_used: u64,
+}
+
 pub struct Renderer {
-    textures: HashMap<(PathBuf, RsiRef), Texture<Resources>>,
+    /// Sprite textures, uploaded when first needed and dropped when over budget.
+    textures: HashMap<(PathBuf, RsiRef), CachedTexture>,
+
+    /// Font glyph textures, which are always kept.
+    glyphs: HashMap<(PathBuf, RsiRef), Texture<Resources>>,
+    fonts: HashMap<PathBuf, Arc<Asset>>,
     pub camera: Vector2<f64>,
+
+    /// The maximum amount of bytes of sprite textures to keep on the GPU, if any.
+    pub texture_budget: Option<usize>,
+    texture_bytes: usize,
+    frame: u64,
+
+    /// Sprites that couldn't be found, so they're only complained about once.
+    missing: HashSet<(PathBuf, RsiRef)>,
 }
 
 impl Renderer {
     pub fn new() -> Renderer {
         Renderer {
             textures: HashMap::new(),
+            glyphs: HashMap::new(),
+            fonts: HashMap::new(),
             camera: Vector2::new(0.0, 0.0),
+            texture_budget: None,
+            texture_bytes: 0,
+            frame: 0,
+            missing: HashSet::new(),
         }
     }
 
-    pub fn load_textures(&mut self, factory: &mut Factory) {
-        let manager = ASSET_MANAGER.read().unwrap();
-        for (path, asset) in manager.iter() {
-            if let Asset::Rsi(ref rsi) = **asset {
-                for state in rsi.iter_states() {
-                    if state.is_empty() {
-                        continue;
+    /// Uploads the glyphs of every font in the asset manager as textures.
+    ///
+    /// Fonts that were evicted are loaded again, and kept from then on.
+    pub fn load_fonts(&mut self, factory: &mut Factory, manager: &AssetManager) {
+        let fonts = manager.paths()
+            .filter(|path| path.extension().and_then(|x| x.to_str()) == Some("font"));
+        for path in fonts {
+            let asset = match manager.get(path) {
+                Some(asset) => asset,
+                None => continue,
+            };
+            if let Some(font) = asset.as_font() {
+                for state in font.get_rsi().iter_states() {
+                    let stateid = state.to_stateid();
+                    let icons = match state.get_icons_vec().get(0) {
+                        Some(icons) => icons,
+                        None => continue,
+                    };
+
+                    for (frame, &(ref image, _)) in icons.iter().enumerate() {
+                        let texture = Texture::from_image(factory,
+                                                          &image.to_rgba(),
+                                                          &TextureSettings::new())
+                                .unwrap();
+                        let rsiref = RsiRef::new(&stateid, 0, frame);
+                        self.glyphs.insert((path.clone(), rsiref), texture);
                     }
+                }
+            }
 
-                    let stateid = state.to_stateid();
-                    for (dir, icons) in state.get_icons_vec().iter().enumerate() {
-                        for (frame, &(ref image, _)) in icons.iter().enumerate() {
-                            let texture = Texture::from_image(factory,
-                                                              &image.to_rgba().clone(),
-                                                              &TextureSettings::new())
-                                    .unwrap();
-                            let rsiref = RsiRef::new(&stateid, dir as u8, frame);
-                            self.textures.insert((path.clone(), rsiref), texture);
-                        }
+            if asset.is_font() {
+                self.fonts.insert(path.clone(), asset);
+            }
+        }
+    }
+
+    /// Uploads the sprite textures needed to render the current frame.
+    ///
+    /// If the texture budget is exceeded afterwards,
+    /// the least recently used textures not needed this frame are dropped.
+    pub fn prepare(&mut self, factory: &mut Factory) {
+        self.frame += 1;
+        let world = WORLD.read().unwrap();
+        for (_, component) in world.iter_components::<RenderableComponent>() {
+            let renderable = component.read().unwrap();
+            // TODO: Don't clone() this shit you idiot.
+            let key = (renderable.image.clone(), renderable.rsiref.clone());
+            if let Some(cached) = self.textures.get_mut(&key) {
+                cached.last_used = self.frame;
+                continue;
+            }
+
+            match upload_icon(factory, &key.0, &key.1) {
+                Some((texture, size)) => {
+                    self.texture_bytes += size;
+                    self.textures.insert(key,
+                                         CachedTexture {
+                                             texture: texture,
+                                             size: size,
+                                             last_used: self.frame,
+                                         });
+                }
+                None => {
+                    if self.missing.insert(key.clone()) {
+                        warn!(LOGGER, "Unable to find sprite.";
+                            "path" => format!("{:?}", key.0), "rsiref" => format!("{:?}", key.1));
                     }
                 }
             }
         }
+
+        self.enforce_texture_budget();
+    }
+
+    fn enforce_texture_budget(&mut self) {
+        let budget = match self.texture_budget {
+            Some(budget) => budget,
+            None => return,
+        };
+
+        while self.texture_bytes > budget {
+            let frame = self.frame;
+            let victim = self.textures
+                .iter()
+                .filter(|&(_, cached)| cached.last_used < frame)
+                .min_by_key(|&(_, cached)| cached.last_used)
+                .map(|(key, _)| key.clone());
+
+            match victim {
+                Some(key) => {
+                    let cached = self.textures.remove(&key).unwrap();
+                    self.texture_bytes -= cached.size;
+                }
+                None => return,
+            }
+        }
+    }
+
+    /// Returns the amount of sprite textures and roughly how many bytes they use on the GPU.
+    pub fn texture_stats(&self) -> (usize, usize) {
+        (self.textures.len(), self.texture_bytes)
     }
 
     pub fn render(&self, c: Context, g: &mut G2d) {
@@ -73,13 +185,76 @@ impl Renderer {
                 let pos = position.read().unwrap().get_position();
                 let new_coords = pos.coordinates - self.camera;
                 // TODO: Don't clone() this shit you idiot.
-                let texture = self.textures
-                    .get(&(renderable.image.clone(), renderable.rsiref.clone()))
-                    .unwrap();
-                image(texture,
+                let cached = match self.textures
+                    .get(&(renderable.image.clone(), renderable.rsiref.clone())) {
+                    Some(cached) => cached,
+                    None => continue,
+                };
+                image(&cached.texture,
                       c.trans(new_coords[(0, 0)].round(), new_coords[(1, 0)].round()).transform,
                       g)
             }
         }
     }
+
+    /// Draws text with a font asset, with the top left corner at `position`.
+    ///
+    /// Returns `false` if the font isn't loaded.
+    pub fn draw_text(&self,
+                     font: &Path,
+                     text: &str,
+                     max_width: Option<u32>,
+                     position: [f64; 2],
+                     c: Context,
+                     g: &mut G2d)
+                     -> bool {
+        let layout = match self.fonts.get(font).and_then(|x| x.as_font()) {
+            Some(font) => font.layout(text, max_width),
+            None => return false,
+        };
+
+        self.draw_layout(font, &layout, position, c, g);
+        true
+    }
+
+    /// Draws text that was already laid out by a font, with the top left corner at `position`.
+    pub fn draw_layout(&self,
+                       font: &Path,
+                       layout: &TextLayout,
+                       position: [f64; 2],
+                       c: Context,
+                       g: &mut G2d) {
+        for glyph in &layout.glyphs {
+            if let Some(texture) = self.glyphs.get(&(font.to_owned(), glyph.rsiref.clone())) {
+                image(texture,
+                      c.trans(position[0] + glyph.x as f64, position[1] + glyph.y as f64)
+                          .transform,
+                      g);
+            }
+        }
+    }
+}
+
+/// Uploads a single icon of an RSI in the global asset manager as a texture.
+///
+/// Returns the texture and roughly how many bytes it uses.
+fn upload_icon(factory: &mut Factory,
+               path: &Path,
+               rsiref: &RsiRef)
+               -> Option<(Texture<Resources>, usize)> {
+    let asset = match get_asset(path) {
+        Some(asset) => asset,
+        None => return None,
+    };
+
+    let icon = asset.as_rsi()
+        .and_then(|rsi| rsi.get_stateid(&rsiref.state))
+        .and_then(|state| state.get_icon(rsiref.dir, rsiref.frame));
+
+    icon.and_then(|icon| {
+        let (width, height) = icon.dimensions();
+        Texture::from_image(factory, &icon.to_rgba(), &TextureSettings::new())
+            .ok()
+            .map(|texture| (texture, (width * height * 4) as usize))
+    })
 }
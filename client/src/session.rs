@@ -0,0 +1,91 @@
+//! Session tokens saved between runs, so the account password doesn't have to stay on disk.
+
+use toml::{Parser, Table, Value};
+use std::fs::File;
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+
+const HEADER: &'static str = "# The session the client logs back in with, managed by the client.\n\
+                              # Delete this file to log in with the password again.\n\n";
+
+/// A session token the server gave us for an account.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SavedSession {
+    /// The server the token is for, as `host:port`.
+    pub address: String,
+    pub account: String,
+    pub token: String,
+}
+
+impl SavedSession {
+    /// Returns whether the token can be used to log into `account` on the server at `address`.
+    pub fn is_for(&self, address: &str, account: &str) -> bool {
+        self.address == address && self.account.eq_ignore_ascii_case(account)
+    }
+}
+
+/// Where the session is saved, next to the client configuration file.
+pub fn path(config_path: &Path) -> PathBuf {
+    config_path.with_file_name("session.toml")
+}
+
+/// Load a saved session. A missing or invalid file is no session.
+pub fn load(path: &Path) -> Option<SavedSession> {
+    let mut data = String::new();
+    File::open(path).and_then(|mut file| file.read_to_string(&mut data)).ok()?;
+    let table = Parser::new(&data).parse()?;
+    let field = |name: &str| table.get(name).and_then(Value::as_str).map(String::from);
+    Some(SavedSession {
+        address: field("address")?,
+        account: field("account")?,
+        token: field("token")?,
+    })
+}
+
+pub fn save(path: &Path, session: &SavedSession) -> io::Result<()> {
+    let mut table = Table::new();
+    table.insert(String::from("address"), Value::String(session.address.clone()));
+    table.insert(String::from("account"), Value::String(session.account.clone()));
+    table.insert(String::from("token"), Value::String(session.token.clone()));
+    let mut file = File::create(path)?;
+    write!(file, "{}{}", HEADER, Value::Table(table))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::fs;
+    use std::process;
+
+    #[test]
+    fn test_save_and_load() {
+        let dir = env::temp_dir().join(format!("yasss_session_test_{}", process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = path(&dir.join("client.toml"));
+        assert_eq!(path, dir.join("session.toml"));
+        let _ = fs::remove_file(&path);
+        assert_eq!(load(&path), None);
+
+        let session = SavedSession {
+            address: String::from("localhost:4000"),
+            account: String::from("Alice"),
+            token: String::from("0123abcd \"quoted\""),
+        };
+        save(&path, &session).unwrap();
+        let loaded = load(&path).unwrap();
+        assert_eq!(loaded, session);
+        assert!(loaded.is_for("localhost:4000", "alice"));
+        assert!(!loaded.is_for("localhost:4001", "alice"));
+        assert!(!loaded.is_for("localhost:4000", "bob"));
+
+        File::create(&path).unwrap().write_all(b"address = \"localhost:4000\"\n").unwrap();
+        assert_eq!(load(&path), None);
+        File::create(&path).unwrap().write_all(b"token = 5\naccount = \"alice\"\n").unwrap();
+        assert_eq!(load(&path), None);
+        File::create(&path).unwrap().write_all(b"this isn't toml").unwrap();
+        assert_eq!(load(&path), None);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
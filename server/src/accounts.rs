@@ -0,0 +1,370 @@
+//! Player accounts, stored in a local TOML file, and the sessions they log back in with.
+
+use shared::net::{Challenge, Credentials};
+use shared::net::auth::{self, DIGEST_SIZE, MAX_ACCOUNT_LENGTH};
+use rand;
+use toml::{Parser, Table, Value};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::fs::File;
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Size of new salts, in bytes.
+pub const SALT_SIZE: usize = 16;
+
+const HEADER: &'static str = "# Player accounts, managed by the server with --add-account.\n\n";
+
+#[derive(Debug, Clone)]
+pub struct Account {
+    /// The account name as it was registered. Lookups ignore case.
+    pub name: String,
+    pub credentials: Credentials,
+}
+
+/// Every account, loaded from and saved to a single file.
+#[derive(Debug)]
+pub struct AccountStore {
+    path: PathBuf,
+    accounts: BTreeMap<String, Account>,
+}
+
+impl AccountStore {
+    /// Load the accounts at `path`. A missing file is an empty store.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<AccountStore, AccountError> {
+        let path = path.as_ref().to_owned();
+        let mut data = String::new();
+        match File::open(&path) {
+            Ok(mut file) => {
+                file.read_to_string(&mut data)?;
+            }
+            Err(ref error) if error.kind() == io::ErrorKind::NotFound => {}
+            Err(error) => return Err(error.into()),
+        }
+
+        let mut parser = Parser::new(&data);
+        let table = match parser.parse() {
+            Some(table) => table,
+            None => {
+                let errors: Vec<String> = parser.errors.iter().map(|e| e.desc.clone()).collect();
+                return Err(AccountError::Parse(errors.join(", ")));
+            }
+        };
+
+        let mut accounts = BTreeMap::new();
+        for (key, value) in table {
+            let account = read_account(&key, &value)
+                .map_err(|reason| AccountError::Invalid(key.clone(), reason))?;
+            accounts.insert(key_of(&account.name), account);
+        }
+
+        Ok(AccountStore {
+            path: path,
+            accounts: accounts,
+        })
+    }
+
+    pub fn get_path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn len(&self) -> usize {
+        self.accounts.len()
+    }
+
+    /// Look up an account, ignoring case.
+    pub fn get(&self, name: &str) -> Option<&Account> {
+        self.accounts.get(&key_of(name))
+    }
+
+    /// Register a new account. It isn't written to disk until `save` is called.
+    pub fn add(&mut self, name: &str, password: &str, iterations: u32) -> Result<(), AccountError> {
+        check_name(name).map_err(|reason| AccountError::Invalid(name.to_owned(), reason))?;
+        if self.get(name).is_some() {
+            return Err(AccountError::Exists(name.to_owned()));
+        }
+
+        let salt: [u8; SALT_SIZE] = rand::random();
+        let account = Account {
+            name: name.to_owned(),
+            credentials: Credentials::new(password, salt.to_vec(), iterations),
+        };
+        self.accounts.insert(key_of(name), account);
+        Ok(())
+    }
+
+    /// A made up challenge for an account that doesn't exist, so clients can't find out which
+    /// accounts do.
+    ///
+    /// The salt and iterations are derived from `secret` and the name, so they stay the same
+    /// between attempts. The iterations are picked from the ones real accounts use, which can
+    /// differ from the current setting for new accounts, `default_iterations`.
+    pub fn decoy_challenge(&self,
+                           secret: &[u8],
+                           name: &str,
+                           default_iterations: u32,
+                           nonce: Vec<u8>)
+                           -> Challenge {
+        let digest = auth::hmac(secret, key_of(name).as_bytes());
+        let iterations: Vec<u32> =
+            self.accounts.values().map(|x| x.credentials.iterations).collect();
+        let pick = digest[SALT_SIZE..SALT_SIZE + 4]
+            .iter()
+            .fold(0, |acc, x| acc << 8 | *x as usize);
+        Challenge {
+            salt: digest[..SALT_SIZE].to_vec(),
+            iterations: if iterations.is_empty() {
+                default_iterations
+            } else {
+                iterations[pick % iterations.len()]
+            },
+            nonce: nonce,
+        }
+    }
+
+    pub fn save(&self) -> Result<(), AccountError> {
+        let mut table = Table::new();
+        for account in self.accounts.values() {
+            let credentials = &account.credentials;
+            let mut entry = Table::new();
+            entry.insert(String::from("salt"), Value::String(to_hex(&credentials.salt)));
+            entry.insert(String::from("iterations"),
+                         Value::Integer(credentials.iterations as i64));
+            entry.insert(String::from("stored_key"),
+                         Value::String(to_hex(&credentials.stored_key)));
+            table.insert(account.name.clone(), Value::Table(entry));
+        }
+
+        let mut file = File::create(&self.path)?;
+        write!(file, "{}{}", HEADER, Value::Table(table))?;
+        Ok(())
+    }
+}
+
+/// Account names are 1 to 64 ASCII letters, digits, `_` and `-`, so they are safe as TOML keys.
+pub fn check_name(name: &str) -> Result<(), String> {
+    if name.is_empty() || name.len() > MAX_ACCOUNT_LENGTH {
+        return Err(format!("account names must be 1 to {} characters long", MAX_ACCOUNT_LENGTH));
+    }
+    if !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-') {
+        return Err(String::from("account names can only contain letters, digits, _ and -"));
+    }
+    Ok(())
+}
+
+fn key_of(name: &str) -> String {
+    name.to_ascii_lowercase()
+}
+
+fn read_account(name: &str, value: &Value) -> Result<Account, String> {
+    check_name(name)?;
+    let table = value.as_table().ok_or("expected a table")?;
+    let salt = table.get("salt")
+        .and_then(Value::as_str)
+        .and_then(from_hex)
+        .ok_or("expected `salt` as a hex string")?;
+    let iterations = table.get("iterations")
+        .and_then(Value::as_integer)
+        .and_then(|x| if x > 0 && x <= ::std::u32::MAX as i64 {
+            Some(x as u32)
+        } else {
+            None
+        })
+        .ok_or("expected `iterations` as a positive integer")?;
+    let stored_key = table.get("stored_key")
+        .and_then(Value::as_str)
+        .and_then(from_hex)
+        .and_then(|key| if key.len() == DIGEST_SIZE { Some(key) } else { None })
+        .ok_or("expected `stored_key` as a 32 byte hex string")?;
+
+    let mut key = [0; DIGEST_SIZE];
+    key.copy_from_slice(&stored_key);
+    Ok(Account {
+        name: name.to_owned(),
+        credentials: Credentials {
+            salt: salt,
+            iterations: iterations,
+            stored_key: key,
+        },
+    })
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|x| format!("{:02x}", x)).collect()
+}
+
+fn from_hex(hex: &str) -> Option<Vec<u8>> {
+    // `from_str_radix` would also take a sign.
+    if hex.len() % 2 != 0 || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        return None;
+    }
+    (0..hex.len() / 2).map(|i| u8::from_str_radix(&hex[i * 2..i * 2 + 2], 16).ok()).collect()
+}
+
+/// Tokens handed out to logged in players, so they can reconnect without their password.
+#[derive(Debug)]
+pub struct Sessions {
+    lifetime: Duration,
+    /// The account and expiry time of every token.
+    tokens: Mutex<HashMap<String, (String, Instant)>>,
+}
+
+impl Sessions {
+    pub fn new(lifetime: Duration) -> Sessions {
+        Sessions {
+            lifetime: lifetime,
+            tokens: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Start a session for an account, returning its token.
+    pub fn create(&self, account: &str) -> String {
+        let token = to_hex(&rand::random::<[u8; 32]>());
+        let mut tokens = self.tokens.lock().unwrap();
+        let now = Instant::now();
+        tokens.retain(|_, &mut (_, expires)| expires > now);
+        tokens.insert(token.clone(), (account.to_owned(), now + self.lifetime));
+        token
+    }
+
+    /// Returns the account a token belongs to, if it hasn't expired.
+    pub fn check(&self, token: &str) -> Option<String> {
+        let tokens = self.tokens.lock().unwrap();
+        match tokens.get(token) {
+            Some(&(ref account, expires)) if expires > Instant::now() => Some(account.clone()),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum AccountError {
+    Io(io::Error),
+    /// The accounts file isn't valid TOML.
+    Parse(String),
+    /// An account or account name is invalid.
+    Invalid(String, String),
+    /// An account with that name already exists.
+    Exists(String),
+}
+
+impl From<io::Error> for AccountError {
+    fn from(error: io::Error) -> AccountError {
+        AccountError::Io(error)
+    }
+}
+
+impl fmt::Display for AccountError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            AccountError::Io(ref error) => write!(f, "{}", error),
+            AccountError::Parse(ref errors) => write!(f, "invalid TOML: {}", errors),
+            AccountError::Invalid(ref name, ref reason) => {
+                write!(f, "account `{}` is invalid: {}", name, reason)
+            }
+            AccountError::Exists(ref name) => write!(f, "account `{}` already exists", name),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::fs;
+    use std::process;
+
+    #[test]
+    fn test_check_name() {
+        assert!(check_name("alice").is_ok());
+        assert!(check_name("Alice_the-2nd").is_ok());
+        assert!(check_name(&"a".repeat(MAX_ACCOUNT_LENGTH)).is_ok());
+        assert!(check_name("").is_err());
+        assert!(check_name(&"a".repeat(MAX_ACCOUNT_LENGTH + 1)).is_err());
+        assert!(check_name("alice smith").is_err());
+        assert!(check_name("alice.smith").is_err());
+        assert!(check_name("\"alice\"").is_err());
+        assert!(check_name("alicé").is_err());
+    }
+
+    #[test]
+    fn test_hex() {
+        assert_eq!(to_hex(&[]), "");
+        assert_eq!(to_hex(&[0, 1, 0xab, 0xff]), "0001abff");
+        assert_eq!(from_hex("0001abff"), Some(vec![0, 1, 0xab, 0xff]));
+        assert_eq!(from_hex("0001ABFF"), Some(vec![0, 1, 0xab, 0xff]));
+        assert_eq!(from_hex(""), Some(vec![]));
+        assert_eq!(from_hex("abc"), None);
+        assert_eq!(from_hex("zz"), None);
+        assert_eq!(from_hex("+1"), None);
+        assert_eq!(from_hex("éé"), None);
+    }
+
+    #[test]
+    fn test_save_and_load() {
+        let dir = env::temp_dir().join(format!("yasss_accounts_test_{}", process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("accounts.toml");
+        let _ = fs::remove_file(&path);
+
+        let mut store = AccountStore::load(&path).unwrap();
+        assert_eq!(store.len(), 0);
+        store.add("Alice", "hunter2", 10).unwrap();
+        store.add("bob", "swordfish", 20).unwrap();
+        match store.add("ALICE", "other", 10) {
+            Err(AccountError::Exists(_)) => {}
+            result => panic!("expected an Exists error, got {:?}", result),
+        }
+        match store.add("bad name", "other", 10) {
+            Err(AccountError::Invalid(..)) => {}
+            result => panic!("expected an Invalid error, got {:?}", result),
+        }
+        store.save().unwrap();
+
+        let loaded = AccountStore::load(&path).unwrap();
+        assert_eq!(loaded.len(), 2);
+        let alice = loaded.get("alice").unwrap();
+        assert_eq!(alice.name, "Alice");
+        assert_eq!(alice.credentials, store.get("Alice").unwrap().credentials);
+        assert_eq!(loaded.get("BOB").unwrap().credentials.iterations, 20);
+        assert!(loaded.get("carol").is_none());
+
+        File::create(&path).unwrap().write_all(b"[alice]\nsalt = \"00\"\n").unwrap();
+        match AccountStore::load(&path) {
+            Err(AccountError::Invalid(..)) => {}
+            result => panic!("expected an Invalid error, got {:?}", result),
+        }
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_decoy_challenge() {
+        let mut store = AccountStore::load("/nonexistent/accounts.toml").unwrap();
+        let decoy = store.decoy_challenge(b"secret", "nobody", 123, vec![1]);
+        assert_eq!(decoy.iterations, 123);
+        assert_eq!(decoy.salt.len(), SALT_SIZE);
+
+        // Stays the same between attempts, whatever the current setting.
+        store.add("alice", "hunter2", 10).unwrap();
+        let decoy = store.decoy_challenge(b"secret", "nobody", 500, vec![1]);
+        assert_eq!(decoy.iterations, 10);
+        assert_eq!(decoy, store.decoy_challenge(b"secret", "NOBODY", 600, vec![1]));
+        assert!(decoy != store.decoy_challenge(b"other secret", "nobody", 500, vec![1]));
+    }
+
+    #[test]
+    fn test_sessions() {
+        let sessions = Sessions::new(Duration::from_secs(3600));
+        let token = sessions.create("alice");
+        assert_eq!(sessions.check(&token), Some(String::from("alice")));
+        assert!(sessions.check("not a token").is_none());
+        assert!(token != sessions.create("alice"));
+
+        let expired = Sessions::new(Duration::from_secs(0));
+        let token = expired.create("alice");
+        assert!(expired.check(&token).is_none());
+    }
+}
@@ -0,0 +1,709 @@
+//! Bans and the whitelist, stored in a local TOML file.
+//!
+//! Both are made of targets written as `account:<name>`, `nickname:<nickname>` or
+//! `ip:<address>[/<prefix length>]`.
+
+use shared::net::nickname;
+use toml::{Parser, Table, Value};
+use std::fmt;
+use std::collections::hash_map::DefaultHasher;
+use std::fs::File;
+use std::hash::{Hash, Hasher};
+use std::io::{self, Read, Write};
+use std::net::IpAddr;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use logs::LOGGER;
+
+const HEADER: &'static str = "\
+    # Bans and the whitelist, managed by the server with --ban and --unban.\n\
+    # Targets are \"account:<name>\", \"nickname:<nickname>\" or \"ip:<address>[/<prefix>]\".\n\
+    # Times are in seconds since the Unix epoch. Bans without `expires` are permanent.\n\n";
+
+/// Who or what a ban or whitelist entry applies to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Target {
+    /// An account, ignoring case.
+    Account(String),
+    /// A nickname, compared like nicknames on the server are.
+    Nickname(String),
+    Address(Cidr),
+}
+
+impl Target {
+    pub fn parse(text: &str) -> Result<Target, String> {
+        let mut parts = text.splitn(2, ':');
+        let kind = parts.next().unwrap();
+        let value = match parts.next() {
+            Some(value) if !value.is_empty() => value,
+            _ => return Err(format!("expected `<kind>:<value>`, got `{}`", text)),
+        };
+        match kind {
+            "account" => Ok(Target::Account(value.to_owned())),
+            "nickname" => Ok(Target::Nickname(value.to_owned())),
+            "ip" => Ok(Target::Address(Cidr::parse(value)?)),
+            _ => {
+                Err(format!("unknown target kind `{}`, expected account, nickname or ip", kind))
+            }
+        }
+    }
+
+    /// Whether two targets refer to the same thing, ignoring case where matching does.
+    pub fn is_same(&self, other: &Target) -> bool {
+        match (self, other) {
+            (&Target::Account(ref a), &Target::Account(ref b)) => a.eq_ignore_ascii_case(b),
+            (&Target::Nickname(ref a), &Target::Nickname(ref b)) => {
+                nickname::key(a) == nickname::key(b)
+            }
+            (a, b) => a == b,
+        }
+    }
+
+    pub fn matches(&self, visitor: &Visitor) -> bool {
+        match *self {
+            Target::Account(ref name) => {
+                visitor.account.map_or(false, |account| account.eq_ignore_ascii_case(name))
+            }
+            Target::Nickname(ref name) => nickname::key(name) == nickname::key(visitor.nickname),
+            Target::Address(ref cidr) => cidr.contains(visitor.address),
+        }
+    }
+}
+
+impl fmt::Display for Target {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Target::Account(ref name) => write!(f, "account:{}", name),
+            Target::Nickname(ref name) => write!(f, "nickname:{}", name),
+            Target::Address(ref cidr) => write!(f, "ip:{}", cidr),
+        }
+    }
+}
+
+/// An IP address range, like `10.0.0.0/8`. A single address is a range of one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cidr {
+    address: IpAddr,
+    prefix: u8,
+}
+
+impl Cidr {
+    pub fn parse(text: &str) -> Result<Cidr, String> {
+        let mut parts = text.splitn(2, '/');
+        let address: IpAddr = parts.next()
+            .unwrap()
+            .parse()
+            .map_err(|_| format!("`{}` is not a valid IP address", text))?;
+        let bits = octets(address).len() as u8 * 8;
+        let prefix = match parts.next() {
+            Some(prefix) => {
+                match prefix.parse() {
+                    Ok(prefix) if prefix <= bits => prefix,
+                    _ => return Err(format!("expected a prefix length from 0 to {}", bits)),
+                }
+            }
+            None => bits,
+        };
+        Ok(Cidr {
+            address: address,
+            prefix: prefix,
+        })
+    }
+
+    pub fn contains(&self, address: IpAddr) -> bool {
+        let range = octets(self.address);
+        let address = octets(unmap(address));
+        if range.len() != address.len() {
+            return false;
+        }
+
+        let whole = (self.prefix / 8) as usize;
+        let rest = self.prefix % 8;
+        range[..whole] == address[..whole] &&
+        (rest == 0 || (range[whole] ^ address[whole]) & (0xff << (8 - rest)) == 0)
+    }
+}
+
+impl fmt::Display for Cidr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.prefix as usize == octets(self.address).len() * 8 {
+            write!(f, "{}", self.address)
+        } else {
+            write!(f, "{}/{}", self.address, self.prefix)
+        }
+    }
+}
+
+fn octets(address: IpAddr) -> Vec<u8> {
+    match address {
+        IpAddr::V4(address) => address.octets().to_vec(),
+        IpAddr::V6(address) => address.octets().to_vec(),
+    }
+}
+
+/// Turn IPv4 addresses mapped into IPv6 back into IPv4, so IPv4 ranges match them.
+fn unmap(address: IpAddr) -> IpAddr {
+    if let IpAddr::V6(v6) = address {
+        let segments = v6.segments();
+        if segments[..5].iter().all(|x| *x == 0) && segments[5] == 0xffff {
+            let octets = v6.octets();
+            return IpAddr::from([octets[12], octets[13], octets[14], octets[15]]);
+        }
+    }
+    address
+}
+
+/// Someone connecting, to check against bans and the whitelist.
+#[derive(Debug, Clone, Copy)]
+pub struct Visitor<'a> {
+    pub address: IpAddr,
+    /// The nickname asked for in the handshake.
+    pub nickname: &'a str,
+    /// The account logged into, if any.
+    pub account: Option<&'a str>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Ban {
+    pub target: Target,
+    /// Shown to the banned player.
+    pub reason: String,
+    /// Who issued the ban.
+    pub admin: String,
+    /// When the ban was issued, in seconds since the Unix epoch.
+    pub issued: u64,
+    /// When the ban ends, in seconds since the Unix epoch. `None` if it is permanent.
+    pub expires: Option<u64>,
+}
+
+impl Ban {
+    pub fn is_active(&self, now: u64) -> bool {
+        self.expires.map_or(true, |expires| expires > now)
+    }
+
+    /// The message shown to the banned player, with the reason and expiry.
+    pub fn message(&self, now: u64) -> String {
+        let mut message = String::from("You are banned from this server");
+        if !self.reason.is_empty() {
+            message += &format!(" ({})", self.reason);
+        }
+        match self.expires {
+            Some(expires) => {
+                message += &format!(". The ban expires in {}.",
+                                    describe_duration(expires.saturating_sub(now)))
+            }
+            None => message += ". The ban is permanent.",
+        }
+        message
+    }
+}
+
+/// Every ban and the whitelist, loaded from and saved to a single file.
+#[derive(Debug)]
+pub struct BanList {
+    path: PathBuf,
+    /// A hash of the file as of loading or saving it, `None` if there was no file.
+    contents: Option<u64>,
+    bans: Vec<Ban>,
+    whitelist: Vec<Target>,
+}
+
+impl BanList {
+    /// Load the bans at `path`. A missing file is an empty list.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<BanList, BanError> {
+        let path = path.as_ref().to_owned();
+        let data = read(&path)?;
+        BanList::parse(path, data)
+    }
+
+    /// Parse the contents of the file at `path`, `None` if it doesn't exist.
+    fn parse(path: PathBuf, data: Option<String>) -> Result<BanList, BanError> {
+        let contents = data.as_ref().map(|data| hash(data));
+        let data = data.unwrap_or_default();
+        let mut parser = Parser::new(&data);
+        let table = match parser.parse() {
+            Some(table) => table,
+            None => {
+                let errors: Vec<String> = parser.errors.iter().map(|e| e.desc.clone()).collect();
+                return Err(BanError::Parse(errors.join(", ")));
+            }
+        };
+
+        let bans = match table.get("bans") {
+            Some(&Value::Array(ref bans)) => {
+                bans.iter()
+                    .enumerate()
+                    .map(|(i, ban)| read_ban(ban).map_err(|e| format!("ban #{}: {}", i + 1, e)))
+                    .collect::<Result<_, _>>()
+                    .map_err(BanError::Invalid)?
+            }
+            Some(_) => return Err(BanError::Invalid(String::from("`bans` must be an array"))),
+            None => Vec::new(),
+        };
+        let whitelist = match table.get("whitelist") {
+            Some(&Value::Array(ref entries)) => {
+                entries.iter()
+                    .map(|entry| {
+                        entry.as_str()
+                            .ok_or_else(|| String::from("expected a string"))
+                            .and_then(Target::parse)
+                            .map_err(|e| format!("whitelist: {}", e))
+                    })
+                    .collect::<Result<_, _>>()
+                    .map_err(BanError::Invalid)?
+            }
+            Some(_) => {
+                return Err(BanError::Invalid(String::from("`whitelist` must be an array")))
+            }
+            None => Vec::new(),
+        };
+
+        Ok(BanList {
+            path: path,
+            contents: contents,
+            bans: bans,
+            whitelist: whitelist,
+        })
+    }
+
+    pub fn get_path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn get_bans(&self) -> &[Ban] {
+        &self.bans
+    }
+
+    pub fn get_whitelist(&self) -> &[Target] {
+        &self.whitelist
+    }
+
+    /// Returns the active ban matching a visitor, if any.
+    pub fn check(&self, visitor: &Visitor, now: u64) -> Option<&Ban> {
+        self.bans.iter().find(|ban| ban.is_active(now) && ban.target.matches(visitor))
+    }
+
+    pub fn is_whitelisted(&self, visitor: &Visitor) -> bool {
+        self.whitelist.iter().any(|target| target.matches(visitor))
+    }
+
+    /// Returns whether the whitelist lets a visitor in so far.
+    ///
+    /// Until the visitor logged in, the account it asked for is ignored, so nobody can find out
+    /// which accounts are whitelisted. Visitors who asked for one are let through to log in,
+    /// and have to be checked again once they did.
+    pub fn whitelist_admits(&self, visitor: &Visitor, verified: bool) -> bool {
+        if verified {
+            return self.is_whitelisted(visitor);
+        }
+        visitor.account.is_some() || self.is_whitelisted(&Visitor { account: None, ..*visitor })
+    }
+
+    /// Add a ban, dropping any that have expired. It isn't written to disk until `save` is
+    /// called.
+    pub fn add(&mut self, ban: Ban) {
+        let now = ban.issued;
+        self.bans.retain(|ban| ban.is_active(now));
+        self.bans.push(ban);
+    }
+
+    /// Remove every ban on `target`, returning how many there were.
+    pub fn remove(&mut self, target: &Target) -> usize {
+        let before = self.bans.len();
+        self.bans.retain(|ban| !ban.target.is_same(target));
+        before - self.bans.len()
+    }
+
+    pub fn save(&mut self) -> Result<(), BanError> {
+        let mut table = Table::new();
+        let whitelist = self.whitelist.iter().map(|x| Value::String(x.to_string())).collect();
+        table.insert(String::from("whitelist"), Value::Array(whitelist));
+        let bans = self.bans.iter().map(|ban| Value::Table(write_ban(ban))).collect();
+        table.insert(String::from("bans"), Value::Array(bans));
+
+        let data = format!("{}{}", HEADER, Value::Table(table));
+        File::create(&self.path)?.write_all(data.as_bytes())?;
+        self.contents = Some(hash(&data));
+        Ok(())
+    }
+}
+
+/// Check the bans file for changes every `interval` on a background thread, swapping in the
+/// new bans when it changed. If it is invalid, the old bans are kept until it is fixed.
+pub fn watch(bans: Arc<Mutex<BanList>>, interval: Duration) -> io::Result<()> {
+    let (path, mut known) = {
+        let bans = bans.lock().unwrap();
+        (bans.path.clone(), bans.contents)
+    };
+    thread::Builder::new()
+        .name(String::from("bans"))
+        .spawn(move || loop {
+            thread::sleep(interval);
+            match reload_if_changed(&bans, &path, &mut known) {
+                Ok(true) => {
+                    info!(LOGGER, "Reloaded bans."; "count" => bans.lock().unwrap().bans.len())
+                }
+                Ok(false) => {}
+                Err(error) => {
+                    error!(LOGGER, "Failed to reload bans, keeping the old ones.";
+                        "path" => format!("{:?}", path), "error" => format!("{}", error));
+                }
+            }
+        })?;
+    Ok(())
+}
+
+/// Load the file at `path` into `bans` if it changed since `known`, returning whether it did.
+///
+/// Changes are found by hashing the whole file, since modification times can be too coarse
+/// to tell quick edits apart. The file is read without holding the lock, so checking players
+/// isn't held up.
+fn reload_if_changed(bans: &Mutex<BanList>,
+                     path: &Path,
+                     known: &mut Option<u64>)
+                     -> Result<bool, BanError> {
+    let data = read(path)?;
+    let current = data.as_ref().map(|data| hash(data));
+    if current == *known {
+        return Ok(false);
+    }
+    // Remember the hash even if the file is invalid, so it isn't parsed again until it's fixed.
+    *known = current;
+    let loaded = BanList::parse(path.to_owned(), data)?;
+    *bans.lock().unwrap() = loaded;
+    Ok(true)
+}
+
+/// Read the file at `path`, `None` if it doesn't exist.
+fn read(path: &Path) -> Result<Option<String>, BanError> {
+    let mut data = String::new();
+    match File::open(path) {
+        Ok(mut file) => {
+            file.read_to_string(&mut data)?;
+            Ok(Some(data))
+        }
+        Err(ref error) if error.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(error) => Err(error.into()),
+    }
+}
+
+fn hash(data: &str) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    data.hash(&mut hasher);
+    hasher.finish()
+}
+
+fn read_ban(value: &Value) -> Result<Ban, String> {
+    let table = value.as_table().ok_or("expected a table")?;
+    let target = table.get("target")
+        .and_then(Value::as_str)
+        .ok_or("expected `target` as a string")
+        .map_err(String::from)
+        .and_then(Target::parse)?;
+    let get_string = |key: &str| match table.get(key) {
+        Some(&Value::String(ref value)) => Ok(value.clone()),
+        None => Ok(String::new()),
+        Some(_) => Err(format!("expected `{}` as a string", key)),
+    };
+    let get_time = |key: &str| match table.get(key) {
+        Some(&Value::Integer(value)) if value >= 0 => Ok(Some(value as u64)),
+        None => Ok(None),
+        Some(_) => Err(format!("expected `{}` as a positive integer", key)),
+    };
+
+    Ok(Ban {
+        target: target,
+        reason: get_string("reason")?,
+        admin: get_string("admin")?,
+        issued: get_time("issued")?.unwrap_or(0),
+        expires: get_time("expires")?,
+    })
+}
+
+fn write_ban(ban: &Ban) -> Table {
+    let mut table = Table::new();
+    table.insert(String::from("target"), Value::String(ban.target.to_string()));
+    table.insert(String::from("reason"), Value::String(ban.reason.clone()));
+    table.insert(String::from("admin"), Value::String(ban.admin.clone()));
+    table.insert(String::from("issued"), Value::Integer(ban.issued as i64));
+    if let Some(expires) = ban.expires {
+        table.insert(String::from("expires"), Value::Integer(expires as i64));
+    }
+    table
+}
+
+/// The current time, in seconds since the Unix epoch.
+pub fn now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|x| x.as_secs()).unwrap_or(0)
+}
+
+/// Parse a duration like `90`, `30m`, `12h`, `7d` or `2w` into seconds.
+pub fn parse_duration(text: &str) -> Result<u64, String> {
+    let (number, unit) = match text.find(|c: char| !c.is_ascii_digit()) {
+        Some(index) => text.split_at(index),
+        None => (text, "s"),
+    };
+    let multiplier = match unit {
+        "s" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        "d" => 24 * 60 * 60,
+        "w" => 7 * 24 * 60 * 60,
+        _ => return Err(format!("unknown unit `{}`, expected s, m, h, d or w", unit)),
+    };
+    number.parse::<u64>()
+        .ok()
+        .and_then(|number| number.checked_mul(multiplier))
+        .ok_or_else(|| format!("`{}` is not a valid duration", text))
+}
+
+/// Describe a number of seconds in the largest unit that fits, rounded, like `3 hours`.
+fn describe_duration(seconds: u64) -> String {
+    let units = [(7 * 24 * 60 * 60, "week"), (24 * 60 * 60, "day"), (60 * 60, "hour"),
+                 (60, "minute")];
+    let (count, unit) = units.iter()
+        .find(|&&(size, _)| seconds >= size)
+        .map(|&(size, unit)| ((seconds + size / 2) / size, unit))
+        .unwrap_or((seconds, "second"));
+    if count == 1 {
+        format!("1 {}", unit)
+    } else {
+        format!("{} {}s", count, unit)
+    }
+}
+
+#[derive(Debug)]
+pub enum BanError {
+    Io(io::Error),
+    /// The bans file isn't valid TOML.
+    Parse(String),
+    /// A ban or whitelist entry is invalid.
+    Invalid(String),
+}
+
+impl From<io::Error> for BanError {
+    fn from(error: io::Error) -> BanError {
+        BanError::Io(error)
+    }
+}
+
+impl fmt::Display for BanError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            BanError::Io(ref error) => write!(f, "{}", error),
+            BanError::Parse(ref errors) => write!(f, "invalid TOML: {}", errors),
+            BanError::Invalid(ref reason) => write!(f, "{}", reason),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::fs;
+    use std::process;
+
+    fn ip(text: &str) -> IpAddr {
+        text.parse().unwrap()
+    }
+
+    fn contains(range: &str, address: &str) -> bool {
+        Cidr::parse(range).unwrap().contains(ip(address))
+    }
+
+    #[test]
+    fn test_cidr() {
+        assert!(contains("10.1.2.3", "10.1.2.3"));
+        assert!(!contains("10.1.2.3", "10.1.2.4"));
+        assert!(contains("10.1.2.3/32", "10.1.2.3"));
+        assert!(!contains("10.1.2.3/32", "10.1.2.2"));
+        assert!(contains("0.0.0.0/0", "203.0.113.9"));
+        assert!(contains("10.0.0.0/8", "10.255.0.1"));
+        assert!(!contains("10.0.0.0/8", "11.0.0.1"));
+
+        // 172.16.0.0/13 covers 172.16.0.0 to 172.23.255.255.
+        assert!(contains("172.16.0.0/13", "172.16.0.0"));
+        assert!(contains("172.16.0.0/13", "172.23.255.255"));
+        assert!(!contains("172.16.0.0/13", "172.24.0.0"));
+        assert!(!contains("172.16.0.0/13", "172.15.255.255"));
+
+        assert!(contains("2001:db8::/32", "2001:db8:1::1"));
+        assert!(!contains("2001:db8::/32", "2001:db9::1"));
+        assert!(contains("::/0", "::1"));
+        assert!(contains("::1/128", "::1"));
+        // Address families never match each other, except for mapped IPv4 addresses.
+        assert!(!contains("0.0.0.0/0", "::1"));
+        assert!(!contains("::/0", "127.0.0.1"));
+        assert!(contains("192.168.0.0/16", "::ffff:192.168.1.1"));
+        assert!(!contains("192.168.0.0/16", "::ffff:192.169.1.1"));
+
+        assert_eq!(Cidr::parse("10.0.0.0/8").unwrap().to_string(), "10.0.0.0/8");
+        assert_eq!(Cidr::parse("10.0.0.1/32").unwrap().to_string(), "10.0.0.1");
+        assert_eq!(Cidr::parse("::1").unwrap().to_string(), "::1");
+        assert!(Cidr::parse("10.0.0.0/33").is_err());
+        assert!(Cidr::parse("::/129").is_err());
+        assert!(Cidr::parse("10.0.0.0/").is_err());
+        assert!(Cidr::parse("10.0.0/8").is_err());
+        assert!(Cidr::parse("localhost").is_err());
+    }
+
+    #[test]
+    fn test_target() {
+        assert_eq!(Target::parse("account:Alice"),
+                   Ok(Target::Account(String::from("Alice"))));
+        assert_eq!(Target::parse("nickname:Bob:2"),
+                   Ok(Target::Nickname(String::from("Bob:2"))));
+        assert_eq!(Target::parse("ip:10.0.0.0/8").unwrap().to_string(), "ip:10.0.0.0/8");
+        assert!(Target::parse("account").is_err());
+        assert!(Target::parse("account:").is_err());
+        assert!(Target::parse("email:alice@example.com").is_err());
+        assert!(Target::parse("ip:nowhere").is_err());
+        assert!(Target::parse("").is_err());
+
+        let visitor = Visitor {
+            address: ip("10.0.0.1"),
+            nickname: "Bob",
+            account: Some("alice"),
+        };
+        assert!(Target::parse("account:ALICE").unwrap().matches(&visitor));
+        assert!(Target::parse("nickname:bob").unwrap().matches(&visitor));
+        assert!(Target::parse("ip:10.0.0.0/24").unwrap().matches(&visitor));
+        assert!(!Target::parse("ip:10.0.1.0/24").unwrap().matches(&visitor));
+        let guest = Visitor { account: None, ..visitor };
+        assert!(!Target::parse("account:alice").unwrap().matches(&guest));
+    }
+
+    #[test]
+    fn test_durations() {
+        assert_eq!(parse_duration("90"), Ok(90));
+        assert_eq!(parse_duration("90s"), Ok(90));
+        assert_eq!(parse_duration("30m"), Ok(30 * 60));
+        assert_eq!(parse_duration("12h"), Ok(12 * 60 * 60));
+        assert_eq!(parse_duration("7d"), Ok(7 * 24 * 60 * 60));
+        assert_eq!(parse_duration("2w"), Ok(14 * 24 * 60 * 60));
+        assert!(parse_duration("").is_err());
+        assert!(parse_duration("d").is_err());
+        assert!(parse_duration("5y").is_err());
+        assert!(parse_duration("-5m").is_err());
+        assert!(parse_duration("1.5h").is_err());
+        assert!(parse_duration("99999999999999999999w").is_err());
+
+        assert_eq!(describe_duration(0), "0 seconds");
+        assert_eq!(describe_duration(1), "1 second");
+        assert_eq!(describe_duration(59), "59 seconds");
+        assert_eq!(describe_duration(60), "1 minute");
+        assert_eq!(describe_duration(2 * 60 * 60), "2 hours");
+        // Rounded, not cut off.
+        assert_eq!(describe_duration(2 * 60 * 60 - 1), "2 hours");
+        assert_eq!(describe_duration(36 * 60 * 60), "2 days");
+        assert_eq!(describe_duration(7 * 24 * 60 * 60), "1 week");
+    }
+
+    #[test]
+    fn test_whitelist() {
+        let mut list = BanList::load(env::temp_dir().join("yasss_whitelist_test_missing.toml"))
+            .unwrap();
+        list.whitelist.push(Target::parse("account:alice").unwrap());
+        list.whitelist.push(Target::parse("nickname:Carol").unwrap());
+
+        let alice = Visitor {
+            address: ip("10.0.0.1"),
+            nickname: "Bob",
+            account: Some("alice"),
+        };
+        let mallory = Visitor { account: Some("mallory"), ..alice };
+        let guest = Visitor { account: None, ..alice };
+        assert!(list.whitelist_admits(&alice, true));
+        assert!(!list.whitelist_admits(&mallory, true));
+        assert!(!list.whitelist_admits(&guest, true));
+
+        // Before logging in, a listed and an unlisted account are treated the same.
+        assert!(list.whitelist_admits(&alice, false));
+        assert!(list.whitelist_admits(&mallory, false));
+        assert!(!list.whitelist_admits(&guest, false));
+        assert!(list.whitelist_admits(&Visitor { nickname: "carol", ..guest }, false));
+    }
+
+    fn ban(target: &str, reason: &str, expires: Option<u64>) -> Ban {
+        Ban {
+            target: Target::parse(target).unwrap(),
+            reason: String::from(reason),
+            admin: String::from("console"),
+            issued: 1000,
+            expires: expires,
+        }
+    }
+
+    #[test]
+    fn test_ban_message() {
+        let permanent = ban("nickname:Bob", "griefing", None);
+        assert!(permanent.is_active(u64::max_value()));
+        assert_eq!(permanent.message(2000),
+                   "You are banned from this server (griefing). The ban is permanent.");
+
+        let expiring = ban("nickname:Bob", "", Some(1000 + 3 * 60 * 60));
+        assert!(expiring.is_active(1000));
+        assert!(!expiring.is_active(1000 + 3 * 60 * 60));
+        assert_eq!(expiring.message(1000),
+                   "You are banned from this server. The ban expires in 3 hours.");
+    }
+
+    #[test]
+    fn test_save_and_reload() {
+        let dir = env::temp_dir().join(format!("yasss_bans_test_{}", process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("bans.toml");
+        let _ = fs::remove_file(&path);
+
+        let mut list = BanList::load(&path).unwrap();
+        assert!(list.get_bans().is_empty());
+        list.add(ban("ip:10.0.0.0/8", "spam", Some(5000)));
+        list.add(ban("account:alice", "", None));
+        list.whitelist.push(Target::parse("nickname:Carol").unwrap());
+        list.save().unwrap();
+
+        let loaded = BanList::load(&path).unwrap();
+        assert_eq!(loaded.get_bans(), list.get_bans());
+        assert_eq!(loaded.get_whitelist(), list.get_whitelist());
+        assert_eq!(loaded.contents, list.contents);
+
+        let visitor = Visitor {
+            address: ip("10.1.1.1"),
+            nickname: "Bob",
+            account: None,
+        };
+        assert_eq!(loaded.check(&visitor, 1000).unwrap().reason, "spam");
+        assert!(loaded.check(&visitor, 5000).is_none());
+        assert!(!loaded.is_whitelisted(&visitor));
+        assert!(loaded.is_whitelisted(&Visitor { nickname: "carol", ..visitor }));
+
+        let bans = Mutex::new(loaded);
+        let mut known = list.contents;
+        assert!(!reload_if_changed(&bans, &path, &mut known).unwrap());
+
+        // Saving right after loading is noticed, however coarse modification times are.
+        assert_eq!(list.remove(&Target::parse("account:ALICE").unwrap()), 1);
+        list.save().unwrap();
+        assert!(reload_if_changed(&bans, &path, &mut known).unwrap());
+        assert_eq!(bans.lock().unwrap().get_bans().len(), 1);
+        assert!(!reload_if_changed(&bans, &path, &mut known).unwrap());
+
+        // So are edits that keep the size of the file.
+        let mut data = String::new();
+        File::open(&path).unwrap().read_to_string(&mut data).unwrap();
+        File::create(&path).unwrap().write_all(data.replace("spam", "scam").as_bytes()).unwrap();
+        assert!(reload_if_changed(&bans, &path, &mut known).unwrap());
+        assert_eq!(bans.lock().unwrap().check(&visitor, 1000).unwrap().reason, "scam");
+
+        // An invalid file keeps the old bans, and isn't read again until it changes.
+        File::create(&path).unwrap().write_all(b"bans = [1, 2]\n").unwrap();
+        assert!(reload_if_changed(&bans, &path, &mut known).is_err());
+        assert_eq!(bans.lock().unwrap().get_bans().len(), 1);
+        assert!(!reload_if_changed(&bans, &path, &mut known).unwrap());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
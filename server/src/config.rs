@@ -0,0 +1,315 @@
+use shared::config::{Config, ConfigError, ConfigSection, FromValue, SectionReader};
+use shared::net::auth::MAX_ITERATIONS;
+use shared::net::handshake::{MAX_NICKNAME_LENGTH, MAX_VERSION_LENGTH};
+use shared::net::{NicknameRules, MAX_FRAME_SIZE};
+use toml::Value;
+
+/// The `[connection]` section of the server config.
+#[derive(Debug)]
+pub struct ConnectionConfig {
+    /// The TCP port to listen on.
+    pub port: u16,
+
+    /// Seconds a new connection has to finish the handshake.
+    pub handshake_timeout: u64,
+
+    /// Seconds between keepalive pings sent to each client.
+    pub ping_interval: u64,
+
+    /// Seconds without receiving anything before a client is disconnected.
+    pub idle_timeout: u64,
+
+    /// The largest packet accepted from a client, in bytes.
+    pub max_packet_size: usize,
+
+    /// The longest nickname accepted in a handshake, in bytes.
+    pub max_nickname_length: usize,
+
+    /// The longest client version string accepted in a handshake, in bytes.
+    pub max_version_length: usize,
+}
+
+impl Default for ConnectionConfig {
+    fn default() -> ConnectionConfig {
+        ConnectionConfig {
+            port: 40000,
+            handshake_timeout: 10,
+            ping_interval: 10,
+            idle_timeout: 30,
+            max_packet_size: MAX_FRAME_SIZE,
+            max_nickname_length: MAX_NICKNAME_LENGTH,
+            max_version_length: MAX_VERSION_LENGTH,
+        }
+    }
+}
+
+impl ConfigSection for ConnectionConfig {
+    fn name() -> &'static str {
+        "connection"
+    }
+
+    fn read(&mut self, reader: &SectionReader) -> Result<(), ConfigError> {
+        reader.read_range("port", &mut self.port, 1, 65535)?;
+        reader.read_range("handshake_timeout", &mut self.handshake_timeout, 1, 3600)?;
+        reader.read_range("ping_interval", &mut self.ping_interval, 1, 3600)?;
+        reader.read_range("idle_timeout", &mut self.idle_timeout, 1, 3600)?;
+        reader.read_range("max_packet_size", &mut self.max_packet_size, 256, 16 * 1024 * 1024)?;
+        reader.read_range("max_nickname_length",
+                        &mut self.max_nickname_length,
+                        1,
+                        MAX_NICKNAME_LENGTH)?;
+        reader.read_range("max_version_length",
+                        &mut self.max_version_length,
+                        1,
+                        MAX_VERSION_LENGTH)?;
+        if self.idle_timeout <= self.ping_interval {
+            return Err(reader.error("idle_timeout",
+                                    String::from("must be longer than ping_interval")));
+        }
+        Ok(())
+    }
+}
+
+/// The `[server]` section of the server config.
+#[derive(Debug)]
+pub struct ServerConfig {
+    /// The name shown to players polling the server.
+    pub name: String,
+
+    /// How many players can be connected at once.
+    pub max_players: u32,
+}
+
+impl Default for ServerConfig {
+    fn default() -> ServerConfig {
+        ServerConfig {
+            name: String::from("YASSS13RTWCF Server"),
+            max_players: 64,
+        }
+    }
+}
+
+impl ConfigSection for ServerConfig {
+    fn name() -> &'static str {
+        "server"
+    }
+
+    fn read(&mut self, reader: &SectionReader) -> Result<(), ConfigError> {
+        reader.read("name", &mut self.name)?;
+        reader.read_range("max_players", &mut self.max_players, 1, 10000)
+    }
+}
+
+/// What to do when a player connects with a nickname someone else is using.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DuplicateNicknames {
+    /// Refuse the connection.
+    Reject,
+    /// Add a number to the new player's nickname.
+    Rename,
+}
+
+impl FromValue for DuplicateNicknames {
+    fn from_value(value: &Value) -> Result<DuplicateNicknames, String> {
+        match value.as_str() {
+            Some("reject") => Ok(DuplicateNicknames::Reject),
+            Some("rename") => Ok(DuplicateNicknames::Rename),
+            _ => Err(String::from("expected \"reject\" or \"rename\"")),
+        }
+    }
+}
+
+/// The `[nicknames]` section of the server config.
+#[derive(Debug)]
+pub struct NicknameConfig {
+    pub rules: NicknameRules,
+    pub duplicates: DuplicateNicknames,
+}
+
+impl Default for NicknameConfig {
+    fn default() -> NicknameConfig {
+        NicknameConfig {
+            rules: NicknameRules::default(),
+            duplicates: DuplicateNicknames::Reject,
+        }
+    }
+}
+
+impl ConfigSection for NicknameConfig {
+    fn name() -> &'static str {
+        "nicknames"
+    }
+
+    fn read(&mut self, reader: &SectionReader) -> Result<(), ConfigError> {
+        // Characters take at least a byte each, so this keeps nicknames within the handshake.
+        let max = MAX_NICKNAME_LENGTH;
+        reader.read_range("min_length", &mut self.rules.min_length, 1, max)?;
+        reader.read_range("max_length", &mut self.rules.max_length, 1, max)?;
+        if self.rules.min_length > self.rules.max_length {
+            return Err(reader.error("min_length",
+                                    String::from("must not be more than max_length")));
+        }
+        reader.read("reserved", &mut self.rules.reserved)?;
+        reader.read("allow_unicode", &mut self.rules.allow_unicode)?;
+        reader.read("duplicates", &mut self.duplicates)
+    }
+}
+
+/// The `[accounts]` section of the server config.
+#[derive(Debug)]
+pub struct AccountsConfig {
+    /// Refuse players who don't log into an account.
+    pub required: bool,
+
+    /// The accounts file, relative to the directory of the config file.
+    pub file: String,
+
+    /// PBKDF2 iterations for new passwords.
+    pub iterations: u32,
+
+    /// Seconds a session token can be used to log back in.
+    pub session_lifetime: u64,
+}
+
+impl Default for AccountsConfig {
+    fn default() -> AccountsConfig {
+        AccountsConfig {
+            required: false,
+            file: String::from("accounts.toml"),
+            iterations: 10000,
+            session_lifetime: 24 * 60 * 60,
+        }
+    }
+}
+
+impl ConfigSection for AccountsConfig {
+    fn name() -> &'static str {
+        "accounts"
+    }
+
+    fn read(&mut self, reader: &SectionReader) -> Result<(), ConfigError> {
+        reader.read("required", &mut self.required)?;
+        reader.read("file", &mut self.file)?;
+        reader.read_range("iterations", &mut self.iterations, 1000, MAX_ITERATIONS)?;
+        reader.read_range("session_lifetime", &mut self.session_lifetime, 1, 365 * 24 * 60 * 60)
+    }
+}
+
+/// The `[bans]` section of the server config.
+#[derive(Debug)]
+pub struct BansConfig {
+    /// The bans file, relative to the directory of the config file.
+    pub file: String,
+
+    /// Only let in players on the whitelist.
+    pub whitelist: bool,
+
+    /// Seconds between checks for changes to the bans file.
+    pub reload_interval: u64,
+}
+
+impl Default for BansConfig {
+    fn default() -> BansConfig {
+        BansConfig {
+            file: String::from("bans.toml"),
+            whitelist: false,
+            reload_interval: 5,
+        }
+    }
+}
+
+impl ConfigSection for BansConfig {
+    fn name() -> &'static str {
+        "bans"
+    }
+
+    fn read(&mut self, reader: &SectionReader) -> Result<(), ConfigError> {
+        reader.read("file", &mut self.file)?;
+        reader.read("whitelist", &mut self.whitelist)?;
+        reader.read_range("reload_interval", &mut self.reload_interval, 1, 3600)
+    }
+}
+
+/// The `[assets]` section of the server config.
+#[derive(Debug, Default)]
+pub struct AssetsConfig {
+    /// Megabytes of decoded sprites and fonts to keep in memory, or 0 for no limit.
+    pub memory_budget: usize,
+}
+
+impl AssetsConfig {
+    /// The memory budget for the asset manager, in bytes.
+    pub fn memory_budget_bytes(&self) -> Option<usize> {
+        match self.memory_budget {
+            0 => None,
+            budget => Some(budget * 1024 * 1024),
+        }
+    }
+}
+
+impl ConfigSection for AssetsConfig {
+    fn name() -> &'static str {
+        "assets"
+    }
+
+    fn read(&mut self, reader: &SectionReader) -> Result<(), ConfigError> {
+        reader.read_range("memory_budget", &mut self.memory_budget, 0, 1024 * 1024)
+    }
+}
+
+/// The `[game]` section of the server config.
+#[derive(Debug)]
+pub struct GameConfig {
+    /// How many times a second the world is updated.
+    pub tick_rate: u32,
+
+    /// Seconds between tick time statistics in the log, or 0 to never log them.
+    pub stats_interval: u64,
+}
+
+impl Default for GameConfig {
+    fn default() -> GameConfig {
+        GameConfig {
+            tick_rate: 30,
+            stats_interval: 300,
+        }
+    }
+}
+
+impl ConfigSection for GameConfig {
+    fn name() -> &'static str {
+        "game"
+    }
+
+    fn read(&mut self, reader: &SectionReader) -> Result<(), ConfigError> {
+        reader.read_range("tick_rate", &mut self.tick_rate, 1, 1000)?;
+        reader.read_range("stats_interval", &mut self.stats_interval, 0, 24 * 60 * 60)
+    }
+}
+
+/// Every section of the server configuration.
+#[derive(Debug, Default)]
+pub struct Settings {
+    pub connection: ConnectionConfig,
+    pub server: ServerConfig,
+    pub nicknames: NicknameConfig,
+    pub accounts: AccountsConfig,
+    pub bans: BansConfig,
+    pub assets: AssetsConfig,
+    pub game: GameConfig,
+}
+
+impl Settings {
+    /// Reads every section out of a loaded config.
+    pub fn from_config(config: &Config) -> Result<Settings, ConfigError> {
+        Ok(Settings {
+            connection: config.section()?,
+            server: config.section()?,
+            nicknames: config.section()?,
+            accounts: config.section()?,
+            bans: config.section()?,
+            assets: config.section()?,
+            game: config.section()?,
+        })
+    }
+}
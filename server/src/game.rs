@@ -0,0 +1,338 @@
+//! The game loop, which advances the world in fixed time steps.
+
+use shared::entities::{World, WORLD};
+use shared::net::NetPacket;
+use net::{self, NetEvent};
+use config::GameConfig;
+use logs::LOGGER;
+use std::net::SocketAddr;
+use std::sync::RwLock;
+use std::sync::mpsc::{Receiver, TryRecvError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Once the loop is this many ticks behind, it skips them instead of trying to catch up.
+const MAX_TICKS_BEHIND: u32 = 10;
+
+/// Something that runs every tick, like moving things around or sending updates to clients.
+pub trait System {
+    /// A name for logs and statistics.
+    fn name(&self) -> &'static str;
+
+    fn run(&mut self, tick: &mut Tick);
+}
+
+/// What systems get to work with during a tick.
+pub struct Tick<'a> {
+    /// Ticks since the game loop started, starting at 0.
+    pub number: u64,
+
+    pub world: &'a RwLock<World>,
+
+    /// Everything that happened on the network since the last tick.
+    pub events: &'a [NetEvent],
+
+    outgoing: &'a mut Vec<(SocketAddr, NetPacket)>,
+}
+
+impl<'a> Tick<'a> {
+    /// Queue a packet for a client, sent once every system has run.
+    pub fn send(&mut self, address: SocketAddr, packet: NetPacket) {
+        self.outgoing.push((address, packet));
+    }
+}
+
+/// How long ticks took over a reporting period.
+#[derive(Debug, Clone, Default)]
+pub struct TickStats {
+    pub ticks: u64,
+
+    /// Ticks that took longer than the time step.
+    pub overruns: u64,
+
+    /// Ticks skipped because the loop fell too far behind.
+    pub skipped: u64,
+
+    pub total: Duration,
+    pub longest: Duration,
+
+    /// Time spent in each system, in the order they were added.
+    pub systems: Vec<Duration>,
+}
+
+impl TickStats {
+    /// Count a tick that took `elapsed`, returning whether it took longer than `period`.
+    pub fn record(&mut self, elapsed: Duration, period: Duration) -> bool {
+        self.ticks += 1;
+        self.total += elapsed;
+        if elapsed > self.longest {
+            self.longest = elapsed;
+        }
+        if elapsed > period {
+            self.overruns += 1;
+            return true;
+        }
+        false
+    }
+
+    pub fn average(&self) -> Duration {
+        if self.ticks == 0 {
+            return Duration::from_secs(0);
+        }
+        let nanos = nanos(self.total) / self.ticks;
+        Duration::new(nanos / 1_000_000_000, (nanos % 1_000_000_000) as u32)
+    }
+}
+
+/// Runs systems over `WORLD` at a fixed tick rate.
+pub struct Game {
+    systems: Vec<Box<System>>,
+    period: Duration,
+    stats_interval: Duration,
+    tick: u64,
+    stats: TickStats,
+}
+
+impl Game {
+    pub fn new(config: &GameConfig) -> Game {
+        Game {
+            systems: Vec::new(),
+            period: Duration::new(0, 1_000_000_000 / config.tick_rate),
+            stats_interval: Duration::from_secs(config.stats_interval),
+            tick: 0,
+            stats: TickStats::default(),
+        }
+    }
+
+    /// Add a system, which runs after the ones added before it.
+    pub fn add_system<S: System + 'static>(&mut self, system: S) {
+        self.systems.push(Box::new(system));
+        self.stats.systems.push(Duration::from_secs(0));
+    }
+
+    /// Tick until the network thread stops.
+    pub fn run(&mut self, events: Receiver<NetEvent>) {
+        info!(LOGGER, "Starting game loop.";
+            "tick_rate" => 1_000_000_000 / nanos(self.period), "systems" => self.systems.len());
+        let mut next_tick = Instant::now();
+        let mut last_report = Instant::now();
+        let mut overran = false;
+
+        loop {
+            let now = Instant::now();
+            if next_tick > now {
+                thread::sleep(next_tick - now);
+            }
+
+            let now = Instant::now();
+            if now > next_tick + self.period * MAX_TICKS_BEHIND {
+                let skipped = nanos(now - next_tick) / nanos(self.period);
+                warn!(LOGGER, "Game loop fell behind, skipping ticks."; "skipped" => skipped);
+                self.stats.skipped += skipped;
+                next_tick = Instant::now();
+            }
+
+            let mut received = Vec::new();
+            loop {
+                match events.try_recv() {
+                    Ok(event) => received.push(event),
+                    Err(TryRecvError::Empty) => break,
+                    Err(TryRecvError::Disconnected) => return,
+                }
+            }
+
+            let started = Instant::now();
+            let slowest = self.tick(&received);
+            let elapsed = started.elapsed();
+            // Only warn once per report, a slow server would otherwise flood the log.
+            if self.stats.record(elapsed, self.period) && !overran {
+                warn!(LOGGER, "Tick took longer than the time step.";
+                    "tick" => self.tick - 1, "ms" => millis(elapsed),
+                    "budget_ms" => millis(self.period), "slowest_system" => slowest);
+                overran = true;
+            }
+            next_tick += self.period;
+
+            if self.stats_interval > Duration::from_secs(0) &&
+               last_report.elapsed() >= self.stats_interval {
+                self.report();
+                last_report = Instant::now();
+                overran = false;
+            }
+        }
+    }
+
+    /// Run a single tick, returning the name of the system that took the longest.
+    fn tick(&mut self, events: &[NetEvent]) -> &'static str {
+        for event in events {
+            log_event(event);
+        }
+
+        let mut outgoing = Vec::new();
+        let mut slowest = ("", Duration::from_secs(0));
+        for (system, time) in self.systems.iter_mut().zip(self.stats.systems.iter_mut()) {
+            let started = Instant::now();
+            system.run(&mut Tick {
+                number: self.tick,
+                world: &WORLD,
+                events: events,
+                outgoing: &mut outgoing,
+            });
+            let elapsed = started.elapsed();
+            *time += elapsed;
+            if elapsed >= slowest.1 {
+                slowest = (system.name(), elapsed);
+            }
+        }
+        send(outgoing);
+        self.tick += 1;
+        slowest.0
+    }
+
+    /// Log the statistics of the current reporting period and start a new one.
+    fn report(&mut self) {
+        let stats = &self.stats;
+        let load = nanos(stats.average()) as f64 / nanos(self.period) as f64 * 100.0;
+        info!(LOGGER, "Tick statistics.";
+            "ticks" => stats.ticks, "average_ms" => millis(stats.average()),
+            "longest_ms" => millis(stats.longest), "load_percent" => load.round(),
+            "overruns" => stats.overruns, "skipped" => stats.skipped);
+        for (system, time) in self.systems.iter().zip(stats.systems.iter()) {
+            debug!(LOGGER, "System time."; "system" => system.name(), "ms" => millis(*time));
+        }
+
+        self.stats = TickStats {
+            systems: vec![Duration::from_secs(0); self.systems.len()],
+            ..TickStats::default()
+        };
+    }
+}
+
+fn log_event(event: &NetEvent) {
+    match *event {
+        NetEvent::Connected { address, ref nickname, ref account } => {
+            info!(LOGGER, "Client connected.";
+                "address" => format!("{}", address), "nickname" => nickname.clone(),
+                "account" => account.clone().unwrap_or_default());
+        }
+        NetEvent::Packet { address, ref packet } => {
+            debug!(LOGGER, "Received packet.";
+                "address" => format!("{}", address), "packet" => format!("{:?}", packet));
+        }
+        NetEvent::Disconnected { address, ref nickname, ref reason } => {
+            info!(LOGGER, "Client disconnected.";
+                "address" => format!("{}", address), "nickname" => nickname.clone(),
+                "reason" => reason.clone());
+        }
+    }
+}
+
+/// Send the packets queued during a tick.
+fn send(outgoing: Vec<(SocketAddr, NetPacket)>) {
+    if outgoing.is_empty() {
+        return;
+    }
+    let clients = net::CLIENTS.read().unwrap();
+    for (address, packet) in outgoing {
+        // The client may have left during the tick.
+        if let Some(client) = clients.get(&address) {
+            client.read().unwrap().send(packet);
+        }
+    }
+}
+
+fn nanos(duration: Duration) -> u64 {
+    duration.as_secs() * 1_000_000_000 + duration.subsec_nanos() as u64
+}
+
+fn millis(duration: Duration) -> f64 {
+    nanos(duration) as f64 / 1e6
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::mpsc::{self, Sender};
+
+    fn ms(millis: u64) -> Duration {
+        Duration::from_millis(millis)
+    }
+
+    /// Sleeps for a given time on some ticks, and stops the game after the last one.
+    struct Sleeper {
+        sleeps: Vec<Duration>,
+        ticks: Vec<u64>,
+        events: Option<Sender<NetEvent>>,
+    }
+
+    impl System for Sleeper {
+        fn name(&self) -> &'static str {
+            "sleeper"
+        }
+
+        fn run(&mut self, tick: &mut Tick) {
+            self.ticks.push(tick.number);
+            let index = self.ticks.len() - 1;
+            thread::sleep(self.sleeps[index]);
+            if self.ticks.len() == self.sleeps.len() {
+                self.events = None;
+            }
+        }
+    }
+
+    /// Run a game at 100 ticks a second until the sleeper is done.
+    fn run(sleeps: Vec<Duration>) -> Game {
+        let (sender, receiver) = mpsc::channel();
+        let mut game = Game::new(&GameConfig {
+            tick_rate: 100,
+            stats_interval: 0,
+        });
+        game.add_system(Sleeper {
+            sleeps: sleeps,
+            ticks: Vec::new(),
+            events: Some(sender),
+        });
+        game.run(receiver);
+        game
+    }
+
+    #[test]
+    fn test_tick_stats() {
+        let mut stats = TickStats::default();
+        assert_eq!(stats.average(), Duration::from_secs(0));
+
+        assert!(!stats.record(ms(10), ms(20)));
+        assert!(!stats.record(ms(20), ms(20)));
+        assert!(stats.record(ms(30), ms(20)));
+        assert_eq!(stats.ticks, 3);
+        assert_eq!(stats.overruns, 1);
+        assert_eq!(stats.total, ms(60));
+        assert_eq!(stats.longest, ms(30));
+        assert_eq!(stats.average(), ms(20));
+
+        stats.record(Duration::new(0, 1), ms(20));
+        assert_eq!(stats.average(), Duration::new(0, 15_000_000));
+    }
+
+    // Sleeps take at least as long as asked, but can take any amount longer on a busy machine,
+    // so the tests below only check what a late wake-up can't change.
+
+    #[test]
+    fn test_overruns() {
+        let game = run(vec![ms(0), ms(15), ms(0), ms(15)]);
+        assert_eq!(game.tick, 4);
+        assert_eq!(game.stats.ticks, 4);
+        assert!(game.stats.overruns >= 2);
+        assert!(game.stats.longest >= ms(15));
+        assert!(game.stats.systems[0] >= ms(30));
+    }
+
+    #[test]
+    fn test_skipped_ticks() {
+        // 20 ticks late, which is past the limit of 10 however fast the loop runs.
+        let game = run(vec![ms(200), ms(0), ms(0)]);
+        assert!(game.stats.skipped >= 10);
+        assert_eq!(game.stats.ticks, 3);
+        assert!(game.stats.overruns >= 1);
+    }
+}
@@ -10,43 +10,259 @@ extern crate tokio_io;
 extern crate tokio_core;
 extern crate futures;
 extern crate byteorder;
+extern crate rand;
+extern crate nalgebra;
 
+mod accounts;
+mod bans;
+mod config;
+mod game;
 mod net;
 mod logs;
+mod replication;
 
-use shared::config;
-use shared::assets::load_from_dir;
+use shared::config::{CONFIG, Layer};
+use shared::helpers::args::flag_value;
+use shared::assets::{ASSET_MANAGER, load_from_dir};
+use shared::locate::{self, Locator};
+use shared::entities::{WORLD, make_builder};
+use shared::entities::components::{PositionComponent, Positional};
 use std::env;
+use std::io;
+use std::path::Path;
+use std::process;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
 use logs::LOGGER;
+use config::Settings;
+use accounts::AccountStore;
+use bans::{Ban, BanList, Target};
+use game::Game;
+use replication::ReplicationSystem;
+use nalgebra::Vector2;
+
+/// The shipped configuration file, used as the defaults for every key.
+const DEFAULT_CONFIG: &'static str = include_str!("../config/config.toml");
 
 
 fn main() {
     info!(LOGGER, "Starting server"; "version" => env!("CARGO_PKG_VERSION"));
 
-    // Asset dir is next to the executable, under "data".
-    let mut asset_dir = env::current_exe().expect("Unable to find executable path.");
-    // Go by data dir in the main project right now.
-    // TODO: Fix this for packaged release builds or something.
-    asset_dir.pop();
-    asset_dir.pop();
-    asset_dir.pop();
-    asset_dir.push("data");
-
-    info!(LOGGER, "Loading asset directory"; "directory" => format!("{:?}", asset_dir));
-    load_from_dir(asset_dir).expect("Failed to load assets.");
-
-    let mut cfg = config::CONFIG.write().unwrap();
-    // TODO: Release builds.
-    let mut config_path = env::current_exe().expect("Unable to find executable path.");
-    config_path.pop();
-    config_path.pop();
-    config_path.pop();
-    config_path.push("server");
-    config_path.push("config");
-    config_path.push("config.toml");
-    cfg.load_file(&config_path).unwrap();
-
-    let port = cfg.get("connection.port").and_then(|x| x.as_integer()).expect("Unable to find port inside configuration file.");
-
-    net::run(port as u16);
+    let config_path = match config_locator().locate() {
+        Ok(located) => located,
+        Err(error) => {
+            crit!(LOGGER, "Failed to find configuration."; "error" => format!("{}", error));
+            process::exit(1);
+        }
+    };
+
+    info!(LOGGER, "Loading configuration";
+        "path" => format!("{:?}", config_path.path), "source" => format!("{}", config_path.source));
+    let mut cfg = CONFIG.write().unwrap();
+    cfg.load_defaults(DEFAULT_CONFIG).expect("Built-in default configuration is invalid.");
+    if let Err(error) = cfg.load_file(&config_path.path) {
+        crit!(LOGGER, "Failed to load configuration."; "error" => format!("{}", error));
+        process::exit(1);
+    }
+    if let Some(path) = cfg.get_local_path() {
+        info!(LOGGER, "Loaded local configuration overrides"; "path" => format!("{:?}", path));
+    }
+    cfg.load_env();
+    let args: Vec<String> = env::args().collect();
+    if let Err(error) = cfg.load_args(&args) {
+        crit!(LOGGER, "Invalid configuration."; "error" => format!("{}", error));
+        process::exit(1);
+    }
+
+    let settings = match Settings::from_config(&cfg) {
+        Ok(settings) => settings,
+        Err(error) => {
+            crit!(LOGGER, "Invalid configuration."; "error" => format!("{}", error));
+            process::exit(1);
+        }
+    };
+
+    let port = settings.connection.port;
+    info!(LOGGER, "Using port"; "port" => port,
+        "source" => format!("{}", cfg.source("connection.port").unwrap_or(Layer::Defaults)));
+    drop(cfg);
+
+    let asset_dir = match locate::assets().locate() {
+        Ok(located) => located,
+        Err(error) => {
+            crit!(LOGGER, "Failed to find assets."; "error" => format!("{}", error));
+            process::exit(1);
+        }
+    };
+
+    info!(LOGGER, "Loading asset directory";
+        "directory" => format!("{:?}", asset_dir.path), "source" => format!("{}", asset_dir.source));
+    {
+        let mut manager = ASSET_MANAGER.write().unwrap();
+        // The server should never run with broken content.
+        manager.set_strict(true);
+        manager.set_budget(settings.assets.memory_budget_bytes());
+    }
+    if let Err(error) = load_from_dir(asset_dir.path) {
+        crit!(LOGGER, "Failed to load assets."; "error" => format!("{}", error));
+        process::exit(1);
+    }
+
+    let config_dir = config_path.path.parent().unwrap_or(Path::new("."));
+    let accounts_path = config_dir.join(&settings.accounts.file);
+    let mut accounts = match AccountStore::load(&accounts_path) {
+        Ok(accounts) => accounts,
+        Err(error) => {
+            crit!(LOGGER, "Failed to load accounts.";
+                "path" => format!("{:?}", accounts_path), "error" => format!("{}", error));
+            process::exit(1);
+        }
+    };
+    if let Some(name) = flag_value(&args, "--add-account") {
+        add_account(&mut accounts, &name, settings.accounts.iterations);
+    }
+    info!(LOGGER, "Loaded accounts";
+        "path" => format!("{:?}", accounts.get_path()), "count" => accounts.len(),
+        "required" => settings.accounts.required);
+
+    let bans_path = config_dir.join(&settings.bans.file);
+    let mut bans = match BanList::load(&bans_path) {
+        Ok(bans) => bans,
+        Err(error) => {
+            crit!(LOGGER, "Failed to load bans.";
+                "path" => format!("{:?}", bans_path), "error" => format!("{}", error));
+            process::exit(1);
+        }
+    };
+    if let Some(target) = flag_value(&args, "--ban") {
+        ban(&mut bans, &target, &args);
+    }
+    if let Some(target) = flag_value(&args, "--unban") {
+        unban(&mut bans, &target);
+    }
+    info!(LOGGER, "Loaded bans";
+        "path" => format!("{:?}", bans.get_path()), "count" => bans.get_bans().len(),
+        "whitelisted" => bans.get_whitelist().len(), "whitelist" => settings.bans.whitelist);
+    let bans = Arc::new(Mutex::new(bans));
+    let reload_interval = Duration::from_secs(settings.bans.reload_interval);
+    if let Err(error) = bans::watch(bans.clone(), reload_interval) {
+        crit!(LOGGER, "Failed to start watching the bans file."; "error" => format!("{}", error));
+        process::exit(1);
+    }
+
+    // Something for clients to see until there is a map.
+    make_builder(&WORLD).with_component(PositionComponent::empty());
+    make_builder(&WORLD)
+        .with_component(PositionComponent::new(Positional::new(Vector2::new(100.0, 100.0), 0)));
+
+    let mut game = Game::new(&settings.game);
+    // Last, so clients see everything the other systems did this tick.
+    game.add_system(ReplicationSystem::new());
+    let events = match net::start(settings, accounts, bans) {
+        Ok(events) => events,
+        Err(error) => {
+            crit!(LOGGER, "Failed to start networking.";
+                "port" => port, "error" => format!("{}", error));
+            process::exit(1);
+        }
+    };
+
+    game.run(events);
+
+    crit!(LOGGER, "Networking stopped, shutting down.");
+    process::exit(1);
+}
+
+/// Register an account, reading its password from the first line of standard input, and exit.
+fn add_account(accounts: &mut AccountStore, name: &str, iterations: u32) -> ! {
+    info!(LOGGER, "Enter the password for the new account."; "account" => name.to_owned());
+    let mut line = String::new();
+    if let Err(error) = io::stdin().read_line(&mut line) {
+        crit!(LOGGER, "Failed to read the password."; "error" => format!("{}", error));
+        process::exit(1);
+    }
+    let password = line.trim_right_matches(|c| c == '\n' || c == '\r');
+    if password.is_empty() {
+        crit!(LOGGER, "The password can't be empty.");
+        process::exit(1);
+    }
+
+    if let Err(error) = accounts.add(name, password, iterations).and_then(|()| accounts.save()) {
+        crit!(LOGGER, "Failed to add the account.";
+            "account" => name.to_owned(), "error" => format!("{}", error));
+        process::exit(1);
+    }
+    info!(LOGGER, "Added account.";
+        "account" => name.to_owned(), "path" => format!("{:?}", accounts.get_path()));
+    process::exit(0);
+}
+
+/// Ban a target given on the command line, save the bans and exit.
+///
+/// `--reason`, `--duration` and `--admin` fill in the rest of the ban.
+fn ban(bans: &mut BanList, target: &str, args: &[String]) -> ! {
+    let target = match Target::parse(target) {
+        Ok(target) => target,
+        Err(error) => {
+            crit!(LOGGER, "Invalid ban target."; "error" => error);
+            process::exit(1);
+        }
+    };
+    let now = bans::now();
+    let expires = match flag_value(args, "--duration").map(|x| bans::parse_duration(&x)) {
+        Some(Ok(duration)) => Some(now + duration),
+        Some(Err(error)) => {
+            crit!(LOGGER, "Invalid ban duration."; "error" => error);
+            process::exit(1);
+        }
+        None => None,
+    };
+    let ban = Ban {
+        target: target,
+        reason: flag_value(args, "--reason").unwrap_or_default(),
+        admin: flag_value(args, "--admin").unwrap_or_else(|| String::from("console")),
+        issued: now,
+        expires: expires,
+    };
+
+    let target = ban.target.to_string();
+    bans.add(ban);
+    if let Err(error) = bans.save() {
+        crit!(LOGGER, "Failed to save bans."; "error" => format!("{}", error));
+        process::exit(1);
+    }
+    info!(LOGGER, "Added ban."; "target" => target, "path" => format!("{:?}", bans.get_path()));
+    process::exit(0);
+}
+
+/// Lift every ban on a target given on the command line, save the bans and exit.
+fn unban(bans: &mut BanList, target: &str) -> ! {
+    let target = match Target::parse(target) {
+        Ok(target) => target,
+        Err(error) => {
+            crit!(LOGGER, "Invalid ban target."; "error" => error);
+            process::exit(1);
+        }
+    };
+    let removed = bans.remove(&target);
+    if removed == 0 {
+        crit!(LOGGER, "No bans found."; "target" => target.to_string());
+        process::exit(1);
+    }
+    if let Err(error) = bans.save() {
+        crit!(LOGGER, "Failed to save bans."; "error" => format!("{}", error));
+        process::exit(1);
+    }
+    info!(LOGGER, "Removed bans."; "target" => target.to_string(), "count" => removed);
+    process::exit(0);
+}
+
+/// Returns a locator for the server configuration file.
+///
+/// It can be overriden with `--config <path>` or `YASSS_CONFIG`.
+fn config_locator() -> Locator {
+    Locator::new("server configuration", "config/config.toml")
+        .with_flag("--config")
+        .with_env("YASSS_CONFIG")
+        .with_repo_path("server/config/config.toml")
 }
@@ -1,12 +1,27 @@
-use std::net::{TcpListener, TcpStream, SocketAddr};
-use std::thread;
-use std::io::{Read, Write};
+use std::net::SocketAddr;
 use std::io;
+use std::thread;
 use slog::Logger;
-use byteorder::{NetworkEndian, ReadBytesExt, WriteBytesExt};
-use std::iter;
+use shared::net::{Handshake, HandshakeLimits, HandshakeReply, Login, ProtocolRange,
+                  RejectReason, Rejection, RoundState, ServerStatus, NetCodec, NetPacket,
+                  ServerCodec, ClientMessage, ServerMessage};
+use shared::net::nickname;
+use accounts::{AccountStore, Sessions};
+use bans::{self, BanList, Visitor};
+use config::{DuplicateNicknames, Settings};
+use rand;
+use std::time::{Duration, Instant};
+use std::cell::{Cell, RefCell};
+use std::rc::Rc;
 use std::collections::HashMap;
-use std::sync::{Arc, RwLock};
+use std::sync::{Arc, Mutex, RwLock};
+use std::sync::mpsc::{self, Receiver, Sender};
+use futures::{future, Future, Sink, Stream};
+use futures::sync::mpsc::{unbounded, UnboundedReceiver, UnboundedSender};
+use tokio_core::net::{TcpListener, TcpStream};
+use tokio_core::reactor::{Core, Handle, Interval, Timeout};
+use tokio_io::AsyncRead;
+use tokio_io::codec::Framed;
 
 lazy_static! {
     #[doc(hidden)]
@@ -20,109 +35,667 @@ lazy_static! {
     };
 }
 
+/// Something that happened on the network, for the rest of the server to act on.
+#[derive(Debug)]
+pub enum NetEvent {
+    /// A client finished the handshake and was added to `CLIENTS`.
+    Connected {
+        address: SocketAddr,
+        nickname: String,
+        /// The account the client logged into, if any.
+        account: Option<String>,
+    },
 
-pub fn run(port: u16) {
-    let listener = TcpListener::bind(("0.0.0.0", port)).unwrap();
-    // TODO: Set listener to non-blocking and have better control here.
-    for connection in listener.incoming().map(|x| x.expect("TODO: handle IO errors.")) {
-        let address = format!("{:?}", connection.peer_addr().unwrap());
-        match spawn_client(connection) {
-            // TODO: handle server polling with better logging, or maybe at all!
-            Ok(_) => {
-                debug!(LOGGER, "Accepted new client."; "address" => address);
-            }
-            Err(e) => {
-                error!(LOGGER, "Error while accepting new client";
-                    "address" => address, "error" => format!("{:?}", e));
-            }
+    /// A client sent a packet.
+    Packet { address: SocketAddr, packet: NetPacket },
+
+    /// A connected client went away and was removed from `CLIENTS`.
+    Disconnected {
+        address: SocketAddr,
+        nickname: String,
+        reason: String,
+    },
+}
+
+/// Server information shared with every connection.
+struct ServerInfo {
+    settings: Settings,
+    started: Instant,
+    events: Sender<NetEvent>,
+    accounts: AccountStore,
+    sessions: Sessions,
+    bans: Arc<Mutex<BanList>>,
+    /// Random key for the challenges sent for accounts that don't exist.
+    secret: [u8; 32],
+}
+
+impl ServerInfo {
+    fn status(&self) -> ServerStatus {
+        ServerStatus {
+            name: self.settings.server.name.clone(),
+            protocols: ProtocolRange::supported(),
+            players: CLIENTS.read().unwrap().len() as u32,
+            max_players: self.settings.server.max_players,
+            // There are no maps or rounds yet.
+            map: String::new(),
+            round: RoundState::Lobby,
+            uptime: self.started.elapsed().as_secs(),
         }
     }
+
+    /// A codec enforcing the configured size limits.
+    fn codec(&self) -> ServerCodec {
+        let connection = &self.settings.connection;
+        let limits = HandshakeLimits {
+            max_version_length: connection.max_version_length,
+            max_nickname_length: connection.max_nickname_length,
+        };
+        ServerCodec::with_limits(NetCodec::with_max_frame_size(connection.max_packet_size), limits)
+    }
 }
 
-fn spawn_client(client: TcpStream) -> io::Result<thread::Thread> {
-    let name = format!("client_{}", client.peer_addr()?);
-    let handle = thread::Builder::new()
-        .name(name)
+/// Start listening on a background thread.
+///
+/// Returns once the port is bound, with a receiver for everything clients do. The receiver
+/// hangs up if the network thread stops.
+pub fn start(settings: Settings,
+             accounts: AccountStore,
+             bans: Arc<Mutex<BanList>>)
+             -> io::Result<Receiver<NetEvent>> {
+    let (events, receiver) = mpsc::channel();
+    let (bound, bind_result) = mpsc::channel();
+
+    thread::Builder::new()
+        .name(String::from("net"))
         .spawn(move || {
-            let address = format!("{:?}", client.peer_addr().unwrap());
-            match do_client(client) {
-                Ok(()) => debug!(LOGGER, "Dropping client"; "client" => address),
-                Err(e) => error!(LOGGER, "Error with client connection.";
-                    "client" => address, "error" => format!("{:?}", e))
+            let port = settings.connection.port;
+            let session_lifetime = Duration::from_secs(settings.accounts.session_lifetime);
+            let info = Arc::new(ServerInfo {
+                settings: settings,
+                started: Instant::now(),
+                events: events,
+                accounts: accounts,
+                sessions: Sessions::new(session_lifetime),
+                bans: bans,
+                secret: rand::random(),
+            });
+            if let Err(error) = run(port, info, bound) {
+                crit!(LOGGER, "Network event loop stopped."; "error" => format!("{}", error));
             }
         })?;
-    Ok(handle.thread().clone())
+
+    match bind_result.recv() {
+        Ok(Ok(())) => Ok(receiver),
+        Ok(Err(error)) => Err(error),
+        Err(_) => Err(io::Error::new(io::ErrorKind::Other, "network thread died while starting")),
+    }
 }
 
-fn do_client(mut connection: TcpStream) -> io::Result<()> {
-    let client = match handshake(&mut connection) {
-        Ok(client) => {
-            connection.write_u8(1)?;
-            client
+/// Milliseconds to stop accepting connections for after failing to accept one.
+const ACCEPT_ERROR_PAUSE: u64 = 100;
+
+/// Run the event loop, accepting connections until an unrecoverable error occurs.
+fn run(port: u16, info: Arc<ServerInfo>, bound: Sender<io::Result<()>>) -> io::Result<()> {
+    let mut core = Core::new()?;
+    let handle = core.handle();
+    let address = SocketAddr::from(([0, 0, 0, 0], port));
+    let listener = match TcpListener::bind(&address, &handle) {
+        Ok(listener) => {
+            let _ = bound.send(Ok(()));
+            listener
         }
-        Err(_) => {
-            return connection.write_u8(0);
+        Err(error) => {
+            let _ = bound.send(Err(error));
+            return Ok(());
         }
-    }
+    };
+    info!(LOGGER, "Listening for connections."; "address" => format!("{}", address));
+
+    let pause_handle = handle.clone();
+    let server = listener.incoming()
+        .then(move |result| -> AcceptFuture {
+            // Accept errors, like running out of file descriptors, only affect that one
+            // connection. Keep going instead of bringing the whole server down, but wait a bit
+            // first, since retrying right away would most likely fail the same way.
+            match result {
+                Ok(accepted) => Box::new(future::ok(Some(accepted))),
+                Err(error) => {
+                    error!(LOGGER, "Error while accepting a connection.";
+                        "error" => format!("{}", error));
+                    let pause = Duration::from_millis(ACCEPT_ERROR_PAUSE);
+                    match Timeout::new(pause, &pause_handle) {
+                        Ok(timeout) => Box::new(timeout.map(|()| None)),
+                        Err(error) => Box::new(future::err(error)),
+                    }
+                }
+            }
+        })
+        .for_each(move |accepted| {
+            if let Some((socket, address)) = accepted {
+                spawn_connection(&handle, socket, address, info.clone());
+            }
+            Ok(())
+        });
 
+    core.run(server)
+}
 
+fn spawn_connection(handle: &Handle,
+                    socket: TcpStream,
+                    address: SocketAddr,
+                    info: Arc<ServerInfo>) {
+    debug!(LOGGER, "Accepted new connection."; "address" => format!("{}", address));
+    let connection = do_connection(socket, address, handle, info).then(move |result| {
+        match result {
+            Ok(()) => debug!(LOGGER, "Dropping connection."; "address" => format!("{}", address)),
+            Err(e) => error!(LOGGER, "Error with client connection.";
+                "address" => format!("{}", address), "error" => format!("{}", e)),
+        }
+        Ok(())
+    });
+    handle.spawn(connection);
+}
 
-    Ok(())
+type IoFuture = Box<Future<Item = (), Error = io::Error>>;
+type AcceptFuture = Box<Future<Item = Option<(TcpStream, SocketAddr)>, Error = io::Error>>;
+type ReasonFuture = Box<Future<Item = String, Error = io::Error>>;
+type ServerFramed = Framed<TcpStream, ServerCodec>;
+type OutcomeFuture = Box<Future<Item = Outcome, Error = io::Error>>;
+
+/// How the handshake of a connection ended.
+enum Outcome {
+    /// The client hung up without a handshake.
+    Closed,
+    /// The client polled the server status.
+    Polled(ServerFramed),
+    /// The client sent something that couldn't be read.
+    Malformed(ServerFramed, io::Error),
+    Rejected(ServerFramed, Rejection),
+    /// The client was registered, and logged in if `session` isn't empty.
+    Accepted {
+        framed: ServerFramed,
+        client: Arc<RwLock<Client>>,
+        outgoing: UnboundedReceiver<NetPacket>,
+        session: String,
+    },
 }
 
-fn handshake(connection: &mut TcpStream) -> io::Result<Arc<RwLock<Client>>> {
-    let mut buf = vec![0, 0];
-    connection.read_exact(&mut buf)?;
-    match buf.as_slice() {
-        b"NT" => {},
-        b"SY" => return Err(io_error("Server polling is unimplemented.")),
-        _ => return Err(io_error("ID bytes incorrect."))
+/// Handle a connection from the handshake until it closes.
+fn do_connection(socket: TcpStream,
+                 address: SocketAddr,
+                 handle: &Handle,
+                 info: Arc<ServerInfo>)
+                 -> IoFuture {
+    let framed = socket.framed(info.codec());
+    let handshake_timeout = Duration::from_secs(info.settings.connection.handshake_timeout);
+    let timeout = match Timeout::new(handshake_timeout, handle) {
+        Ok(timeout) => timeout,
+        Err(error) => return Box::new(future::err(error)),
     };
+    let timeout = timeout.then(|_| -> Result<Outcome, _> {
+        Err(io::Error::new(io::ErrorKind::TimedOut, "handshake timed out"))
+    });
+    let handle = handle.clone();
+
+    // The timeout covers the whole handshake, including logging in.
+    let outcome = read_handshake(framed, address, info.clone())
+        .select(timeout)
+        .map(|(outcome, _)| outcome)
+        .map_err(|(e, _)| e);
 
-    let len = connection.read_u8()?;
-    buf = empty_buf(len as usize);
-    connection.read_exact(&mut buf)?;
-    if buf.as_slice() != env!("CARGO_PKG_VERSION").as_bytes() {
-        return Err(io_error("Version mismatch."));
+    Box::new(outcome.and_then(move |outcome| -> IoFuture {
+        match outcome {
+            Outcome::Closed => Box::new(future::ok(())),
+            Outcome::Polled(framed) => {
+                let status = ServerMessage::Status(info.status());
+                Box::new(framed.send(status).map(|_| ()))
+            }
+            Outcome::Malformed(framed, error) => {
+                let rejection = Rejection::new(RejectReason::Malformed, format!("{}", error));
+                let reply = ServerMessage::Reply(HandshakeReply::reject(rejection));
+                Box::new(framed.send(reply).then(move |_| Err(error)))
+            }
+            Outcome::Rejected(framed, rejection) => {
+                warn!(LOGGER, "Rejected client.";
+                    "address" => format!("{}", address),
+                    "reason" => format!("{}", rejection.reason), "code" => rejection.reason.code(),
+                    "message" => rejection.message.clone());
+                let reply = ServerMessage::Reply(HandshakeReply::reject(rejection));
+                Box::new(framed.send(reply).map(|_| ()))
+            }
+            Outcome::Accepted { framed, client, outgoing, session } => {
+                let nickname = client.read().unwrap().get_nick().to_owned();
+                let mut reply = HandshakeReply::accept(nickname);
+                reply.session = session;
+                let serve_info = info.clone();
+                Box::new(framed.send(ServerMessage::Reply(reply))
+                    .and_then(move |framed| {
+                        serve(framed, &handle, client, outgoing, serve_info)
+                    })
+                    .then(move |result| {
+                        disconnect(address, &info, &result);
+                        result.map(|_| ())
+                    }))
+            }
+        }
+    }))
+}
+
+/// Read the handshake, log the client in if it asked to, and register it.
+fn read_handshake(framed: ServerFramed,
+                  address: SocketAddr,
+                  info: Arc<ServerInfo>)
+                  -> OutcomeFuture {
+    Box::new(framed.into_future().then(move |result| -> OutcomeFuture {
+        let (message, framed) = match result {
+            Ok(next) => next,
+            Err((error, framed)) => return Box::new(future::result(malformed(framed, error))),
+        };
+        let (nickname, login) = match message {
+            Some(ClientMessage::Handshake(Handshake::Poll { version, .. })) => {
+                debug!(LOGGER, "Server polled."; "version" => version);
+                return Box::new(future::ok(Outcome::Polled(framed)));
+            }
+            Some(ClientMessage::Handshake(Handshake::Connect { version,
+                                                               protocol,
+                                                               nickname,
+                                                               login })) => {
+                if let Err(rejection) = check_handshake(&info, version, protocol, &nickname) {
+                    return Box::new(future::ok(Outcome::Rejected(framed, rejection)));
+                }
+                (nickname, login)
+            }
+            // The codec always produces the handshake first.
+            Some(_) => unreachable!(),
+            None => return Box::new(future::ok(Outcome::Closed)),
+        };
+
+        // The account the client says it is playing on, and whether that's been checked.
+        let (account, verified) = match login {
+            Login::Guest if info.settings.accounts.required => {
+                let rejection = Rejection::new(RejectReason::LoginRequired,
+                                               "This server only allows players with accounts.");
+                return Box::new(future::ok(Outcome::Rejected(framed, rejection)));
+            }
+            Login::Guest => (None, true),
+            Login::Session(token) => {
+                match info.sessions.check(&token) {
+                    Some(account) => (Some(account), true),
+                    None => {
+                        let rejection = Rejection::new(RejectReason::LoginFailed,
+                                                       "Your session has expired.");
+                        return Box::new(future::ok(Outcome::Rejected(framed, rejection)));
+                    }
+                }
+            }
+            Login::Account(name) => (Some(name), false),
+        };
+
+        // Banned addresses don't even get to try logging in.
+        if let Err(rejection) = check_bans(address, &info, &nickname, account.as_ref(), verified) {
+            return Box::new(future::ok(Outcome::Rejected(framed, rejection)));
+        }
+        match account {
+            Some(name) if !verified => {
+                Box::new(authenticate(framed, &info, name).map(move |result| {
+                    match result {
+                        Ok((framed, account)) => {
+                            register(framed, address, &info, nickname, Some(account))
+                        }
+                        Err(outcome) => outcome,
+                    }
+                }))
+            }
+            account => Box::new(future::ok(register(framed, address, &info, nickname, account))),
+        }
+    }))
+}
+
+/// Challenge a client logging into an account, and check its proof.
+///
+/// Resolves to the connection and the account name, or to how the handshake ended if the login
+/// failed.
+fn authenticate(framed: ServerFramed,
+                info: &ServerInfo,
+                name: String)
+                -> Box<Future<Item = Result<(ServerFramed, String), Outcome>, Error = io::Error>> {
+    let nonce = rand::random::<[u8; 32]>().to_vec();
+    let account = info.accounts.get(&name).cloned();
+    let challenge = match account {
+        Some(ref account) => account.credentials.challenge(nonce.clone()),
+        None => {
+            info.accounts.decoy_challenge(&info.secret,
+                                          &name,
+                                          info.settings.accounts.iterations,
+                                          nonce.clone())
+        }
+    };
+
+    Box::new(framed.send(ServerMessage::Challenge(challenge))
+        .map_err(|error| (error, None))
+        .and_then(|framed| framed.into_future().map_err(|(error, framed)| (error, Some(framed))))
+        .then(move |result| {
+            let (message, framed) = match result {
+                Ok(next) => next,
+                Err((error, Some(framed))) => return malformed(framed, error).map(Err),
+                Err((error, None)) => return Err(error),
+            };
+            let proof = match message {
+                Some(ClientMessage::Proof(proof)) => proof,
+                Some(_) => unreachable!(),
+                None => return Ok(Err(Outcome::Closed)),
+            };
+            match account {
+                Some(ref account) if account.credentials.verify(&nonce, &proof) => {
+                    Ok(Ok((framed, account.name.clone())))
+                }
+                _ => {
+                    let rejection = Rejection::new(RejectReason::LoginFailed,
+                                                   "Wrong account name or password.");
+                    Ok(Err(Outcome::Rejected(framed, rejection)))
+                }
+            }
+        }))
+}
+
+/// Turn a read error into `Outcome::Malformed` if the client sent invalid data.
+fn malformed(framed: ServerFramed, error: io::Error) -> io::Result<Outcome> {
+    if error.kind() == io::ErrorKind::InvalidData {
+        Ok(Outcome::Malformed(framed, error))
+    } else {
+        Err(error)
     }
+}
 
-    let len = connection.read_u32::<NetworkEndian>()? as usize;
-    buf = empty_buf(len);
-    connection.read_exact(&mut buf)?;
-    let nick = match String::from_utf8(buf) {
-        Ok(nick) => nick,
-        Err(_) => return Err(io_error("Nick is not valid UTF-8"))
+/// Pass packets between an accepted client and the rest of the server until either side stops.
+///
+/// Resolves to why the connection closed. Queuing a `Disconnect` for the client sends it and
+/// closes the connection, and so does the client sending one.
+///
+/// Also pings the client regularly, and disconnects it once nothing has been received for
+/// longer than the idle timeout.
+fn serve<S>(framed: S,
+            handle: &Handle,
+            client: Arc<RwLock<Client>>,
+            outgoing: UnboundedReceiver<NetPacket>,
+            info: Arc<ServerInfo>)
+            -> ReasonFuture
+    where S: Stream<Item = ClientMessage, Error = io::Error> +
+             Sink<SinkItem = ServerMessage, SinkError = io::Error> + 'static
+{
+    let ping_interval = Duration::from_secs(info.settings.connection.ping_interval);
+    let interval = match Interval::new(ping_interval, handle) {
+        Ok(interval) => interval,
+        Err(error) => return Box::new(future::err(error)),
     };
+    let address = client.read().unwrap().get_address();
+    let (sink, stream) = framed.split();
+    let last_received = Rc::new(Cell::new(Instant::now()));
 
-    let client_object = Arc::new(RwLock::new(Client {
-        nick: nick,
-        thread: thread::current(),
-        address: connection.peer_addr().unwrap()
-    }));
+    let idle_timeout = Duration::from_secs(info.settings.connection.idle_timeout);
+    let pinged = client.clone();
+    let last_pinged = last_received.clone();
+    let mut token = 0u32;
+    let mut timed_out = false;
+    let pinger = interval.for_each(move |()| {
+        if last_pinged.get().elapsed() >= idle_timeout {
+            // Give the client one more ping interval to get the reason before giving up on it.
+            if timed_out {
+                return Err(io::Error::new(io::ErrorKind::TimedOut, "client stopped responding"));
+            }
+            timed_out = true;
+            let reason = String::from("The server stopped hearing from your client.");
+            pinged.read().unwrap().send(NetPacket::Disconnect { reason: reason });
+            return Ok(());
+        }
+        token = token.wrapping_add(1);
+        pinged.read().unwrap().send(NetPacket::Ping { token: token });
+        Ok(())
+    });
 
+    // A `Disconnect` is sent last, after which the connection closes.
+    let closing = Rc::new(RefCell::new(None));
+    let stash = closing.clone();
+    let writer = outgoing.take_while(move |packet| match *packet {
+            NetPacket::Disconnect { ref reason } => {
+                *stash.borrow_mut() = Some(reason.clone());
+                Ok(false)
+            }
+            _ => Ok(true),
+        })
+        .map(ServerMessage::Packet)
+        .map_err(|()| io::Error::new(io::ErrorKind::Other, "outgoing packet channel failed"))
+        .forward(sink)
+        .and_then(move |(_, sink)| -> ReasonFuture {
+            match closing.borrow_mut().take() {
+                Some(reason) => {
+                    let packet = NetPacket::Disconnect { reason: reason.clone() };
+                    Box::new(sink.send(ServerMessage::Packet(packet)).map(|_| reason))
+                }
+                None => Box::new(future::ok(String::from("Connection closed."))),
+            }
+        });
 
+    let reader = stream.filter_map(move |message| {
+            last_received.set(Instant::now());
+            match message {
+                ClientMessage::Packet(NetPacket::Ping { token }) => {
+                    client.read().unwrap().send(NetPacket::Pong { token: token });
+                    None
+                }
+                ClientMessage::Packet(NetPacket::Pong { .. }) => None,
+                ClientMessage::Packet(NetPacket::Disconnect { reason }) => Some(reason),
+                ClientMessage::Packet(packet) => {
+                    let _ = info.events.send(NetEvent::Packet {
+                        address: address,
+                        packet: packet,
+                    });
+                    None
+                }
+                ClientMessage::Handshake(_) |
+                ClientMessage::Proof(_) => unreachable!(),
+            }
+        })
+        .into_future()
+        .map(|(reason, _)| match reason {
+            Some(ref reason) if !reason.is_empty() => reason.clone(),
+            Some(_) => String::from("Client disconnected."),
+            None => String::from("Connection closed."),
+        })
+        .map_err(|(e, _)| e);
 
-    let mut clients = CLIENTS.write().unwrap();
-    clients.insert(connection.peer_addr().unwrap(), client_object.clone());
+    Box::new(reader.select(writer)
+        .map(|(reason, _)| reason)
+        .map_err(|(e, _)| e)
+        .select(pinger.map(|()| unreachable!()))
+        .map(|(reason, _)| reason)
+        .map_err(|(e, _)| e))
+}
 
-    Ok(client_object)
+/// Remove a client that was served from `CLIENTS` and tell the rest of the server it is gone.
+fn disconnect(address: SocketAddr, info: &ServerInfo, result: &io::Result<String>) {
+    let client = match CLIENTS.write().unwrap().remove(&address) {
+        Some(client) => client,
+        None => return,
+    };
+    let reason = match *result {
+        Ok(ref reason) => reason.clone(),
+        Err(ref error) => format!("{}", error),
+    };
+    let nickname = client.read().unwrap().get_nick().to_owned();
+    let _ = info.events.send(NetEvent::Disconnected {
+        address: address,
+        nickname: nickname,
+        reason: reason,
+    });
 }
 
-/// Create an empty buffer of a certain length for usage by `Read::read_exact()`.
-#[inline(always)]
-fn empty_buf(len: usize) -> Vec<u8> {
-    // There has to be a better approach to this.
-    iter::repeat(0).take(len).collect::<Vec<u8>>()
+/// Check the parts of a connection handshake that don't depend on other clients.
+fn check_handshake(info: &ServerInfo,
+                   version: String,
+                   protocol: u32,
+                   nickname: &str)
+                   -> Result<(), Rejection> {
+    let supported = ProtocolRange::supported();
+    if !supported.contains(protocol) {
+        return Err(Rejection::new(RejectReason::VersionMismatch,
+                                  format!("Protocol version {} is not supported, expected {}.",
+                                          protocol,
+                                          supported)));
+    }
+    if let Err(error) = info.settings.nicknames.rules.check(nickname) {
+        return Err(Rejection::new(RejectReason::BadNickname, format!("{}", error)));
+    }
+    debug!(LOGGER, "Client handshake";
+        "nickname" => nickname.to_owned(), "version" => version, "protocol" => protocol);
+    Ok(())
 }
 
-#[inline(always)]
-fn io_error<P: AsRef<str>>(msg: P) -> io::Error {
-    io::Error::new(io::ErrorKind::Other, msg.as_ref())
+/// Register a client that passed the handshake checks and logged in, if it had to.
+///
+/// Starts a session if the client logged into an account.
+fn register(framed: ServerFramed,
+            address: SocketAddr,
+            info: &ServerInfo,
+            nickname: String,
+            account: Option<String>)
+            -> Outcome {
+    // Account bans are only checked once the client proved it owns the account.
+    if let Err(rejection) = check_bans(address, info, &nickname, account.as_ref(), true) {
+        return Outcome::Rejected(framed, rejection);
+    }
+
+    let mut clients = CLIENTS.write().unwrap();
+    let max_players = info.settings.server.max_players;
+    if clients.len() >= max_players as usize {
+        let rejection = Rejection::new(RejectReason::ServerFull,
+                                       format!("The server is full ({} players).", max_players));
+        return Outcome::Rejected(framed, rejection);
+    }
+    if let Some(ref account) = account {
+        let logged_in = clients.values()
+            .any(|client| client.read().unwrap().get_account() == Some(account.as_str()));
+        if logged_in {
+            let rejection = Rejection::new(RejectReason::LoginFailed,
+                                           "Someone is already playing on that account.");
+            return Outcome::Rejected(framed, rejection);
+        }
+    }
+
+    let nicknames = &info.settings.nicknames;
+    let nickname = {
+        let is_taken = |candidate: &str| {
+            let key = nickname::key(candidate);
+            clients.values().any(|client| nickname::key(client.read().unwrap().get_nick()) == key)
+        };
+        if !is_taken(&nickname) {
+            nickname
+        } else {
+            let renamed = match nicknames.duplicates {
+                DuplicateNicknames::Reject => None,
+                DuplicateNicknames::Rename => nicknames.rules.unique(&nickname, is_taken),
+            };
+            match renamed {
+                Some(renamed) => {
+                    debug!(LOGGER, "Renamed client with a duplicate nickname.";
+                        "nickname" => nickname, "renamed" => renamed.clone());
+                    renamed
+                }
+                None => {
+                    let reason = format!("The nickname {} is already taken.", nickname);
+                    let rejection = Rejection::new(RejectReason::BadNickname, reason);
+                    return Outcome::Rejected(framed, rejection);
+                }
+            }
+        }
+    };
+
+    let session = match account {
+        Some(ref account) => info.sessions.create(account),
+        None => String::new(),
+    };
+    let (sender, receiver) = unbounded();
+    let client_object = Arc::new(RwLock::new(Client {
+        nick: nickname.clone(),
+        account: account.clone(),
+        address: address,
+        sender: sender,
+    }));
+    clients.insert(address, client_object.clone());
+
+    let _ = info.events.send(NetEvent::Connected {
+        address: address,
+        nickname: nickname,
+        account: account,
+    });
+    Outcome::Accepted {
+        framed: framed,
+        client: client_object,
+        outgoing: receiver,
+        session: session,
+    }
+}
+
+/// Check a client against the bans and the whitelist.
+///
+/// Until the client logged in, `account` is only the one it asked for. It isn't matched against
+/// the bans or the whitelist, so neither can tell anyone what they list about an account.
+fn check_bans(address: SocketAddr,
+              info: &ServerInfo,
+              nickname: &str,
+              account: Option<&String>,
+              verified: bool)
+              -> Result<(), Rejection> {
+    let bans = info.bans.lock().unwrap();
+    let visitor = Visitor {
+        address: address.ip(),
+        nickname: nickname,
+        account: account.map(|x| x.as_str()),
+    };
+    let now = bans::now();
+    let banned = if verified {
+        bans.check(&visitor, now)
+    } else {
+        bans.check(&Visitor { account: None, ..visitor }, now)
+    };
+    if let Some(ban) = banned {
+        return Err(Rejection::new(RejectReason::Banned, ban.message(now)));
+    }
+    if info.settings.bans.whitelist && !bans.whitelist_admits(&visitor, verified) {
+        return Err(Rejection::new(RejectReason::Banned,
+                                  "This server only lets in players on its whitelist."));
+    }
+    Ok(())
 }
 
 #[derive(Debug)]
 pub struct Client {
     nick: String,
-    thread: thread::Thread,
-    address: SocketAddr
+    /// The account the client logged into, if any.
+    account: Option<String>,
+    address: SocketAddr,
+
+    /// Queue of packets to send, drained by the connection's task.
+    sender: UnboundedSender<NetPacket>,
+}
+
+impl Client {
+    pub fn get_nick(&self) -> &str {
+        &self.nick
+    }
+
+    pub fn get_account(&self) -> Option<&str> {
+        self.account.as_ref().map(|x| x.as_str())
+    }
+
+    pub fn get_address(&self) -> SocketAddr {
+        self.address
+    }
+
+    /// Queue a packet to be sent to this client.
+    ///
+    /// Returns false if the connection is already gone.
+    pub fn send(&self, packet: NetPacket) -> bool {
+        self.sender.unbounded_send(packet).is_ok()
+    }
 }
@@ -0,0 +1,73 @@
+//! Keeping every client's copy of the world in sync with the server's.
+
+use shared::entities::replication::{Tracker, REPLICATION};
+use game::{System, Tick};
+use logs::LOGGER;
+use net::NetEvent;
+use std::collections::HashSet;
+use std::net::SocketAddr;
+
+/// Sends a snapshot of the world to clients that join, and what changed to the others.
+pub struct ReplicationSystem {
+    tracker: Tracker,
+    /// Clients that got their snapshot.
+    synced: HashSet<SocketAddr>,
+}
+
+impl ReplicationSystem {
+    pub fn new() -> ReplicationSystem {
+        ReplicationSystem {
+            tracker: Tracker::new(),
+            synced: HashSet::new(),
+        }
+    }
+}
+
+impl System for ReplicationSystem {
+    fn name(&self) -> &'static str {
+        "replication"
+    }
+
+    fn run(&mut self, tick: &mut Tick) {
+        let mut joined = Vec::new();
+        for event in tick.events {
+            match *event {
+                NetEvent::Connected { address, .. } => joined.push(address),
+                NetEvent::Disconnected { address, .. } => {
+                    self.synced.remove(&address);
+                    joined.retain(|x| *x != address);
+                }
+                NetEvent::Packet { .. } => {}
+            }
+        }
+
+        let states = REPLICATION.read().unwrap().encode_world(&tick.world.read().unwrap());
+        let states = match states {
+            Ok(states) => states,
+            Err(error) => {
+                error!(LOGGER, "Failed to encode the world."; "error" => format!("{}", error));
+                return;
+            }
+        };
+
+        let changes = self.tracker.update(states);
+        if !changes.is_empty() && !self.synced.is_empty() {
+            for packet in changes.into_packets(tick.number) {
+                for address in &self.synced {
+                    tick.send(*address, packet.clone());
+                }
+            }
+        }
+
+        // Joining clients get the world as it is now, so they don't need this tick's changes.
+        if !joined.is_empty() {
+            let packets = self.tracker.snapshot(tick.number);
+            for address in joined {
+                for packet in &packets {
+                    tick.send(address, packet.clone());
+                }
+                self.synced.insert(address);
+            }
+        }
+    }
+}
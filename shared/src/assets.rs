@@ -1,13 +1,17 @@
 use slog::Logger;
 use logs::LOGGER as HEAD_LOGGER;
-use rsi::Rsi;
+use font::{Font, FontError};
+use rsi::{Rsi, RsiError};
+use sound::{Sound, SoundError};
 use std::fs::File;
 use std::io::Read;
 use std::collections::HashMap;
-use std::collections::hash_map::Iter as HashMapIter;
+use std::collections::hash_map::{self, Keys};
 use std::path::{Path, PathBuf};
-use std::sync::{RwLock, Arc};
-use std::io::Result as IoResult;
+use std::sync::{RwLock, Arc, Mutex};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::io;
+use std::fmt;
 
 lazy_static! {
     #[doc(hidden)]
@@ -17,7 +21,7 @@ lazy_static! {
 
     /// The global asset manager all things should use.
     pub static ref ASSET_MANAGER: RwLock<AssetManager> = {
-        RwLock::new(AssetManager {assets: HashMap::new(), root: PathBuf::new()})
+        RwLock::new(AssetManager::new())
     };
 }
 
@@ -31,6 +35,12 @@ pub enum Asset {
 
     /// An RSI.
     Rsi(Rsi),
+
+    /// A sound, decoded from a WAV or OGG file.
+    Sound(Sound),
+
+    /// A bitmap font.
+    Font(Font),
 }
 
 impl Asset {
@@ -67,32 +77,69 @@ impl Asset {
         }
     }
 
+    /// Returns `true` if this asset is a sound.
+    pub fn is_sound(&self) -> bool {
+        match self {
+            &Asset::Sound(_) => true,
+            _ => false,
+        }
+    }
+
+    /// Returns a sound if this asset is indeed a sound.
+    pub fn as_sound(&self) -> Option<&Sound> {
+        match self {
+            &Asset::Sound(ref sound) => Some(&sound),
+            _ => None,
+        }
+    }
+
+    /// Returns `true` if this asset is a font.
+    pub fn is_font(&self) -> bool {
+        match self {
+            &Asset::Font(_) => true,
+            _ => false,
+        }
+    }
+
+    /// Returns a font if this asset is indeed a font.
+    pub fn as_font(&self) -> Option<&Font> {
+        match self {
+            &Asset::Font(ref font) => Some(&font),
+            _ => None,
+        }
+    }
+
+    /// Returns roughly how many bytes of memory this asset uses.
+    pub fn memory_size(&self) -> usize {
+        match self {
+            &Asset::Binary(ref vec) => vec.len(),
+            &Asset::Rsi(ref rsi) => rsi.memory_size(),
+            &Asset::Sound(ref sound) => sound.memory_size(),
+            &Asset::Font(ref font) => font.memory_size(),
+        }
+    }
+
+    /// Returns `true` if this asset holds decoded images,
+    /// which an `AssetManager` may drop and reload from disk to save memory.
+    pub fn is_evictable(&self) -> bool {
+        self.is_rsi() || self.is_font()
+    }
+
     /// Recursively ran over files by the `AssetManager` to load assets.
     ///
-    /// If this returns `None` on a file, the file is ignored.
+    /// If this returns `Ok(None)` on a file, the file is skipped.
     #[doc(hidden)]
-    pub fn from_file(path: &Path) -> Option<Self> {
+    pub fn from_file(path: &Path) -> Result<Option<Self>, AssetError> {
         // TODO: Can we softcode this?
-
-        match File::open(path) {
-            Ok(mut file) => {
-                let mut buf = Vec::new();
-                match file.read_to_end(&mut buf) {
-                    Ok(_) => return Some(Asset::Binary(buf)),
-                    Err(error) => {
-                        error!(LOGGER, "Failed to read from file.";
-                            "error" => format!("{:?}", error), "path" => format!("{:?}", path));
-                        return None;
-                    }
-                }
-            }
-            Err(error) => {
-                error!(LOGGER, "Failed to open file.";
-                    "error" => format!("{:?}", error), "path" => format!("{:?}", path));
-                return None;
-            }
+        match path.extension().and_then(|x| x.to_str()) {
+            Some("wav") | Some("ogg") => return Ok(Some(Asset::Sound(Sound::open(path)?))),
+            _ => {}
         }
-        //None
+
+        let mut file = File::open(path)?;
+        let mut buf = Vec::new();
+        file.read_to_end(&mut buf)?;
+        Ok(Some(Asset::Binary(buf)))
     }
 
     /// Recursively ran over directories by the `AssetManager` to load assets.
@@ -101,119 +148,571 @@ impl Asset {
     /// if the second value of the tuple return is `false`,
     /// the contents of the directory are ignored.
     #[doc(hidden)]
-    pub fn from_dir(path: &Path) -> (Option<Asset>, bool) {
+    pub fn from_dir(path: &Path) -> (Result<Option<Asset>, AssetError>, bool) {
         if let Some(string) = path.extension().and_then(|x| x.to_str()) {
             return match string {
                 "rsi" => {
-                    match Rsi::open(path) {
-                        Ok(rsi) => (Some(Asset::Rsi(rsi)), false),
-                        Err(error) => {
-                            error!(LOGGER, "Failed to open RSI.";
-                    "error" => format!("{:?}", error), "path" => format!("{:?}", path));
-                            (None, false)
-                        }
-                    }
+                    let result = Rsi::open(path).map(|rsi| Some(Asset::Rsi(rsi)));
+                    (result.map_err(AssetError::from), false)
+                }
+                "font" => {
+                    let result = Font::open(path).map(|font| Some(Asset::Font(font)));
+                    (result.map_err(AssetError::from), false)
                 }
-                _ => (None, true),
+                _ => (Ok(None), true),
             };
         }
-        (None, true)
+        (Ok(None), true)
+    }
+}
+
+/// An error that prevented a single asset from loading.
+#[derive(Debug)]
+pub enum AssetError {
+    /// An IO error occured while reading the asset.
+    Io(io::Error),
+
+    /// The asset is an RSI that failed to open.
+    Rsi(RsiError),
+
+    /// The asset is a sound that failed to decode.
+    Sound(SoundError),
+
+    /// The asset is a font that failed to open.
+    Font(FontError),
+}
+
+impl From<io::Error> for AssetError {
+    fn from(err: io::Error) -> AssetError {
+        AssetError::Io(err)
+    }
+}
+
+impl From<RsiError> for AssetError {
+    fn from(err: RsiError) -> AssetError {
+        AssetError::Rsi(err)
+    }
+}
+
+impl From<SoundError> for AssetError {
+    fn from(err: SoundError) -> AssetError {
+        AssetError::Sound(err)
+    }
+}
+
+impl From<FontError> for AssetError {
+    fn from(err: FontError) -> AssetError {
+        AssetError::Font(err)
+    }
+}
+
+impl fmt::Display for AssetError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            AssetError::Io(ref err) => write!(f, "IO error: {}", err),
+            AssetError::Rsi(ref err) => write!(f, "invalid RSI: {:?}", err),
+            AssetError::Sound(ref err) => write!(f, "{}", err),
+            AssetError::Font(ref err) => write!(f, "invalid font: {:?}", err),
+        }
+    }
+}
+
+/// A report of what happened to every entry while loading a directory of assets.
+///
+/// Paths are relative to the directory that was loaded.
+#[derive(Debug, Default)]
+pub struct LoadReport {
+    /// Assets that loaded successfully.
+    pub loaded: Vec<PathBuf>,
+
+    /// Entries that were not considered assets, such as symlinks.
+    pub skipped: Vec<PathBuf>,
+
+    /// Assets that failed to load, with the reason why.
+    pub failed: Vec<(PathBuf, AssetError)>,
+}
+
+impl LoadReport {
+    /// Returns `true` if no assets failed to load.
+    pub fn is_ok(&self) -> bool {
+        self.failed.is_empty()
+    }
+}
+
+impl fmt::Display for LoadReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f,
+               "{} loaded, {} skipped, {} failed",
+               self.loaded.len(),
+               self.skipped.len(),
+               self.failed.len())?;
+        for &(ref path, ref error) in &self.failed {
+            write!(f, "\n  {}: {}", path.display(), error)?;
+        }
+        Ok(())
+    }
+}
+
+/// An error from loading a whole directory of assets.
+#[derive(Debug)]
+pub enum LoadError {
+    /// The directory itself could not be read.
+    Io(io::Error),
+
+    /// The asset manager is strict and one or more assets failed to load.
+    ///
+    /// Nothing was replaced in the asset manager.
+    Failed(LoadReport),
+}
+
+impl From<io::Error> for LoadError {
+    fn from(err: io::Error) -> LoadError {
+        LoadError::Io(err)
+    }
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            LoadError::Io(ref err) => write!(f, "unable to read asset directory: {}", err),
+            LoadError::Failed(ref report) => write!(f, "assets failed to load: {}", report),
+        }
     }
 }
 
 // Ah yes a 1000 lines in and I'm already writing shitcode.
 // This project is going great.
 
-/// Manages all assets.
+/// Manages a set of assets loaded from a directory.
+///
+/// Most code should go through the global `ASSET_MANAGER`,
+/// but separate managers can be made to hold different sets of content side by side.
+///
+/// A manager can be given a memory budget.
+/// When more memory is used than that, the decoded images of the least recently used
+/// RSIs and fonts are dropped, and transparently loaded from disk again when next needed.
+/// Anything still holding an `Arc` of an evicted asset keeps it alive until it's dropped.
 pub struct AssetManager {
-    assets: HashMap<PathBuf, Arc<Asset>>,
+    assets: HashMap<PathBuf, AssetEntry>,
 
     /// The absolute path to which the assets are relative.
     root: PathBuf,
+
+    /// Whether any failed asset makes the whole load fail.
+    strict: bool,
+
+    /// The maximum amount of bytes loaded assets may use, if any.
+    budget: Option<usize>,
+
+    /// Incremented every time an asset is accessed, to find the least recently used assets.
+    clock: AtomicUsize,
+
+    /// The amount of bytes used by currently loaded assets.
+    resident: AtomicUsize,
+    evictions: AtomicUsize,
+    reloads: AtomicUsize,
+}
+
+/// An asset in an `AssetManager`, and everything needed to load it again.
+struct AssetEntry {
+    /// `None` if the asset was evicted.
+    asset: Mutex<Option<Arc<Asset>>>,
+
+    /// The absolute path the asset was loaded from.
+    source: PathBuf,
+    is_dir: bool,
+
+    /// The memory used by the asset when it was last loaded.
+    size: AtomicUsize,
+    evictable: bool,
+    last_used: AtomicUsize,
+}
+
+impl AssetEntry {
+    fn is_resident(&self) -> bool {
+        // If the entry is locked, it's being used or reloaded right now.
+        self.asset.try_lock().map(|x| x.is_some()).unwrap_or(true)
+    }
+
+    fn reload(&self) -> Result<Asset, AssetError> {
+        let result = if self.is_dir {
+            Asset::from_dir(&self.source).0
+        } else {
+            Asset::from_file(&self.source)
+        };
+
+        match result {
+            Ok(Some(asset)) => Ok(asset),
+            Ok(None) => {
+                Err(AssetError::Io(io::Error::new(io::ErrorKind::NotFound,
+                                                  "Asset is no longer an asset.")))
+            }
+            Err(error) => Err(error),
+        }
+    }
+}
+
+/// Memory statistics of an `AssetManager`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MemoryStats {
+    /// The amount of assets, loaded or not.
+    pub assets: usize,
+
+    /// The amount of assets currently loaded in memory.
+    pub resident_assets: usize,
+
+    /// The amount of bytes used by currently loaded assets.
+    pub resident_bytes: usize,
+
+    /// The amount of bytes all assets would use if they were all loaded.
+    pub total_bytes: usize,
+
+    /// The memory budget, if any.
+    pub budget: Option<usize>,
+
+    /// How many times an asset was evicted since the directory was loaded.
+    pub evictions: usize,
+
+    /// How many times an evicted asset was loaded again since the directory was loaded.
+    pub reloads: usize,
 }
 
 impl AssetManager {
-    /// Returns an iterator over all loaded assets.
+    /// Creates a new, empty asset manager.
+    pub fn new() -> AssetManager {
+        AssetManager {
+            assets: HashMap::new(),
+            root: PathBuf::new(),
+            strict: false,
+            budget: None,
+            clock: AtomicUsize::new(0),
+            resident: AtomicUsize::new(0),
+            evictions: AtomicUsize::new(0),
+            reloads: AtomicUsize::new(0),
+        }
+    }
+
+    /// Creates a new, empty asset manager with the same settings as this one.
+    pub fn with_settings(&self) -> AssetManager {
+        let mut manager = AssetManager::new();
+        manager.strict = self.strict;
+        manager.budget = self.budget;
+        manager
+    }
+
+    /// Returns `true` if this manager is in strict mode.
+    pub fn is_strict(&self) -> bool {
+        self.strict
+    }
+
+    /// Sets strict mode.
+    ///
+    /// In strict mode, loading a directory fails if any single asset in it fails to load,
+    /// instead of only listing it in the `LoadReport`.
+    pub fn set_strict(&mut self, strict: bool) {
+        self.strict = strict;
+    }
+
+    /// Returns the memory budget in bytes, if any.
+    pub fn get_budget(&self) -> Option<usize> {
+        self.budget
+    }
+
+    /// Sets the memory budget in bytes, evicting assets right away if it's exceeded.
+    ///
+    /// Only RSIs and fonts can be evicted, so other assets may still exceed the budget.
+    pub fn set_budget(&mut self, budget: Option<usize>) {
+        self.budget = budget;
+        self.enforce_budget(None);
+    }
+
+    /// Returns memory statistics.
+    pub fn stats(&self) -> MemoryStats {
+        MemoryStats {
+            assets: self.assets.len(),
+            resident_assets: self.assets.values().filter(|x| x.is_resident()).count(),
+            resident_bytes: self.resident.load(Ordering::SeqCst),
+            total_bytes: self.assets.values().map(|x| x.size.load(Ordering::SeqCst)).sum(),
+            budget: self.budget,
+            evictions: self.evictions.load(Ordering::SeqCst),
+            reloads: self.reloads.load(Ordering::SeqCst),
+        }
+    }
+
+    /// Get an asset by path relative from the directory assets were loaded from.
+    ///
+    /// This means that if assets were loaded from directory `/a`,
+    /// `b/c` would point to `/a/b/c`
+    ///
+    /// If the asset was evicted, it is loaded from disk again.
+    /// `None` is returned if that fails.
+    pub fn get<P: AsRef<Path>>(&self, path: P) -> Option<Arc<Asset>> {
+        let path = path.as_ref();
+        let entry = match self.assets.get(path) {
+            Some(entry) => entry,
+            None => return None,
+        };
+        entry.last_used.store(self.clock.fetch_add(1, Ordering::SeqCst), Ordering::SeqCst);
+
+        let asset = {
+            let mut slot = entry.asset.lock().unwrap();
+            if let Some(ref asset) = *slot {
+                return Some(asset.clone());
+            }
+
+            let asset = match entry.reload() {
+                Ok(asset) => Arc::new(asset),
+                Err(error) => {
+                    error!(LOGGER, "Failed to reload evicted asset.";
+                        "error" => format!("{}", error), "path" => format!("{:?}", path));
+                    return None;
+                }
+            };
+
+            let size = asset.memory_size();
+            entry.size.store(size, Ordering::SeqCst);
+            self.resident.fetch_add(size, Ordering::SeqCst);
+            self.reloads.fetch_add(1, Ordering::SeqCst);
+            *slot = Some(asset.clone());
+            asset
+        };
+
+        self.enforce_budget(Some(path));
+        Some(asset)
+    }
+
+    /// Returns `true` if an asset is currently loaded in memory.
+    pub fn is_resident<P: AsRef<Path>>(&self, path: P) -> bool {
+        self.assets.get(path.as_ref()).map(|x| x.is_resident()).unwrap_or(false)
+    }
+
+    /// Returns the absolute path the assets were loaded from.
+    ///
+    /// This is empty if nothing has been loaded yet.
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Returns the amount of loaded assets.
+    pub fn len(&self) -> usize {
+        self.assets.len()
+    }
+
+    /// Returns `true` if no assets are loaded.
+    pub fn is_empty(&self) -> bool {
+        self.assets.is_empty()
+    }
+
+    /// Returns an iterator over the assets currently in memory.
+    ///
+    /// Evicted assets are skipped instead of being loaded again.
+    /// Use `paths` and `get` to go over every asset.
     pub fn iter<'a>(&'a self) -> AssetIter<'a> {
         AssetIter { iter: self.assets.iter() }
     }
+
+    /// Returns an iterator over the paths of all loaded assets, including evicted ones.
+    pub fn paths<'a>(&'a self) -> AssetPaths<'a> {
+        AssetPaths { iter: self.assets.keys() }
+    }
+
+    // TODO: Some sort of async to speed this up? Long ways off.
+    /// Loads a directory into this asset manager, replacing any previously loaded assets.
+    ///
+    /// The path used will be the "root" for the loaded files, and must be absolute.
+    /// If the directory can't be read, or the manager is strict and an asset failed,
+    /// the previously loaded assets are kept.
+    /// # Panics.
+    /// Panics if the path isn't absolute.
+    pub fn load_from_dir<P: AsRef<Path>>(&mut self, path: P) -> Result<LoadReport, LoadError> {
+        let path = path.as_ref();
+        let mut staging = self.with_settings();
+        let mut report = LoadReport::default();
+
+        // TODO: This is dumb, past me.
+        if !path.is_absolute() {
+            panic!("Path provided is not absolute.")
+        }
+
+        _load_dir(path, path, &mut staging, &mut report)?;
+
+        for &(ref path, ref error) in &report.failed {
+            error!(LOGGER, "Failed to load asset.";
+                "error" => format!("{}", error), "path" => format!("{:?}", path));
+        }
+
+        if self.strict && !report.is_ok() {
+            return Err(LoadError::Failed(report));
+        }
+
+        staging.root = path.to_owned();
+        *self = staging;
+
+        info!(LOGGER, "Assets loaded from directory"; "path" => format!("{:?}", path),
+            "loaded" => report.loaded.len(), "skipped" => report.skipped.len(),
+            "failed" => report.failed.len(),
+            "resident_bytes" => self.resident.load(Ordering::SeqCst));
+
+        Ok(report)
+    }
+
+    /// Adds a freshly loaded asset.
+    ///
+    /// If it would go over budget, it's stored evicted right away,
+    /// so loading a large directory never needs more memory than the budget.
+    fn insert(&mut self, relative: PathBuf, source: PathBuf, is_dir: bool, asset: Asset) {
+        let size = asset.memory_size();
+        let evictable = asset.is_evictable();
+        let resident = self.resident.load(Ordering::SeqCst);
+        let fits = self.budget.map(|budget| resident + size <= budget).unwrap_or(true);
+
+        let asset = if fits || !evictable {
+            self.resident.fetch_add(size, Ordering::SeqCst);
+            Some(Arc::new(asset))
+        } else {
+            None
+        };
+
+        self.assets.insert(relative,
+                           AssetEntry {
+                               asset: Mutex::new(asset),
+                               source: source,
+                               is_dir: is_dir,
+                               size: AtomicUsize::new(size),
+                               evictable: evictable,
+                               last_used: AtomicUsize::new(0),
+                           });
+    }
+
+    /// Evicts the least recently used assets until the budget is met,
+    /// or nothing is left to evict.
+    fn enforce_budget(&self, keep: Option<&Path>) {
+        let budget = match self.budget {
+            Some(budget) => budget,
+            None => return,
+        };
+
+        while self.resident.load(Ordering::SeqCst) > budget {
+            let victim = self.assets
+                .iter()
+                .filter(|&(path, entry)| entry.evictable && Some(path.as_path()) != keep)
+                .filter(|&(_, entry)| entry.is_resident())
+                .min_by_key(|&(_, entry)| entry.last_used.load(Ordering::SeqCst));
+
+            let entry = match victim {
+                Some((_, entry)) => entry,
+                None => return,
+            };
+
+            let mut slot = match entry.asset.try_lock() {
+                Ok(slot) => slot,
+                Err(_) => return,
+            };
+            if slot.take().is_some() {
+                self.resident.fetch_sub(entry.size.load(Ordering::SeqCst), Ordering::SeqCst);
+                self.evictions.fetch_add(1, Ordering::SeqCst);
+            }
+        }
+    }
 }
 
-/// Get an asset by path relative from the directory assets were loaded from.
+impl Default for AssetManager {
+    fn default() -> AssetManager {
+        AssetManager::new()
+    }
+}
+
+/// Get an asset from the global `ASSET_MANAGER`.
 ///
-/// This means that if assets were loaded from directory `/a`,
-/// `b/c` would point to `/a/b/c`
+/// See [`AssetManager::get`](struct.AssetManager.html#method.get).
 pub fn get_asset<P: AsRef<Path>>(path: P) -> Option<Arc<Asset>> {
-    let path = path.as_ref();
-    let manager = ASSET_MANAGER.read().unwrap();
-
-    manager.assets.get(path).map(|x| x.clone())
+    ASSET_MANAGER.read().unwrap().get(path)
 }
 
-// TODO: Some sort of async to speed this up? Long ways off.
-/// Loads a directory into the global asset manager.
+/// Loads a directory into the global `ASSET_MANAGER`.
 ///
-/// The path used will be the "root" for the loaded files, and must be absolute.
+/// The directory is loaded before the manager is locked,
+/// so readers are not blocked while files are being read.
+/// Settings such as strict mode and the budget are taken from the global manager.
+/// See [`AssetManager::load_from_dir`](struct.AssetManager.html#method.load_from_dir).
 /// # Panics.
 /// Panics if the path isn't absolute.
-pub fn load_from_dir<P: AsRef<Path>>(path: P) -> IoResult<()> {
-    let path = path.as_ref();
-    let mut new_assets = HashMap::new();
-    let new_root = path.to_owned();
-
-    // TODO: This is dumb, past me.
-    if !new_root.is_absolute() {
-        panic!("Path provided is not absolute.")
-    }
+pub fn load_from_dir<P: AsRef<Path>>(path: P) -> Result<LoadReport, LoadError> {
+    let mut manager = ASSET_MANAGER.read().unwrap().with_settings();
+    let report = manager.load_from_dir(path)?;
 
-    _load_dir(path, &new_root, &mut new_assets)?;
+    *ASSET_MANAGER.write().unwrap() = manager;
 
-    let mut manager = ASSET_MANAGER.write().unwrap();
-    manager.assets = new_assets;
-    manager.root = new_root;
-
-    info!(LOGGER, "Assets loaded from directory"; "path" => format!("{:?}", path));
-
-    Ok(())
+    Ok(report)
 }
 
 /// Recursive function to load all files and subfiles in a directory.
-fn _load_dir(path: &Path, root: &Path, map: &mut HashMap<PathBuf, Arc<Asset>>) -> IoResult<()> {
+fn _load_dir(path: &Path,
+             root: &Path,
+             manager: &mut AssetManager,
+             report: &mut LoadReport)
+             -> io::Result<()> {
     for entry in path.read_dir()? {
         let entry = entry?;
         let path = &entry.path();
         let entry_type = entry.file_type()?;
-        let relative = path.strip_prefix(root).unwrap();
+        let relative = path.strip_prefix(root).unwrap().to_owned();
 
-        if entry_type.is_dir() {
-            let (asset, cont) = Asset::from_dir(path);
-            if let Some(asset) = asset {
-                map.insert(relative.to_owned(), Arc::new(asset));
-            }
-            if cont {
-                _load_dir(path, root, map)?;
-            }
+        let (result, cont) = if entry_type.is_dir() {
+            Asset::from_dir(path)
         } else if entry_type.is_file() {
-            if let Some(asset) = Asset::from_file(path) {
-                map.insert(relative.to_owned(), Arc::new(asset));
+            (Asset::from_file(path), false)
+        } else {
+            // TODO: Do we care about symlinks?
+            // Probably not but eh.
+            (Ok(None), false)
+        };
+
+        match result {
+            Ok(Some(asset)) => {
+                manager.insert(relative.clone(), path.clone(), entry_type.is_dir(), asset);
+                report.loaded.push(relative);
             }
+            Ok(None) if !entry_type.is_dir() => report.skipped.push(relative),
+            Ok(None) => {}
+            Err(error) => report.failed.push((relative, error)),
+        }
+
+        if cont {
+            _load_dir(path, root, manager, report)?;
         }
-        // TODO: Do we care about symlinks?
-        // Probably not but eh.
     }
 
     Ok(())
 }
 
-/// An iterator over all the loaded assets.
+/// An iterator over the assets in memory.
 pub struct AssetIter<'a> {
-    iter: HashMapIter<'a, PathBuf, Arc<Asset>>,
+    iter: hash_map::Iter<'a, PathBuf, AssetEntry>,
 }
 
 impl<'a> Iterator for AssetIter<'a> {
-    type Item = (&'a PathBuf, &'a Arc<Asset>);
+    type Item = (&'a PathBuf, Arc<Asset>);
+
+    fn next(&mut self) -> Option<(&'a PathBuf, Arc<Asset>)> {
+        while let Some((path, entry)) = self.iter.next() {
+            if let Some(ref asset) = *entry.asset.lock().unwrap() {
+                return Some((path, asset.clone()));
+            }
+        }
+        None
+    }
+}
+
+/// An iterator over the paths of all loaded assets.
+pub struct AssetPaths<'a> {
+    iter: Keys<'a, PathBuf, AssetEntry>,
+}
+
+impl<'a> Iterator for AssetPaths<'a> {
+    type Item = &'a PathBuf;
 
-    fn next(&mut self) -> Option<(&'a PathBuf, &'a Arc<Asset>)> {
+    fn next(&mut self) -> Option<&'a PathBuf> {
         self.iter.next()
     }
-}
\ No newline at end of file
+}
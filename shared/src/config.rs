@@ -1,82 +1,855 @@
 extern crate toml;
 
 use toml::{ParserError, Parser, Table, Value};
-use std::sync::RwLock;
-use std::path::Path;
+use helpers::args::flag_values;
+use std::collections::{BTreeMap, HashMap};
+use std::env;
+use std::sync::{Mutex, RwLock};
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::path::{Path, PathBuf};
+use std::fmt;
 use std::io;
-use std::io::Read;
+use std::io::{Read, Write};
 use std::fs::File;
 
+/// A source of configuration values.
+///
+/// Layers are ordered by precedence: a value in a later layer overrides the same key in any
+/// earlier one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Layer {
+    /// Built-in defaults shipped with the program.
+    Defaults,
+    /// The main config file, such as `config.toml`.
+    File,
+    /// The optional local override file next to the main one, such as `config.local.toml`.
+    LocalFile,
+    /// `YASSS_*` environment variables.
+    Environment,
+    /// `--set key=value` command line arguments.
+    CommandLine,
+    /// Values changed while running, see `Config::set`.
+    Runtime,
+}
+
+impl fmt::Display for Layer {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match *self {
+            Layer::Defaults => "defaults",
+            Layer::File => "config file",
+            Layer::LocalFile => "local config file",
+            Layer::Environment => "environment",
+            Layer::CommandLine => "command line",
+            Layer::Runtime => "runtime",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// Prefix of environment variables that override config keys.
+pub const ENV_PREFIX: &'static str = "YASSS_";
+
+/// Command line flag that overrides config keys.
+pub const SET_FLAG: &'static str = "--set";
+
+/// Written at the top of local override files saved by `Config::persist`.
+const PERSIST_HEADER: &'static str = "# Saved from runtime configuration changes.\n\
+                                      # Values here override the main config file.\n\n";
+
 pub struct Config {
+    /// All layers merged together.
     toml: Table,
+    layers: BTreeMap<Layer, Table>,
+    path: Option<PathBuf>,
+    local_path: Option<PathBuf>,
+    subscribers: Mutex<Vec<Sender<Vec<String>>>>,
 }
 
 impl Config {
+    /// Create an empty config.
+    pub fn new() -> Config {
+        Config {
+            toml: Table::new(),
+            layers: BTreeMap::new(),
+            path: None,
+            local_path: None,
+            subscribers: Mutex::new(Vec::new()),
+        }
+    }
+
     /// Load the config from the string.
+    ///
+    /// This replaces the `File` layer.
     pub fn load(&mut self, data: &str) -> Result<(), Vec<ParserError>> {
-        let mut parser = Parser::new(data);
-        if let Some(value) = parser.parse() {
-            self.toml = value;
-            return Ok(());
-        }
+        self.load_layer(Layer::File, data)
+    }
 
-        Err(parser.errors)
+    /// Load the built-in defaults from a string.
+    pub fn load_defaults(&mut self, data: &str) -> Result<(), Vec<ParserError>> {
+        self.load_layer(Layer::Defaults, data)
+    }
+
+    /// Replace a single layer with the parsed contents of the string.
+    pub fn load_layer(&mut self, layer: Layer, data: &str) -> Result<(), Vec<ParserError>> {
+        let table = parse(data)?;
+        self.set_layer(layer, table);
+        Ok(())
+    }
+
+    /// Replace a single layer, returning the keys whose value changed.
+    pub fn set_layer(&mut self, layer: Layer, table: Table) -> Vec<String> {
+        self.layers.insert(layer, table);
+        self.rebuild()
     }
 
     /// Load a configuration file.
+    ///
+    /// If a local override file exists next to it (`config.local.toml` for `config.toml`),
+    /// it is loaded too.
     pub fn load_file(&mut self, path: &Path) -> Result<(), ConfigError> {
-        let mut data = String::new();
-        let mut file = File::open(path)?;
-        file.read_to_string(&mut data)?;
-        self.load(&data)?;
+        self.read_files(path)?;
         Ok(())
     }
 
+    /// Read the config file and local override file from disk again.
+    ///
+    /// Returns the keys whose value changed. Does nothing if the config was never loaded from
+    /// a file.
+    pub fn reload(&mut self) -> Result<Vec<String>, ConfigError> {
+        match self.path.clone() {
+            Some(path) => self.read_files(&path),
+            None => Ok(Vec::new()),
+        }
+    }
+
+    /// Change a value while running, returning the keys whose value changed.
+    ///
+    /// Runtime values override every other layer until they are reset or the program exits,
+    /// unless they are saved with `persist`. Single array elements can't be set, so keys with
+    /// indices like "jobs[1].name" are an error.
+    pub fn set(&mut self, key: &str, value: Value) -> Result<Vec<String>, ConfigError> {
+        let names = table_names(key, Layer::Runtime)?;
+        let mut table = self.layers.remove(&Layer::Runtime).unwrap_or_default();
+        insert(&mut table, &names, value);
+        Ok(self.set_layer(Layer::Runtime, table))
+    }
+
+    /// Remove a runtime value set with `set`, returning the keys whose value changed.
+    pub fn reset(&mut self, key: &str) -> Result<Vec<String>, ConfigError> {
+        let names = table_names(key, Layer::Runtime)?;
+        let mut table = self.layers.remove(&Layer::Runtime).unwrap_or_default();
+        remove(&mut table, &names);
+        Ok(self.set_layer(Layer::Runtime, table))
+    }
+
+    /// Save the runtime values into the local override file, so they survive a restart.
+    ///
+    /// The local override file is created next to the config file if it does not exist yet.
+    pub fn persist(&mut self) -> Result<(), ConfigError> {
+        let path = match (self.local_path.clone(), self.path.as_ref()) {
+            (Some(path), _) => path,
+            (None, Some(path)) => local_path(path),
+            (None, None) => {
+                return Err(ConfigError::Io {
+                    file: None,
+                    error: io::Error::new(io::ErrorKind::NotFound,
+                                          "config was not loaded from a file"),
+                })
+            }
+        };
+
+        let mut table = self.layers.get(&Layer::LocalFile).cloned().unwrap_or_default();
+        if let Some(runtime) = self.layers.get(&Layer::Runtime) {
+            merge(&mut table, runtime);
+        }
+
+        File::create(&path)
+            .and_then(|mut file| write!(file, "{}{}", PERSIST_HEADER, Value::Table(table.clone())))
+            .map_err(|error| ConfigError::io(&path, error))?;
+
+        self.layers.remove(&Layer::Runtime);
+        self.layers.insert(Layer::LocalFile, table);
+        self.local_path = Some(path);
+        self.rebuild();
+        Ok(())
+    }
+
+    /// Get notified whenever values change.
+    ///
+    /// The receiver gets the list of changed keys every time a layer is loaded or a value
+    /// is set. Dropping it unsubscribes.
+    pub fn subscribe(&self) -> Receiver<Vec<String>> {
+        let (sender, receiver) = channel();
+        self.subscribers.lock().unwrap().push(sender);
+        receiver
+    }
+
+    /// Load overrides from `YASSS_*` environment variables.
+    pub fn load_env(&mut self) {
+        self.load_env_from(env::vars());
+    }
+
+    /// Load overrides from the given environment variables.
+    ///
+    /// A variable overrides a key if its name is `YASSS_` followed by the key in upper case,
+    /// with periods and dashes replaced by underscores: `YASSS_CONNECTION_PORT` sets
+    /// `connection.port`. Only keys that already exist in an earlier layer can be set this way.
+    pub fn load_env_from<I>(&mut self, vars: I)
+        where I: IntoIterator<Item = (String, String)>
+    {
+        let mut names = HashMap::new();
+        for key in self.keys_below(Layer::Environment) {
+            names.insert(env_name(&key), key);
+        }
+
+        let mut table = Table::new();
+        for (name, value) in vars {
+            // Keys that can't be split into tables, like quoted ones with periods, are skipped.
+            let key = names.get(&name).and_then(|key| table_names(key, Layer::Environment).ok());
+            if let Some(key) = key {
+                insert(&mut table, &key, parse_value(&value));
+            }
+        }
+        self.set_layer(Layer::Environment, table);
+    }
+
+    /// Load overrides given as `--set key=value` in the command line arguments.
+    pub fn load_args(&mut self, args: &[String]) -> Result<(), ConfigError> {
+        let mut table = Table::new();
+        for setting in flag_values(args, SET_FLAG) {
+            let mut split = setting.splitn(2, '=');
+            let key = split.next().unwrap().trim();
+            let value = match split.next() {
+                Some(value) if !key.is_empty() => value.trim(),
+                _ => {
+                    return Err(ConfigError::Invalid {
+                        key: key.to_owned(),
+                        file: None,
+                        layer: Some(Layer::CommandLine),
+                        reason: format!("expected `{} key=value`, found `{}`", SET_FLAG, setting),
+                    })
+                }
+            };
+            insert(&mut table, &table_names(key, Layer::CommandLine)?, parse_value(value));
+        }
+        self.set_layer(Layer::CommandLine, table);
+        Ok(())
+    }
+
+    /// The file this config was last loaded from, if any.
+    pub fn get_path(&self) -> Option<&Path> {
+        self.path.as_ref().map(|p| p.as_path())
+    }
+
+    /// The local override file that was loaded alongside the main file, if any.
+    pub fn get_local_path(&self) -> Option<&Path> {
+        self.local_path.as_ref().map(|p| p.as_path())
+    }
+
+    /// Returns the layer that the value for a key comes from.
+    pub fn source(&self, key: &str) -> Option<Layer> {
+        self.layers
+            .iter()
+            .rev()
+            .find(|&(_, table)| lookup(table, key).is_some())
+            .map(|(layer, _)| *layer)
+    }
+
     /// Access a value on the config file.
     ///
-    /// The key used is a period separated path such as "connection.port".
+    /// The key used is a period separated path such as "connection.port". Arrays can be
+    /// indexed with brackets, as in "jobs.slots[2].name". If the path ends on a section, the
+    /// whole table is returned.
     pub fn get(&self, key: &str) -> Option<Value> {
-        let key = String::from(key);
-        let mut current = &self.toml;
+        lookup(&self.toml, key).cloned()
+    }
 
-        for name in key.split('.') {
-            if !current.contains_key(name) {
-                return None;
-            }
-            if let Value::Table(ref table) = current[name] {
-                current = table;
-            } else {
-                return Some(current[name].clone());
+    /// Returns whether a key exists, see `get`.
+    pub fn contains(&self, key: &str) -> bool {
+        lookup(&self.toml, key).is_some()
+    }
+
+    /// Returns the value of a key if it is an integer.
+    pub fn get_int(&self, key: &str) -> Option<i64> {
+        lookup(&self.toml, key).and_then(|x| x.as_integer())
+    }
+
+    /// Returns the value of a key if it is a string.
+    pub fn get_str(&self, key: &str) -> Option<&str> {
+        lookup(&self.toml, key).and_then(|x| x.as_str())
+    }
+
+    /// Returns the value of a key if it is an array.
+    pub fn get_list(&self, key: &str) -> Option<&[Value]> {
+        lookup(&self.toml, key).and_then(|x| x.as_slice())
+    }
+
+    /// Returns the names of the keys in a section, sorted.
+    ///
+    /// An empty string lists the top level. Returns an empty list if the key is not a section.
+    pub fn keys(&self, section: &str) -> Vec<String> {
+        let table = if section.is_empty() {
+            Some(&self.toml)
+        } else {
+            lookup(&self.toml, section).and_then(|x| x.as_table())
+        };
+        table.map(|t| t.keys().cloned().collect()).unwrap_or_default()
+    }
+
+    /// Read a typed section out of the config.
+    ///
+    /// Keys missing from the file keep the value from `T::default()`.
+    pub fn section<T: ConfigSection>(&self) -> Result<T, ConfigError> {
+        let name = T::name();
+        let table = match self.toml.get(name) {
+            None => None,
+            Some(&Value::Table(ref table)) => Some(table),
+            Some(value) => {
+                let reason = format!("expected a section, found {}", value.type_str());
+                return Err(self.invalid(name, reason));
             }
+        };
+
+        let reader = SectionReader {
+            name: name,
+            table: table,
+            config: self,
+        };
+
+        let mut section = T::default();
+        section.read(&reader)?;
+        Ok(section)
+    }
+
+    /// Create an error for an invalid value, naming the layer it came from.
+    fn invalid(&self, key: &str, reason: String) -> ConfigError {
+        let layer = self.source(key);
+        let file = match layer {
+            Some(Layer::File) => self.path.clone(),
+            Some(Layer::LocalFile) => self.local_path.clone(),
+            _ => None,
+        };
+
+        ConfigError::Invalid {
+            key: key.to_owned(),
+            file: file,
+            layer: layer,
+            reason: reason,
+        }
+    }
+
+    /// Every leaf key defined in the layers below `layer`.
+    fn keys_below(&self, layer: Layer) -> Vec<String> {
+        let mut merged = Table::new();
+        for (_, table) in self.layers.range(..layer) {
+            merge(&mut merged, table);
+        }
+
+        let mut values = BTreeMap::new();
+        flatten(&merged, "", &mut values);
+        values.into_iter().map(|(key, _)| key).collect()
+    }
+
+    fn read_files(&mut self, path: &Path) -> Result<Vec<String>, ConfigError> {
+        let table = read_file(path)?;
+        let local_path = local_path(path);
+        let local = if local_path.is_file() {
+            Some(read_file(&local_path)?)
+        } else {
+            None
+        };
+
+        self.layers.insert(Layer::File, table);
+        self.path = Some(path.to_path_buf());
+        if let Some(local) = local {
+            self.layers.insert(Layer::LocalFile, local);
+            self.local_path = Some(local_path);
+        } else {
+            self.layers.remove(&Layer::LocalFile);
+            self.local_path = None;
+        }
+        Ok(self.rebuild())
+    }
+
+    /// Merge the layers again and notify subscribers of the keys that changed.
+    fn rebuild(&mut self) -> Vec<String> {
+        let mut merged = Table::new();
+        for table in self.layers.values() {
+            merge(&mut merged, table);
         }
-        None
+
+        let changed = changed_keys(&self.toml, &merged);
+        self.toml = merged;
+
+        if !changed.is_empty() {
+            let mut subscribers = self.subscribers.lock().unwrap();
+            subscribers.retain(|sender| sender.send(changed.clone()).is_ok());
+        }
+        changed
     }
 }
 
 lazy_static! {
     pub static ref CONFIG: RwLock<Config> = {
         // Initialize it to nothing.
-        RwLock::new(Config { toml: toml::Table::new() })
+        RwLock::new(Config::new())
+    };
+}
+
+/// Returns the local override file for a config file: `config.local.toml` for `config.toml`.
+pub fn local_path(path: &Path) -> PathBuf {
+    let stem = path.file_stem().map(|s| s.to_string_lossy().into_owned()).unwrap_or_default();
+    match path.extension() {
+        Some(extension) => {
+            path.with_file_name(format!("{}.local.{}", stem, extension.to_string_lossy()))
+        }
+        None => path.with_file_name(format!("{}.local", stem)),
+    }
+}
+
+/// Returns the environment variable that overrides a key.
+pub fn env_name(key: &str) -> String {
+    format!("{}{}", ENV_PREFIX, key.replace('.', "_").replace('-', "_").to_uppercase())
+}
+
+/// Parse an override value as TOML, falling back to a plain string.
+///
+/// This means `40001` is an integer and `true` a boolean, while `localhost` is a string.
+pub fn parse_value(value: &str) -> Value {
+    let data = format!("value = {}", value);
+    match Parser::new(&data).parse().and_then(|mut table| table.remove("value")) {
+        Some(parsed) => parsed,
+        None => Value::String(value.to_owned()),
+    }
+}
+
+fn parse(data: &str) -> Result<Table, Vec<ParserError>> {
+    let mut parser = Parser::new(data);
+    match parser.parse() {
+        Some(table) => Ok(table),
+        None => Err(parser.errors),
+    }
+}
+
+/// Read and parse a file, naming it in errors.
+fn read_file(path: &Path) -> Result<Table, ConfigError> {
+    let mut data = String::new();
+    File::open(path)
+        .and_then(|mut file| file.read_to_string(&mut data))
+        .map_err(|error| ConfigError::io(path, error))?;
+    parse(&data).map_err(|errors| {
+        ConfigError::Parse {
+            file: path.to_path_buf(),
+            errors: errors.iter().map(|error| ParseError::new(&data, error)).collect(),
+        }
+    })
+}
+
+/// A single step in a key path.
+#[derive(Debug, PartialEq)]
+enum Segment<'a> {
+    /// A key in a table.
+    Name(&'a str),
+    /// An index into an array.
+    Index(usize),
+}
+
+/// Split a key path such as `a.b[2].c` into its segments.
+///
+/// Returns `None` if the path is malformed.
+fn parse_path<'a>(key: &'a str) -> Option<Vec<Segment<'a>>> {
+    let mut segments = Vec::new();
+    for part in key.split('.') {
+        let (name, mut rest) = match part.find('[') {
+            Some(index) => part.split_at(index),
+            None => (part, ""),
+        };
+        if name.is_empty() {
+            return None;
+        }
+        segments.push(Segment::Name(name));
+
+        while !rest.is_empty() {
+            let close = match rest.find(']') {
+                Some(close) if rest.starts_with('[') => close,
+                _ => return None,
+            };
+            match rest[1..close].parse() {
+                Ok(index) => segments.push(Segment::Index(index)),
+                Err(_) => return None,
+            }
+            rest = &rest[close + 1..];
+        }
+    }
+    Some(segments)
+}
+
+fn lookup<'a>(table: &'a Table, key: &str) -> Option<&'a Value> {
+    let segments = match parse_path(key) {
+        Some(segments) => segments,
+        None => return None,
+    };
+
+    let mut segments = segments.into_iter();
+    let mut current = match segments.next() {
+        Some(Segment::Name(name)) => table.get(name),
+        _ => None,
     };
+
+    for segment in segments {
+        current = match (current, segment) {
+            (Some(&Value::Table(ref table)), Segment::Name(name)) => table.get(name),
+            (Some(&Value::Array(ref array)), Segment::Index(index)) => array.get(index),
+            _ => return None,
+        };
+    }
+    current
+}
+
+/// Split a key path into the names of the tables leading to it, for `insert` and `remove`.
+///
+/// Array indices are not supported there, arrays are always replaced as a whole. Errors name
+/// the layer the key was meant for.
+fn table_names(key: &str, layer: Layer) -> Result<Vec<&str>, ConfigError> {
+    let invalid = |reason: &str| {
+        ConfigError::Invalid {
+            key: key.to_owned(),
+            file: None,
+            layer: Some(layer),
+            reason: reason.to_owned(),
+        }
+    };
+
+    let segments = parse_path(key).ok_or_else(|| invalid("malformed key"))?;
+    segments.into_iter()
+        .map(|segment| match segment {
+            Segment::Name(name) => Ok(name),
+            Segment::Index(_) => Err(invalid("array elements can't be set, only whole arrays")),
+        })
+        .collect()
+}
+
+/// Set a key split by `table_names`, creating (or replacing) tables along the way.
+fn insert(table: &mut Table, names: &[&str], value: Value) {
+    let (last, names) = names.split_last().unwrap();
+    let mut current = table;
+
+    for name in names {
+        let entry = current.entry(name.to_string()).or_insert_with(|| Value::Table(Table::new()));
+        if entry.as_table().is_none() {
+            *entry = Value::Table(Table::new());
+        }
+        current = match *entry {
+            Value::Table(ref mut table) => table,
+            _ => unreachable!(),
+        };
+    }
+    current.insert(last.to_string(), value);
+}
+
+/// Merge `other` into `table`, recursing into tables present in both.
+fn merge(table: &mut Table, other: &Table) {
+    for (name, value) in other {
+        if let Value::Table(ref other) = *value {
+            if let Some(&mut Value::Table(ref mut existing)) = table.get_mut(name) {
+                merge(existing, other);
+                continue;
+            }
+        }
+        table.insert(name.clone(), value.clone());
+    }
+}
+
+/// Remove a key split by `table_names`, if it exists.
+fn remove(table: &mut Table, names: &[&str]) {
+    let (last, names) = names.split_last().unwrap();
+    let mut current = table;
+
+    for name in names {
+        current = match current.get_mut(*name) {
+            Some(&mut Value::Table(ref mut table)) => table,
+            _ => return,
+        };
+    }
+    current.remove(*last);
+}
+
+/// Collect every non-table value by its full key.
+fn flatten<'a>(table: &'a Table, prefix: &str, values: &mut BTreeMap<String, &'a Value>) {
+    for (name, value) in table {
+        let key = if prefix.is_empty() {
+            name.clone()
+        } else {
+            format!("{}.{}", prefix, name)
+        };
+        match *value {
+            Value::Table(ref table) => flatten(table, &key, values),
+            _ => {
+                values.insert(key, value);
+            }
+        }
+    }
+}
+
+/// Returns the keys that were added, removed or changed between two tables, sorted.
+fn changed_keys(old: &Table, new: &Table) -> Vec<String> {
+    let mut old_values = BTreeMap::new();
+    let mut new_values = BTreeMap::new();
+    flatten(old, "", &mut old_values);
+    flatten(new, "", &mut new_values);
+
+    let mut changed: Vec<String> = new_values.iter()
+        .filter(|&(key, value)| old_values.get(key) != Some(value))
+        .map(|(key, _)| key.clone())
+        .collect();
+    changed.extend(old_values.keys().filter(|key| !new_values.contains_key(*key)).cloned());
+    changed.sort();
+    changed
+}
+
+/// A table in the config file that deserializes into a struct.
+pub trait ConfigSection: Default {
+    /// The name of the table, such as "connection".
+    fn name() -> &'static str;
+
+    /// Overwrite fields with the values present in the section.
+    fn read(&mut self, reader: &SectionReader) -> Result<(), ConfigError>;
+}
+
+/// Reads typed values out of a single section, see `ConfigSection`.
+pub struct SectionReader<'a> {
+    name: &'a str,
+    table: Option<&'a Table>,
+    config: &'a Config,
+}
+
+impl<'a> SectionReader<'a> {
+    /// Read `field` into `target`, leaving it untouched if the key is missing.
+    pub fn read<T: FromValue>(&self, field: &str, target: &mut T) -> Result<(), ConfigError> {
+        let value = match self.table.and_then(|t| t.get(field)) {
+            Some(value) => value,
+            None => return Ok(()),
+        };
+
+        match T::from_value(value) {
+            Ok(parsed) => {
+                *target = parsed;
+                Ok(())
+            }
+            Err(reason) => Err(self.error(field, reason)),
+        }
+    }
+
+    /// Like `read`, but the value has to lie within `min` and `max`, inclusive.
+    pub fn read_range<T>(&self,
+                         field: &str,
+                         target: &mut T,
+                         min: T,
+                         max: T)
+                         -> Result<(), ConfigError>
+        where T: FromValue + PartialOrd + fmt::Display
+    {
+        self.read(field, target)?;
+        if *target < min || *target > max {
+            let reason = format!("expected a value between {} and {}, found {}",
+                                 min,
+                                 max,
+                                 target);
+            return Err(self.error(field, reason));
+        }
+        Ok(())
+    }
+
+    /// Create an error for `field` in this section.
+    pub fn error(&self, field: &str, reason: String) -> ConfigError {
+        self.config.invalid(&format!("{}.{}", self.name, field), reason)
+    }
+}
+
+/// Conversion from a TOML value, with a description of what was expected on failure.
+pub trait FromValue: Sized {
+    fn from_value(value: &Value) -> Result<Self, String>;
+}
+
+fn mismatch(expected: &str, value: &Value) -> String {
+    format!("expected {}, found {}", expected, value.type_str())
+}
+
+impl FromValue for i64 {
+    fn from_value(value: &Value) -> Result<i64, String> {
+        value.as_integer().ok_or_else(|| mismatch("an integer", value))
+    }
+}
+
+macro_rules! from_value_int {
+    ($t:ident, $min:expr, $max:expr) => {
+        impl FromValue for $t {
+            fn from_value(value: &Value) -> Result<$t, String> {
+                match *value {
+                    Value::Integer(i) if i >= $min && i <= $max => Ok(i as $t),
+                    Value::Integer(i) => {
+                        Err(format!("expected an integer between {} and {}, found {}",
+                                    $min,
+                                    $max,
+                                    i))
+                    }
+                    ref other => Err(mismatch("an integer", other)),
+                }
+            }
+        }
+    }
+}
+
+from_value_int!(i32, ::std::i32::MIN as i64, ::std::i32::MAX as i64);
+from_value_int!(u8, 0, ::std::u8::MAX as i64);
+from_value_int!(u16, 0, ::std::u16::MAX as i64);
+from_value_int!(u32, 0, ::std::u32::MAX as i64);
+from_value_int!(u64, 0, ::std::i64::MAX);
+from_value_int!(usize, 0, ::std::i64::MAX);
+
+impl FromValue for f64 {
+    fn from_value(value: &Value) -> Result<f64, String> {
+        match *value {
+            Value::Float(f) => Ok(f),
+            Value::Integer(i) => Ok(i as f64),
+            ref other => Err(mismatch("a number", other)),
+        }
+    }
+}
+
+impl FromValue for bool {
+    fn from_value(value: &Value) -> Result<bool, String> {
+        value.as_bool().ok_or_else(|| mismatch("a boolean", value))
+    }
+}
+
+impl FromValue for String {
+    fn from_value(value: &Value) -> Result<String, String> {
+        value.as_str().map(String::from).ok_or_else(|| mismatch("a string", value))
+    }
+}
+
+impl<T: FromValue> FromValue for Vec<T> {
+    fn from_value(value: &Value) -> Result<Vec<T>, String> {
+        let array = match value.as_slice() {
+            Some(array) => array,
+            None => return Err(mismatch("an array", value)),
+        };
+
+        let mut result = Vec::with_capacity(array.len());
+        for (index, item) in array.iter().enumerate() {
+            match T::from_value(item) {
+                Ok(item) => result.push(item),
+                Err(reason) => return Err(format!("element {}: {}", index, reason)),
+            }
+        }
+        Ok(result)
+    }
+}
+
+/// A TOML syntax error, located in the file it was found in.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParseError {
+    /// The line of the error, starting at 1.
+    pub line: usize,
+    /// The column of the error in characters, starting at 1.
+    pub column: usize,
+    pub description: String,
+}
+
+impl ParseError {
+    /// Locate an error the parser found in `data`.
+    pub fn new(data: &str, error: &ParserError) -> ParseError {
+        let mut line = 1;
+        let mut column = 1;
+        for (index, c) in data.char_indices() {
+            if index >= error.lo {
+                break;
+            }
+            if c == '\n' {
+                line += 1;
+                column = 1;
+            } else {
+                column += 1;
+            }
+        }
+
+        ParseError {
+            line: line,
+            column: column,
+            description: error.desc.clone(),
+        }
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}: {}", self.line, self.column, self.description)
+    }
 }
 
 #[derive(Debug)]
 pub enum ConfigError {
-    /// An IO error occured.
-    Io(io::Error),
+    /// A file couldn't be read or written.
+    Io {
+        /// The file, unless there was none to begin with.
+        file: Option<PathBuf>,
+        error: io::Error,
+    },
 
-    /// The TOML failed to parse.
-    ParserError(Vec<ParserError>),
+    /// A file failed to parse as TOML.
+    Parse {
+        file: PathBuf,
+        errors: Vec<ParseError>,
+    },
+
+    /// A key was present but held an unusable value.
+    Invalid {
+        /// The full key, such as "connection.port".
+        key: String,
+        /// The file the value came from, if it was loaded from one.
+        file: Option<PathBuf>,
+        /// The layer the value came from.
+        layer: Option<Layer>,
+        reason: String,
+    },
 }
 
-impl From<io::Error> for ConfigError {
-    fn from(err: io::Error) -> ConfigError {
-        ConfigError::Io(err)
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ConfigError::Io { ref file, ref error } => {
+                if let Some(ref file) = *file {
+                    write!(f, "{}: ", file.display())?;
+                }
+                write!(f, "{}", error)
+            }
+            ConfigError::Parse { ref file, ref errors } => {
+                write!(f, "failed to parse TOML")?;
+                for (index, error) in errors.iter().enumerate() {
+                    let separator = if index == 0 { ":" } else { ";" };
+                    write!(f, "{} {}:{}", separator, file.display(), error)?;
+                }
+                Ok(())
+            }
+            ConfigError::Invalid { ref key, ref file, ref layer, ref reason } => {
+                if let Some(ref file) = *file {
+                    write!(f, "{}: ", file.display())?;
+                } else if let Some(ref layer) = *layer {
+                    write!(f, "{}: ", layer)?;
+                }
+                write!(f, "invalid value for `{}`: {}", key, reason)
+            }
+        }
     }
 }
 
-impl From<Vec<ParserError>> for ConfigError {
-    fn from(err: Vec<ParserError>) -> ConfigError {
-        ConfigError::ParserError(err)
+impl ConfigError {
+    /// Create an error for a file that couldn't be read or written.
+    pub fn io<P: AsRef<Path>>(file: P, error: io::Error) -> ConfigError {
+        ConfigError::Io {
+            file: Some(file.as_ref().to_path_buf()),
+            error: error,
+        }
     }
 }
@@ -1,5 +1,8 @@
 use super::Component;
+use entities::replication::Replicated;
+use byteorder::{NetworkEndian, ReadBytesExt, WriteBytesExt};
 use nalgebra::Vector2;
+use std::io::{self, Cursor};
 
 
 /// A component for giving an entity a position.
@@ -28,6 +31,23 @@ impl PositionComponent {
 
 impl Component for PositionComponent {}
 
+impl Replicated for PositionComponent {
+    const KIND: u16 = 0;
+
+    fn write(&self, buf: &mut Vec<u8>) -> io::Result<()> {
+        buf.write_f64::<NetworkEndian>(self.position.coordinates.x)?;
+        buf.write_f64::<NetworkEndian>(self.position.coordinates.y)?;
+        buf.write_u16::<NetworkEndian>(self.position.dimension)
+    }
+
+    fn read(cursor: &mut Cursor<&[u8]>) -> io::Result<PositionComponent> {
+        let x = cursor.read_f64::<NetworkEndian>()?;
+        let y = cursor.read_f64::<NetworkEndian>()?;
+        let dimension = cursor.read_u16::<NetworkEndian>()?;
+        Ok(PositionComponent::new(Positional::new(Vector2::new(x, y), dimension)))
+    }
+}
+
 /// Represents an absolute position somewhere in the world.
 #[derive(Copy, Clone, Debug, PartialEq)]
 pub struct Positional {
@@ -1,6 +1,7 @@
 //! This module provides all
 
 pub mod components;
+pub mod replication;
 
 use std::sync::{RwLock, RwLockWriteGuard};
 use std::any::TypeId;
@@ -88,6 +89,41 @@ impl World {
     pub fn iter_entities<'a>(&'a self) -> EntityIter<'a> {
         EntityIter { iter: self.entities.iter() }
     }
+
+    /// Create an entity with a specific ID, like one replicated from the server.
+    ///
+    /// Returns the existing entity if there already is one with that ID.
+    /// IDs given out by `make_builder` afterwards start after it.
+    pub fn create_entity(&mut self, id: ID) -> Arc<RwLock<Entity>> {
+        if id >= self.id {
+            self.id = id + 1;
+        }
+        self.entities.entry(id).or_insert_with(|| Arc::new(RwLock::new(Entity { id: id }))).clone()
+    }
+
+    /// Remove an entity and all of its components.
+    pub fn remove_entity(&mut self, id: ID) -> Option<Arc<RwLock<Entity>>> {
+        for storage in self.components.values_mut() {
+            storage.remove(id);
+        }
+        self.entities.remove(&id)
+    }
+
+    /// Give an entity a component, replacing the one of the same type it had.
+    ///
+    /// # Panics.
+    /// Panics if the component type is not registered.
+    pub fn add_component<T: Component>(&mut self, id: ID, component: T) {
+        self.get_storage_mut().0.insert(id, Arc::new(RwLock::new(component)));
+    }
+
+    /// Take a component away from an entity, returning it.
+    ///
+    /// # Panics.
+    /// Panics if the component type is not registered.
+    pub fn remove_component<T: Component>(&mut self, id: ID) -> Option<Arc<RwLock<T>>> {
+        self.get_storage_mut().0.remove(&id)
+    }
 }
 
 impl World {
@@ -126,11 +162,18 @@ pub fn make_builder<'a>(world: &'a RwLock<World>) -> EntityBuilder<'a> {
     EntityBuilder::new(world, id)
 }
 
-trait ComponentStorageTrait: mopa::Any + Send + Sync {}
+trait ComponentStorageTrait: mopa::Any + Send + Sync {
+    /// Drop the component of an entity, if it has one.
+    fn remove(&mut self, id: ID);
+}
 mopafy!(ComponentStorageTrait);
 
 struct ComponentStorage<T: Component>(HashMap<ID, Arc<RwLock<T>>>);
-impl<T: Component> ComponentStorageTrait for ComponentStorage<T> {}
+impl<T: Component> ComponentStorageTrait for ComponentStorage<T> {
+    fn remove(&mut self, id: ID) {
+        self.0.remove(&id);
+    }
+}
 impl<T: Component> ComponentStorage<T> {
     fn new() -> ComponentStorage<T> {
         ComponentStorage(HashMap::new())
@@ -282,6 +325,39 @@ mod tests {
         assert_eq!(comp.read().unwrap().a, 123);
     }
 
+    #[test]
+    fn test_remove_entity() {
+        let world = RwLock::new(World::new());
+        world.write().unwrap().register_component::<TestComponent>();
+        make_builder(&world).with_component(TestComponent { a: 1 }).finish();
+        make_builder(&world).with_component(TestComponent { a: 2 }).finish();
+
+        let mut world = world.write().unwrap();
+        assert!(world.remove_entity(0).is_some());
+        assert!(world.remove_entity(0).is_none());
+        assert!(world.get_entity(0).is_none());
+        assert!(world.get_component::<TestComponent>(0).is_none());
+        assert_eq!(world.iter_components::<TestComponent>().count(), 1);
+    }
+
+    #[test]
+    fn test_create_entity() {
+        let world = RwLock::new(World::new());
+        world.write().unwrap().register_component::<TestComponent>();
+        {
+            let mut world = world.write().unwrap();
+            world.create_entity(5);
+            world.add_component(5, TestComponent { a: 3 });
+            assert_eq!(world.get_component::<TestComponent>(5).unwrap().read().unwrap().a, 3);
+            assert!(world.remove_component::<TestComponent>(5).is_some());
+            assert!(world.get_component::<TestComponent>(5).is_none());
+        }
+
+        // Built entities don't reuse the ID.
+        let built = make_builder(&world).finish();
+        assert_eq!(built.read().unwrap().get_id(), 6);
+    }
+
     #[test]
     fn test_iter_entities() {
         let world = RwLock::new(World::new());
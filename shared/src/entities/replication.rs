@@ -0,0 +1,431 @@
+//! Mirroring the server's entities on clients.
+//!
+//! Components opt in by implementing `Replicated` and being registered with `REPLICATION`.
+//! Each tick the server encodes every entity, and a `Tracker` works out what changed since the
+//! last tick. Clients get a `NetPacket::Snapshot` when they join and a `NetPacket::Delta` every
+//! tick after, which `apply` plays back into their world.
+
+use std::collections::{BTreeMap, HashSet};
+use std::io::{self, Cursor};
+use std::marker::PhantomData;
+use std::sync::RwLock;
+use entities::{World, ID};
+use entities::components::{Component, PositionComponent};
+use net::{invalid_data, ComponentState, EntityState, NetPacket, MAX_FRAME_SIZE};
+
+/// How much entity data goes into a single packet, leaving room below the frame size limit.
+pub const MAX_PACKET_DATA: usize = MAX_FRAME_SIZE / 2;
+
+lazy_static! {
+    /// The replicated component types, which the server and clients have to agree on.
+    pub static ref REPLICATION: RwLock<Registry> = {
+        let mut registry = Registry::new();
+
+        registry.register::<PositionComponent>();
+
+        RwLock::new(registry)
+    };
+}
+
+/// A component that is sent from the server to clients.
+///
+/// The type also has to be registered with the `World`s it's used in.
+pub trait Replicated: Component + Sized {
+    /// Identifies the component type on the network. Must be unique among replicated types.
+    const KIND: u16;
+
+    fn write(&self, buf: &mut Vec<u8>) -> io::Result<()>;
+
+    /// Read a component written by `write`.
+    fn read(cursor: &mut Cursor<&[u8]>) -> io::Result<Self>;
+}
+
+/// Knows how to encode and apply every replicated component type.
+pub struct Registry {
+    kinds: BTreeMap<u16, Box<ReplicatorTrait>>,
+}
+
+impl Registry {
+    pub fn new() -> Registry {
+        Registry { kinds: BTreeMap::new() }
+    }
+
+    /// Register a replicated component type.
+    ///
+    /// # Panics.
+    /// Panics if another type already uses the same `KIND`.
+    pub fn register<T: Replicated>(&mut self) {
+        if self.kinds.contains_key(&T::KIND) {
+            panic!("Replicated component kind {} is registered twice.", T::KIND);
+        }
+        self.kinds.insert(T::KIND, Box::new(Replicator::<T>(PhantomData)));
+    }
+
+    /// Encode the replicated components of an entity.
+    pub fn encode(&self, world: &World, id: ID) -> io::Result<EntityState> {
+        let mut components = Vec::new();
+        for (kind, replicator) in &self.kinds {
+            if let Some(data) = replicator.encode(world, id)? {
+                components.push(ComponentState {
+                    kind: *kind,
+                    data: data,
+                });
+            }
+        }
+        Ok(EntityState {
+            id: id,
+            components: components,
+        })
+    }
+
+    /// Encode every entity in a world, sorted by ID.
+    pub fn encode_world(&self, world: &World) -> io::Result<Vec<EntityState>> {
+        let mut ids: Vec<ID> = world.iter_entities().map(|(id, _)| id).collect();
+        ids.sort();
+        ids.into_iter().map(|id| self.encode(world, id)).collect()
+    }
+
+    /// Make an entity in a world match a state, creating it if needed.
+    ///
+    /// Replicated components missing from the state are removed, others are left alone.
+    pub fn apply(&self, world: &mut World, state: &EntityState) -> io::Result<()> {
+        // Check every kind before changing anything.
+        for component in &state.components {
+            if !self.kinds.contains_key(&component.kind) {
+                return Err(invalid_data(format!("unknown component kind {}", component.kind)));
+            }
+        }
+
+        world.create_entity(state.id);
+        for (kind, replicator) in &self.kinds {
+            match state.components.iter().find(|x| x.kind == *kind) {
+                Some(component) => replicator.apply(world, state.id, &component.data)?,
+                None => replicator.remove(world, state.id),
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Default for Registry {
+    fn default() -> Registry {
+        Registry::new()
+    }
+}
+
+trait ReplicatorTrait: Send + Sync {
+    fn encode(&self, world: &World, id: ID) -> io::Result<Option<Vec<u8>>>;
+    fn apply(&self, world: &mut World, id: ID, data: &[u8]) -> io::Result<()>;
+    fn remove(&self, world: &mut World, id: ID);
+}
+
+struct Replicator<T>(PhantomData<fn() -> T>);
+
+impl<T: Replicated> ReplicatorTrait for Replicator<T> {
+    fn encode(&self, world: &World, id: ID) -> io::Result<Option<Vec<u8>>> {
+        match world.get_component::<T>(id) {
+            Some(component) => {
+                let mut buf = Vec::new();
+                component.read().unwrap().write(&mut buf)?;
+                Ok(Some(buf))
+            }
+            None => Ok(None),
+        }
+    }
+
+    fn apply(&self, world: &mut World, id: ID, data: &[u8]) -> io::Result<()> {
+        let mut cursor = Cursor::new(data);
+        let component = T::read(&mut cursor)?;
+        if cursor.position() as usize != data.len() {
+            return Err(invalid_data(format!("trailing bytes after component kind {}", T::KIND)));
+        }
+
+        // Update existing components in place, so anything holding on to them sees the change.
+        match world.get_component::<T>(id) {
+            Some(existing) => *existing.write().unwrap() = component,
+            None => world.add_component(id, component),
+        }
+        Ok(())
+    }
+
+    fn remove(&self, world: &mut World, id: ID) {
+        world.remove_component::<T>(id);
+    }
+}
+
+/// What happened to the replicated entities since the last update.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Changes {
+    pub created: Vec<EntityState>,
+    pub changed: Vec<EntityState>,
+    pub removed: Vec<ID>,
+}
+
+impl Changes {
+    pub fn is_empty(&self) -> bool {
+        self.created.is_empty() && self.changed.is_empty() && self.removed.is_empty()
+    }
+
+    /// Split the changes into `Delta` packets of at most about `MAX_PACKET_DATA` bytes.
+    pub fn into_packets(self, tick: u64) -> Vec<NetPacket> {
+        let mut packets = Vec::new();
+        let mut delta = Changes::default();
+        let mut size = 0;
+
+        for state in self.created {
+            if size > 0 && size + state.size() > MAX_PACKET_DATA {
+                packets.push(delta.take_packet(tick));
+                size = 0;
+            }
+            size += state.size();
+            delta.created.push(state);
+        }
+        for state in self.changed {
+            if size > 0 && size + state.size() > MAX_PACKET_DATA {
+                packets.push(delta.take_packet(tick));
+                size = 0;
+            }
+            size += state.size();
+            delta.changed.push(state);
+        }
+        for id in self.removed {
+            if size + 8 > MAX_PACKET_DATA {
+                packets.push(delta.take_packet(tick));
+                size = 0;
+            }
+            size += 8;
+            delta.removed.push(id);
+        }
+
+        if !delta.is_empty() {
+            packets.push(delta.take_packet(tick));
+        }
+        packets
+    }
+
+    /// Turn the changes into a packet, leaving them empty.
+    fn take_packet(&mut self, tick: u64) -> NetPacket {
+        NetPacket::Delta {
+            tick: tick,
+            created: self.created.drain(..).collect(),
+            changed: self.changed.drain(..).collect(),
+            removed: self.removed.drain(..).collect(),
+        }
+    }
+}
+
+/// Keeps the last sent state of every entity, to work out what changed.
+#[derive(Debug, Default)]
+pub struct Tracker {
+    states: BTreeMap<ID, EntityState>,
+}
+
+impl Tracker {
+    pub fn new() -> Tracker {
+        Tracker::default()
+    }
+
+    /// Compare the current states of every entity with the last ones, and remember them.
+    pub fn update(&mut self, current: Vec<EntityState>) -> Changes {
+        let mut changes = Changes::default();
+        let mut states = BTreeMap::new();
+        for state in current {
+            match self.states.remove(&state.id) {
+                None => changes.created.push(state.clone()),
+                Some(ref old) if *old != state => changes.changed.push(state.clone()),
+                Some(_) => {}
+            }
+            states.insert(state.id, state);
+        }
+        // Whatever wasn't in the current states is gone.
+        changes.removed = self.states.keys().cloned().collect();
+        self.states = states;
+        changes
+    }
+
+    /// Packets with every entity as of the last update, for a client that just joined.
+    ///
+    /// The first is a `Snapshot`, and the rest are `Delta`s creating the entities that didn't fit.
+    pub fn snapshot(&self, tick: u64) -> Vec<NetPacket> {
+        let mut entities = Vec::new();
+        let mut size = 0;
+        let mut rest = Changes::default();
+        for state in self.states.values() {
+            let full = size > 0 && size + state.size() > MAX_PACKET_DATA;
+            if full || !rest.created.is_empty() {
+                rest.created.push(state.clone());
+            } else {
+                size += state.size();
+                entities.push(state.clone());
+            }
+        }
+
+        let mut packets = vec![NetPacket::Snapshot {
+                                   tick: tick,
+                                   entities: entities,
+                               }];
+        packets.extend(rest.into_packets(tick));
+        packets
+    }
+}
+
+/// Play a `Snapshot` or `Delta` from the server back into a world.
+///
+/// Returns `Ok(false)` for any other packet.
+pub fn apply(registry: &Registry, world: &mut World, packet: &NetPacket) -> io::Result<bool> {
+    match *packet {
+        NetPacket::Snapshot { ref entities, .. } => {
+            let kept: HashSet<ID> = entities.iter().map(|x| x.id).collect();
+            let stale: Vec<ID> = world.iter_entities()
+                .map(|(id, _)| id)
+                .filter(|id| !kept.contains(id))
+                .collect();
+            for id in stale {
+                world.remove_entity(id);
+            }
+            for state in entities {
+                registry.apply(world, state)?;
+            }
+        }
+        NetPacket::Delta { ref created, ref changed, ref removed, .. } => {
+            for state in created.iter().chain(changed) {
+                registry.apply(world, state)?;
+            }
+            for id in removed {
+                world.remove_entity(*id);
+            }
+        }
+        _ => return Ok(false),
+    }
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use entities::{make_builder, Positional};
+    use nalgebra::Vector2;
+
+    fn registry() -> Registry {
+        let mut registry = Registry::new();
+        registry.register::<PositionComponent>();
+        registry
+    }
+
+    fn world() -> RwLock<World> {
+        let world = RwLock::new(World::new());
+        world.write().unwrap().register_component::<PositionComponent>();
+        world
+    }
+
+    fn position_of(world: &World, id: ID) -> Option<Positional> {
+        world.get_component::<PositionComponent>(id).map(|x| x.read().unwrap().get_position())
+    }
+
+    #[test]
+    fn test_tracker() {
+        let registry = registry();
+        let server = world();
+        let moved = make_builder(&server).with_component(PositionComponent::empty()).finish();
+        let moved = moved.read().unwrap().get_id();
+        make_builder(&server).finish();
+        let mut tracker = Tracker::new();
+
+        let changes = tracker.update(registry.encode_world(&server.read().unwrap()).unwrap());
+        assert_eq!(changes.created.len(), 2);
+        assert!(changes.changed.is_empty() && changes.removed.is_empty());
+        assert!(tracker.update(registry.encode_world(&server.read().unwrap()).unwrap()).is_empty());
+
+        let position = Positional::new(Vector2::new(1.0, 2.0), 1);
+        server.read()
+            .unwrap()
+            .get_component::<PositionComponent>(moved)
+            .unwrap()
+            .write()
+            .unwrap()
+            .set_position(position);
+        server.write().unwrap().remove_entity(1);
+        let changes = tracker.update(registry.encode_world(&server.read().unwrap()).unwrap());
+        assert!(changes.created.is_empty());
+        assert_eq!(changes.changed.len(), 1);
+        assert_eq!(changes.changed[0].id, moved);
+        assert_eq!(changes.removed, vec![1]);
+    }
+
+    #[test]
+    fn test_apply() {
+        let registry = registry();
+        let server = world();
+        let position = Positional::new(Vector2::new(-3.5, 8.0), 2);
+        make_builder(&server).with_component(PositionComponent::new(position)).finish();
+        make_builder(&server).finish();
+        let mut tracker = Tracker::new();
+        tracker.update(registry.encode_world(&server.read().unwrap()).unwrap());
+
+        let client = world();
+        // Left over from before the snapshot, so it has to go.
+        client.write().unwrap().create_entity(9);
+        for packet in tracker.snapshot(0) {
+            assert!(apply(&registry, &mut client.write().unwrap(), &packet).unwrap());
+        }
+        {
+            let client = client.read().unwrap();
+            assert_eq!(position_of(&client, 0), Some(position));
+            assert!(client.get_entity(1).is_some());
+            assert!(position_of(&client, 1).is_none());
+            assert!(client.get_entity(9).is_none());
+        }
+
+        server.write().unwrap().remove_component::<PositionComponent>(0);
+        server.write().unwrap().remove_entity(1);
+        let changes = tracker.update(registry.encode_world(&server.read().unwrap()).unwrap());
+        for packet in changes.into_packets(1) {
+            assert!(apply(&registry, &mut client.write().unwrap(), &packet).unwrap());
+        }
+        let client = client.read().unwrap();
+        assert!(client.get_entity(0).is_some());
+        assert!(position_of(&client, 0).is_none());
+        assert!(client.get_entity(1).is_none());
+    }
+
+    #[test]
+    fn test_unknown_kind() {
+        let packet = NetPacket::Delta {
+            tick: 0,
+            created: vec![EntityState {
+                              id: 0,
+                              components: vec![ComponentState {
+                                                   kind: 1000,
+                                                   data: Vec::new(),
+                                               }],
+                          }],
+            changed: Vec::new(),
+            removed: Vec::new(),
+        };
+        let client = world();
+        assert!(apply(&registry(), &mut client.write().unwrap(), &packet).is_err());
+        assert!(client.read().unwrap().get_entity(0).is_none());
+        assert!(!apply(&registry(), &mut client.write().unwrap(), &NetPacket::Ping { token: 0 })
+            .unwrap());
+    }
+
+    #[test]
+    fn test_chunking() {
+        let registry = registry();
+        let server = world();
+        let count = MAX_PACKET_DATA / 20 + 10;
+        for _ in 0..count {
+            make_builder(&server).with_component(PositionComponent::empty()).finish();
+        }
+        let mut tracker = Tracker::new();
+        tracker.update(registry.encode_world(&server.read().unwrap()).unwrap());
+
+        let packets = tracker.snapshot(0);
+        assert!(packets.len() > 1);
+        let client = world();
+        for packet in &packets {
+            assert!(apply(&registry, &mut client.write().unwrap(), packet).unwrap());
+        }
+        assert_eq!(client.read().unwrap().iter_entities().count(), count);
+    }
+}
@@ -0,0 +1,2 @@
+pub const MINIMUM_FONT_VERSION: u64 = 1;
+pub const MAXIMUM_FONT_VERSION: u64 = 1;
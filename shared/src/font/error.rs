@@ -0,0 +1,41 @@
+//! Various types related to errors when opening fonts.
+use rsi::RsiError;
+use rustc_serialize::json::BuilderError;
+use std::io::Error as IOError;
+
+/// Represents an error while opening a font.
+#[derive(Debug)]
+pub enum FontError {
+    /// If an error occured during the IO of a file.
+    IO(IOError),
+
+    /// If an error occured during the parsing of the JSON metadata.
+    Json(BuilderError),
+
+    /// If some part of the metadata is corrupt.
+    Metadata(String),
+
+    /// If the version of the font can't be handled by this version of the module.
+    Version,
+
+    /// If the RSI holding the glyphs failed to open.
+    Rsi(RsiError),
+}
+
+impl From<IOError> for FontError {
+    fn from(err: IOError) -> FontError {
+        FontError::IO(err)
+    }
+}
+
+impl From<BuilderError> for FontError {
+    fn from(err: BuilderError) -> FontError {
+        FontError::Json(err)
+    }
+}
+
+impl From<RsiError> for FontError {
+    fn from(err: RsiError) -> FontError {
+        FontError::Rsi(err)
+    }
+}
@@ -0,0 +1,208 @@
+use rsi::{Rsi, RsiRef, StateId};
+use rustc_serialize::json::Json;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+use super::*;
+
+/// A single character of a font.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Glyph {
+    /// The frame of the glyph in the font's state.
+    pub frame: usize,
+
+    /// How far the pen moves after drawing this glyph, in pixels.
+    pub advance: i32,
+}
+
+/// A bitmap font. See the [module documentation](index.html) for the format on disk.
+#[derive(Debug, Clone)]
+pub struct Font {
+    rsi: Rsi,
+    state: StateId,
+    glyphs: HashMap<char, Glyph>,
+    kerning: HashMap<(char, char), i32>,
+    line_height: u32,
+    fallback: Option<char>,
+}
+
+impl Font {
+    /// Opens a font from the file system.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Font, FontError> {
+        let path = path.as_ref();
+
+        let mut meta_content = String::new();
+        File::open(path.join("meta.json"))?.read_to_string(&mut meta_content)?;
+
+        let json = match Json::from_str(&meta_content)? {
+            Json::Object(a) => a,
+            _ => return Err(FontError::Metadata("Not a root object".to_string())),
+        };
+
+        match json.get("version") {
+            Some(&Json::U64(version)) => {
+                if MAXIMUM_FONT_VERSION < version || version < MINIMUM_FONT_VERSION {
+                    return Err(FontError::Version);
+                }
+            }
+            _ => return Err(FontError::Metadata("Version not a number.".to_string())),
+        };
+
+        let characters = match json.get("characters") {
+            Some(&Json::String(ref characters)) => characters,
+            _ => return Err(FontError::Metadata("Characters not a string.".to_string())),
+        };
+
+        let line_height = match json.get("line_height") {
+            Some(&Json::U64(height)) => height as u32,
+            _ => return Err(FontError::Metadata("Line height not a number.".to_string())),
+        };
+
+        let rsi_path = match json.get("rsi") {
+            Some(&Json::String(ref rsi)) => rsi.as_str(),
+            None => "glyphs.rsi",
+            _ => return Err(FontError::Metadata("RSI not a string.".to_string())),
+        };
+
+        let state = match json.get("state") {
+            Some(&Json::String(ref state)) => StateId::new(state),
+            None => StateId::new("glyphs"),
+            _ => return Err(FontError::Metadata("State not a string.".to_string())),
+        };
+
+        let fallback = match json.get("fallback") {
+            Some(&Json::String(ref fallback)) => Some(single_char(fallback)?),
+            None => None,
+            _ => return Err(FontError::Metadata("Fallback not a string.".to_string())),
+        };
+
+        let rsi = Rsi::open(path.join(rsi_path))?;
+        let frames = match rsi.get_stateid(&state) {
+            Some(state) => state.get_icons_vec().get(0).map(|x| x.len()).unwrap_or(0),
+            None => return Err(FontError::Metadata(format!("State missing: {:?}", state))),
+        };
+
+        let default_advance = rsi.get_size().0 as i32;
+        let mut glyphs = HashMap::new();
+        for (frame, character) in characters.chars().enumerate() {
+            if frame >= frames {
+                return Err(FontError::Metadata(format!("No frame for: {:?}", character)));
+            }
+            glyphs.insert(character,
+                          Glyph {
+                              frame: frame,
+                              advance: default_advance,
+                          });
+        }
+
+        match json.get("advances") {
+            Some(&Json::Object(ref advances)) => {
+                for (character, advance) in advances {
+                    let character = single_char(character)?;
+                    let glyph = match glyphs.get_mut(&character) {
+                        Some(glyph) => glyph,
+                        None => {
+                            return Err(FontError::Metadata(format!("Unknown character: {:?}",
+                                                                   character)))
+                        }
+                    };
+                    glyph.advance = match json_int(advance) {
+                        Some(advance) => advance as i32,
+                        None => return Err(FontError::Metadata("Advance not int.".to_string())),
+                    };
+                }
+            }
+            None => {}
+            _ => return Err(FontError::Metadata("Advances not an object.".to_string())),
+        };
+
+        let mut kerning = HashMap::new();
+        match json.get("kerning") {
+            Some(&Json::Array(ref pairs)) => {
+                for pair in pairs {
+                    let (left, right, amount) = match *pair {
+                        Json::Array(ref pair) if pair.len() == 3 => {
+                            match (&pair[0], &pair[1], json_int(&pair[2])) {
+                                (&Json::String(ref left),
+                                 &Json::String(ref right),
+                                 Some(amount)) => {
+                                    (single_char(left)?, single_char(right)?, amount as i32)
+                                }
+                                _ => {
+                                    return Err(FontError::Metadata("Invalid kerning pair."
+                                        .to_string()))
+                                }
+                            }
+                        }
+                        _ => return Err(FontError::Metadata("Kerning not a list.".to_string())),
+                    };
+                    kerning.insert((left, right), amount);
+                }
+            }
+            None => {}
+            _ => return Err(FontError::Metadata("Kerning not an array.".to_string())),
+        };
+
+        Ok(Font {
+            rsi: rsi,
+            state: state,
+            glyphs: glyphs,
+            kerning: kerning,
+            line_height: line_height,
+            fallback: fallback,
+        })
+    }
+}
+
+impl Font {
+    /// Returns the RSI the glyphs are stored in.
+    pub fn get_rsi(&self) -> &Rsi {
+        &self.rsi
+    }
+
+    /// Returns roughly how many bytes of memory the glyphs of this font use.
+    pub fn memory_size(&self) -> usize {
+        self.rsi.memory_size()
+    }
+
+    /// Returns the distance between lines, in pixels.
+    pub fn get_line_height(&self) -> u32 {
+        self.line_height
+    }
+
+    /// Returns the glyph for a character.
+    ///
+    /// If the font doesn't have the character, the fallback glyph is returned, if any.
+    pub fn get_glyph(&self, character: char) -> Option<&Glyph> {
+        self.glyphs
+            .get(&character)
+            .or_else(|| self.fallback.and_then(|x| self.glyphs.get(&x)))
+    }
+
+    /// Returns the position of a glyph inside the RSI.
+    pub fn get_rsiref(&self, glyph: &Glyph) -> RsiRef {
+        RsiRef::new(&self.state, 0, glyph.frame)
+    }
+
+    /// Returns the adjustment to the space between two characters, in pixels.
+    pub fn get_kerning(&self, left: char, right: char) -> i32 {
+        self.kerning.get(&(left, right)).cloned().unwrap_or(0)
+    }
+}
+
+fn single_char(string: &str) -> Result<char, FontError> {
+    let mut chars = string.chars();
+    match (chars.next(), chars.next()) {
+        (Some(character), None) => Ok(character),
+        _ => Err(FontError::Metadata(format!("Not a single character: {:?}", string))),
+    }
+}
+
+fn json_int(json: &Json) -> Option<i64> {
+    match *json {
+        Json::U64(x) => Some(x as i64),
+        Json::I64(x) => Some(x),
+        _ => None,
+    }
+}
@@ -0,0 +1,139 @@
+use rsi::RsiRef;
+use super::Font;
+
+/// A glyph placed somewhere in a `TextLayout`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PositionedGlyph {
+    pub character: char,
+
+    /// Which icon of the font's RSI to draw.
+    pub rsiref: RsiRef,
+
+    /// The position of the top left corner, relative to the top left of the text.
+    pub x: i32,
+    pub y: i32,
+}
+
+/// Text laid out by a `Font`, ready to be drawn.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TextLayout {
+    pub glyphs: Vec<PositionedGlyph>,
+
+    /// The width of the widest line, in pixels.
+    pub width: u32,
+
+    /// The height of all lines together, in pixels.
+    pub height: u32,
+
+    /// The amount of lines the text was laid out over.
+    pub lines: usize,
+}
+
+impl Font {
+    /// Lays out a string of text.
+    ///
+    /// Lines are broken at newlines, and if `max_width` is given,
+    /// at spaces so that no line is wider than `max_width` pixels.
+    /// Words too wide to fit on a line by themselves are broken between characters.
+    pub fn layout(&self, text: &str, max_width: Option<u32>) -> TextLayout {
+        let lines = match max_width {
+            Some(max_width) => self.wrap(text, max_width as i32),
+            None => text.split('\n').map(|x| x.to_owned()).collect(),
+        };
+
+        let mut layout = TextLayout {
+            glyphs: Vec::new(),
+            width: 0,
+            height: lines.len() as u32 * self.get_line_height(),
+            lines: lines.len(),
+        };
+
+        for (index, line) in lines.iter().enumerate() {
+            let y = (index as u32 * self.get_line_height()) as i32;
+            let mut x = 0;
+            let mut previous = None;
+            for character in line.chars() {
+                let glyph = match self.get_glyph(character) {
+                    Some(glyph) => glyph,
+                    None => continue,
+                };
+
+                if let Some(previous) = previous {
+                    x += self.get_kerning(previous, character);
+                }
+                layout.glyphs.push(PositionedGlyph {
+                    character: character,
+                    rsiref: self.get_rsiref(glyph),
+                    x: x,
+                    y: y,
+                });
+                x += glyph.advance;
+                previous = Some(character);
+            }
+            layout.width = layout.width.max(x.max(0) as u32);
+        }
+
+        layout
+    }
+
+    /// Returns the width and height the text would take up, in pixels.
+    pub fn measure(&self, text: &str, max_width: Option<u32>) -> (u32, u32) {
+        let layout = self.layout(text, max_width);
+        (layout.width, layout.height)
+    }
+
+    /// Returns the width of a single line of text, in pixels.
+    pub fn line_width(&self, line: &str) -> i32 {
+        let mut width = 0;
+        let mut previous = None;
+        for character in line.chars() {
+            if let Some(glyph) = self.get_glyph(character) {
+                if let Some(previous) = previous {
+                    width += self.get_kerning(previous, character);
+                }
+                width += glyph.advance;
+                previous = Some(character);
+            }
+        }
+        width
+    }
+
+    /// Splits text into lines no wider than `max_width`.
+    fn wrap(&self, text: &str, max_width: i32) -> Vec<String> {
+        let mut lines = Vec::new();
+        for paragraph in text.split('\n') {
+            let mut line = String::new();
+            for word in paragraph.split(' ') {
+                let candidate = if line.is_empty() {
+                    word.to_owned()
+                } else {
+                    format!("{} {}", line, word)
+                };
+
+                if self.line_width(&candidate) <= max_width {
+                    line = candidate;
+                    continue;
+                }
+
+                if !line.is_empty() {
+                    lines.push(line);
+                    line = String::new();
+                }
+
+                // The word goes on a line of its own, broken up if it's still too wide.
+                for character in word.chars() {
+                    let mut next = line.clone();
+                    next.push(character);
+                    if !line.is_empty() && self.line_width(&next) > max_width {
+                        lines.push(line);
+                        line = character.to_string();
+                    } else {
+                        line = next;
+                    }
+                }
+            }
+            lines.push(line);
+        }
+        lines
+    }
+}
@@ -0,0 +1,28 @@
+//! Bitmap fonts, with their glyphs stored in an RSI.
+//!
+//! A font is a folder with a name ending in `.font`.
+//! It contains a `meta.json` describing the font, and an RSI holding the glyphs.
+//! The glyphs are the frames of a single state, in the order of the `characters` string.
+//!
+//! Key | Meaning
+//! --- | -------
+//! `version` | The font format version, currently `1`.
+//! `characters` | A string of all characters in the font, in the order of the frames.
+//! `line_height` | The distance between lines, in pixels.
+//! `rsi`* | The path of the RSI relative to the font folder. Defaults to `glyphs.rsi`.
+//! `state`* | The state holding the glyphs. Defaults to `glyphs`.
+//! `advances`* | An object of `character: pixels`, how far to move after a glyph. Defaults to the RSI width.
+//! `kerning`* | A list of `[left, right, pixels]` adjusting the space between two characters.
+//! `fallback`* | A character drawn in place of characters the font doesn't have.
+//!
+//! <sup>\* Optional value.</sup>
+
+pub mod constants;
+pub mod error;
+pub mod font;
+pub mod layout;
+
+pub use self::constants::{MAXIMUM_FONT_VERSION, MINIMUM_FONT_VERSION};
+pub use self::error::FontError;
+pub use self::font::{Font, Glyph};
+pub use self::layout::{PositionedGlyph, TextLayout};
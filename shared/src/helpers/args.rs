@@ -0,0 +1,28 @@
+//! Tiny helpers for reading command line arguments.
+
+/// Returns every value given to a command line flag, in order.
+///
+/// Values can be given as either `--flag value` or `--flag=value`.
+pub fn flag_values(args: &[String], flag: &str) -> Vec<String> {
+    let prefix = format!("{}=", flag);
+    let mut values = Vec::new();
+    let mut iter = args.iter();
+
+    while let Some(arg) = iter.next() {
+        if arg == flag {
+            if let Some(value) = iter.next() {
+                values.push(value.clone());
+            }
+        } else if arg.starts_with(&prefix) {
+            values.push(arg[prefix.len()..].to_owned());
+        }
+    }
+    values
+}
+
+/// Returns the value of a command line flag.
+///
+/// If the flag is given more than once, the last one wins.
+pub fn flag_value(args: &[String], flag: &str) -> Option<String> {
+    flag_values(args, flag).pop()
+}
@@ -12,11 +12,21 @@ extern crate nalgebra;
 extern crate mopa;
 extern crate tokio_io;
 extern crate bytes;
+extern crate hound;
+extern crate lewton;
+extern crate byteorder;
+extern crate sha2;
+extern crate hmac;
+extern crate pbkdf2;
+extern crate subtle;
 
 pub mod helpers;
 pub mod rsi;
+pub mod font;
 pub mod config;
 pub mod logs;
 pub mod assets;
+pub mod locate;
 pub mod entities;
 pub mod net;
+pub mod sound;
@@ -0,0 +1,223 @@
+//! Finding data and configuration files on disk.
+//!
+//! Packaged builds keep `data/` and `config/` next to the executable,
+//! while `cargo run` builds live deep inside `target/`.
+//! A [`Locator`](struct.Locator.html) checks, in order:
+//!
+//! 1. A command line flag, such as `--data <path>`.
+//! 2. An environment variable.
+//! 3. The directory the executable is in.
+//! 4. The current working directory.
+//! 5. The source repository layout.
+//!
+//! An explicitly given flag or environment variable is never skipped over:
+//! if it points to something that doesn't exist, locating fails.
+
+use helpers::args::flag_value;
+use std::env;
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+/// Where a candidate path came from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SearchSource {
+    /// A command line flag.
+    Flag,
+
+    /// An environment variable.
+    Environment,
+
+    /// The directory containing the executable.
+    Executable,
+
+    /// The current working directory.
+    WorkingDirectory,
+
+    /// The layout of the source repository, for `cargo run` and tests.
+    Repository,
+}
+
+impl fmt::Display for SearchSource {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match *self {
+            SearchSource::Flag => "command line flag",
+            SearchSource::Environment => "environment variable",
+            SearchSource::Executable => "executable directory",
+            SearchSource::WorkingDirectory => "working directory",
+            SearchSource::Repository => "repository",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// A path that was found by a `Locator`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Located {
+    /// The absolute path that was found.
+    pub path: PathBuf,
+
+    /// Where the path came from.
+    pub source: SearchSource,
+}
+
+/// Returned when a `Locator` couldn't find anything.
+#[derive(Debug, Clone)]
+pub struct LocateError {
+    /// The human readable name of what was being looked for.
+    pub name: String,
+
+    /// Every path that was checked, in order.
+    pub searched: Vec<(SearchSource, PathBuf)>,
+}
+
+impl fmt::Display for LocateError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "unable to find {}, looked in:", self.name)?;
+        for &(source, ref path) in &self.searched {
+            write!(f, "\n  {}: {}", source, path.display())?;
+        }
+        Ok(())
+    }
+}
+
+/// Looks for a file or directory in all the places it could reasonably be.
+///
+/// # Example
+///
+/// ```
+/// use yasss13rtwcf_shared::locate::Locator;
+/// let locator = Locator::new("asset directory", "data")
+///               .with_flag("--data")
+///               .with_env("YASSS_DATA")
+///               .with_repo_path("data");
+/// ```
+#[derive(Debug, Clone)]
+pub struct Locator {
+    name: String,
+    relative: PathBuf,
+    flag: Option<String>,
+    env: Option<String>,
+    repo: Option<PathBuf>,
+}
+
+impl Locator {
+    /// Creates a new locator.
+    ///
+    /// `name` is used in error messages and logs.
+    /// `relative` is the path to look for, relative to the executable and working directories.
+    pub fn new<P: AsRef<Path>>(name: &str, relative: P) -> Locator {
+        Locator {
+            name: name.to_owned(),
+            relative: relative.as_ref().to_owned(),
+            flag: None,
+            env: None,
+            repo: None,
+        }
+    }
+
+    /// Sets the command line flag that can override the location.
+    pub fn with_flag(mut self, flag: &str) -> Locator {
+        self.flag = Some(flag.to_owned());
+        self
+    }
+
+    /// Sets the environment variable that can override the location.
+    pub fn with_env(mut self, var: &str) -> Locator {
+        self.env = Some(var.to_owned());
+        self
+    }
+
+    /// Sets the path relative to the root of the source repository.
+    pub fn with_repo_path<P: AsRef<Path>>(mut self, path: P) -> Locator {
+        self.repo = Some(path.as_ref().to_owned());
+        self
+    }
+
+    /// Returns the human readable name of what this locator looks for.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns the path given explicitly through the flag or environment variable, if any.
+    ///
+    /// The path is returned even if nothing exists there yet.
+    pub fn explicit(&self, args: &[String]) -> Option<Located> {
+        let cwd = env::current_dir().unwrap_or_default();
+        if let Some(value) = self.flag.as_ref().and_then(|flag| flag_value(args, flag)) {
+            return Some(Located {
+                path: cwd.join(value),
+                source: SearchSource::Flag,
+            });
+        }
+
+        if let Some(value) = self.env.as_ref().and_then(|var| env::var_os(var)) {
+            return Some(Located {
+                path: cwd.join(value),
+                source: SearchSource::Environment,
+            });
+        }
+        None
+    }
+
+    /// Returns the implicit places this locator looks in, in order.
+    pub fn candidates(&self) -> Vec<(SearchSource, PathBuf)> {
+        let mut candidates = Vec::new();
+        if let Some(dir) = env::current_exe().ok().and_then(|x| x.parent().map(|x| x.to_owned())) {
+            candidates.push((SearchSource::Executable, dir.join(&self.relative)));
+        }
+
+        if let Ok(dir) = env::current_dir() {
+            candidates.push((SearchSource::WorkingDirectory, dir.join(&self.relative)));
+        }
+
+        if let Some(ref repo) = self.repo {
+            // The shared crate sits right below the repository root.
+            let root = Path::new(env!("CARGO_MANIFEST_DIR")).parent().unwrap();
+            candidates.push((SearchSource::Repository, root.join(repo)));
+        }
+        candidates
+    }
+
+    /// Locates the path using the process' own command line arguments.
+    pub fn locate(&self) -> Result<Located, LocateError> {
+        self.locate_with_args(&env::args().collect::<Vec<String>>())
+    }
+
+    /// Locates the path using a given set of command line arguments.
+    pub fn locate_with_args(&self, args: &[String]) -> Result<Located, LocateError> {
+        if let Some(located) = self.explicit(args) {
+            if located.path.exists() {
+                return Ok(located);
+            }
+            return Err(LocateError {
+                name: self.name.clone(),
+                searched: vec![(located.source, located.path)],
+            });
+        }
+
+        let candidates = self.candidates();
+        for &(source, ref path) in &candidates {
+            if path.exists() {
+                return Ok(Located {
+                    path: path.clone(),
+                    source: source,
+                });
+            }
+        }
+
+        Err(LocateError {
+            name: self.name.clone(),
+            searched: candidates,
+        })
+    }
+}
+
+/// Returns a locator for the asset directory, `data`.
+///
+/// It can be overriden with `--data <path>` or `YASSS_DATA`.
+pub fn assets() -> Locator {
+    Locator::new("asset directory", "data")
+        .with_flag("--data")
+        .with_env("YASSS_DATA")
+        .with_repo_path("data")
+}
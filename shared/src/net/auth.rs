@@ -0,0 +1,176 @@
+//! Account logins.
+//!
+//! Passwords never cross the network, and the server only stores a key it can check proofs
+//! with, not one it can log in with. This works like SCRAM-SHA-256:
+//!
+//! * The salted password is `PBKDF2(password, salt, iterations)`.
+//! * The client key is `HMAC(salted password, "Client Key")`, and the stored key its SHA-256.
+//! * The server challenges the client with a random nonce, and the client proves it knows the
+//!   client key by sending `client key XOR HMAC(stored key, nonce)`.
+
+use byteorder::{NetworkEndian, ReadBytesExt, WriteBytesExt};
+use hmac::{Hmac, Mac};
+use pbkdf2::pbkdf2;
+use sha2::{Digest, Sha256};
+use subtle::ConstantTimeEq;
+use std::io::{self, Read, Write};
+use super::{invalid_data, read_bytes, read_string, write_bytes, write_string};
+
+/// Size of a SHA-256 digest, in bytes.
+pub const DIGEST_SIZE: usize = 32;
+
+/// The first byte of a `Challenge`, which tells it apart from a `HandshakeReply`.
+pub const CHALLENGE_CODE: u8 = 0xff;
+
+/// The longest account name, in bytes.
+pub const MAX_ACCOUNT_LENGTH: usize = 64;
+
+/// The longest session token, in bytes.
+pub const MAX_TOKEN_LENGTH: usize = 128;
+
+/// The largest salt or nonce accepted in a challenge, in bytes.
+pub const MAX_CHALLENGE_DATA: usize = 256;
+
+/// The most PBKDF2 iterations a client will do for a challenge.
+pub const MAX_ITERATIONS: u32 = 1000000;
+
+/// How a client logs in, sent at the end of `Handshake::Connect`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Login {
+    /// No account.
+    Guest,
+    /// Log into an account with a password, which makes the server send a `Challenge`.
+    Account(String),
+    /// Log back in with a session token from an earlier connection.
+    Session(String),
+}
+
+impl Login {
+    pub fn write<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        match *self {
+            Login::Guest => writer.write_u8(0),
+            Login::Account(ref name) => {
+                writer.write_u8(1)?;
+                write_string(writer, name)
+            }
+            Login::Session(ref token) => {
+                writer.write_u8(2)?;
+                write_string(writer, token)
+            }
+        }
+    }
+
+    pub fn read<R: Read>(reader: &mut R) -> io::Result<Login> {
+        match reader.read_u8()? {
+            0 => Ok(Login::Guest),
+            1 => Ok(Login::Account(read_string(reader, MAX_ACCOUNT_LENGTH)?)),
+            2 => Ok(Login::Session(read_string(reader, MAX_TOKEN_LENGTH)?)),
+            kind => Err(invalid_data(format!("unknown login kind {}", kind))),
+        }
+    }
+}
+
+/// Sent by the server in answer to `Login::Account`, before the `HandshakeReply`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Challenge {
+    pub salt: Vec<u8>,
+    pub iterations: u32,
+    pub nonce: Vec<u8>,
+}
+
+impl Challenge {
+    pub fn write<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        writer.write_u8(CHALLENGE_CODE)?;
+        write_bytes(writer, &self.salt)?;
+        writer.write_u32::<NetworkEndian>(self.iterations)?;
+        write_bytes(writer, &self.nonce)?;
+        writer.flush()
+    }
+
+    pub fn read<R: Read>(reader: &mut R) -> io::Result<Challenge> {
+        if reader.read_u8()? != CHALLENGE_CODE {
+            return Err(invalid_data("expected a login challenge"));
+        }
+        let salt = read_bytes(reader, MAX_CHALLENGE_DATA)?;
+        let iterations = reader.read_u32::<NetworkEndian>()?;
+        if iterations == 0 || iterations > MAX_ITERATIONS {
+            return Err(invalid_data(format!("{} iterations is outside of the allowed 1 to {}",
+                                            iterations,
+                                            MAX_ITERATIONS)));
+        }
+        Ok(Challenge {
+            salt: salt,
+            iterations: iterations,
+            nonce: read_bytes(reader, MAX_CHALLENGE_DATA)?,
+        })
+    }
+}
+
+/// What the server stores to check an account's logins.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Credentials {
+    pub salt: Vec<u8>,
+    pub iterations: u32,
+    pub stored_key: [u8; DIGEST_SIZE],
+}
+
+impl Credentials {
+    /// Derive the credentials for a password.
+    pub fn new(password: &str, salt: Vec<u8>, iterations: u32) -> Credentials {
+        let client_key = client_key(password, &salt, iterations);
+        Credentials {
+            salt: salt,
+            iterations: iterations,
+            stored_key: sha256(&client_key),
+        }
+    }
+
+    /// A challenge for these credentials with the given nonce.
+    pub fn challenge(&self, nonce: Vec<u8>) -> Challenge {
+        Challenge {
+            salt: self.salt.clone(),
+            iterations: self.iterations,
+            nonce: nonce,
+        }
+    }
+
+    /// Check a client's answer to a challenge with `nonce`.
+    pub fn verify(&self, nonce: &[u8], proof: &[u8]) -> bool {
+        if proof.len() != DIGEST_SIZE {
+            return false;
+        }
+        let signature = hmac(&self.stored_key, nonce);
+        let client_key: Vec<u8> = proof.iter().zip(signature.iter()).map(|(p, s)| p ^ s).collect();
+        sha256(&client_key).ct_eq(&self.stored_key).unwrap_u8() == 1
+    }
+}
+
+/// Answer a challenge, proving the client knows `password` without sending it.
+pub fn proof(password: &str, challenge: &Challenge) -> Vec<u8> {
+    let client_key = client_key(password, &challenge.salt, challenge.iterations);
+    let stored_key = sha256(&client_key);
+    let signature = hmac(&stored_key, &challenge.nonce);
+    client_key.iter().zip(signature.iter()).map(|(k, s)| k ^ s).collect()
+}
+
+/// HMAC-SHA256 of `data` with `key`.
+pub fn hmac(key: &[u8], data: &[u8]) -> [u8; DIGEST_SIZE] {
+    // HMAC takes keys of any length.
+    let mut mac = Hmac::<Sha256>::new_varkey(key).unwrap();
+    mac.input(data);
+    let mut result = [0; DIGEST_SIZE];
+    result.copy_from_slice(&mac.result().code());
+    result
+}
+
+fn sha256(data: &[u8]) -> [u8; DIGEST_SIZE] {
+    let mut result = [0; DIGEST_SIZE];
+    result.copy_from_slice(&Sha256::digest(data));
+    result
+}
+
+fn client_key(password: &str, salt: &[u8], iterations: u32) -> [u8; DIGEST_SIZE] {
+    let mut salted = [0; DIGEST_SIZE];
+    pbkdf2::<Hmac<Sha256>>(password.as_bytes(), salt, iterations as usize, &mut salted);
+    hmac(&salted, b"Client Key")
+}
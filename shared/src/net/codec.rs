@@ -0,0 +1,281 @@
+use byteorder::{ByteOrder, NetworkEndian};
+use bytes::BytesMut;
+use std::io::{self, Cursor};
+use tokio_io::codec::{Encoder, Decoder};
+use super::{invalid_data, read_bytes, write_bytes, Handshake, HandshakeLimits, HandshakeReply,
+            NetPacket, ServerStatus};
+use super::auth::{Challenge, Login, CHALLENGE_CODE, DIGEST_SIZE};
+
+/// The default maximum size of a single frame, excluding the length prefix.
+pub const MAX_FRAME_SIZE: usize = 64 * 1024;
+
+/// Frames `NetPacket`s with an `u32` length prefix.
+#[derive(Debug, Clone)]
+pub struct NetCodec {
+    max_frame_size: usize,
+}
+
+impl NetCodec {
+    pub fn new() -> NetCodec {
+        NetCodec::with_max_frame_size(MAX_FRAME_SIZE)
+    }
+
+    /// Create a codec that rejects frames over `max_frame_size` bytes, in both directions.
+    pub fn with_max_frame_size(max_frame_size: usize) -> NetCodec {
+        NetCodec { max_frame_size: max_frame_size }
+    }
+
+    pub fn get_max_frame_size(&self) -> usize {
+        self.max_frame_size
+    }
+
+    fn check_size(&self, len: usize) -> io::Result<()> {
+        if len > self.max_frame_size {
+            return Err(invalid_data(format!("frame of {} bytes is over the limit of {}",
+                                            len,
+                                            self.max_frame_size)));
+        }
+        Ok(())
+    }
+}
+
+impl Default for NetCodec {
+    fn default() -> NetCodec {
+        NetCodec::new()
+    }
+}
+
+impl Encoder for NetCodec {
+    type Item = NetPacket;
+    type Error = io::Error;
+
+    fn encode(&mut self, item: NetPacket, dst: &mut BytesMut) -> Result<(), io::Error> {
+        let mut frame = Vec::new();
+        item.write(&mut frame)?;
+        self.check_size(frame.len())?;
+
+        let mut len = [0; 4];
+        NetworkEndian::write_u32(&mut len, frame.len() as u32);
+        dst.reserve(len.len() + frame.len());
+        dst.extend_from_slice(&len);
+        dst.extend_from_slice(&frame);
+        Ok(())
+    }
+}
+
+impl Decoder for NetCodec {
+    type Item = NetPacket;
+    type Error = io::Error;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<NetPacket>, io::Error> {
+        if src.len() < 4 {
+            return Ok(None);
+        }
+
+        let len = NetworkEndian::read_u32(&src[..4]) as usize;
+        // Checked before waiting for the rest, so a bad length can't make us buffer forever.
+        self.check_size(len)?;
+        if len == 0 {
+            return Err(invalid_data("empty frame"));
+        }
+        if src.len() < 4 + len {
+            src.reserve(4 + len - src.len());
+            return Ok(None);
+        }
+
+        let frame = src.split_to(4 + len);
+        NetPacket::read(&frame[4..]).map(Some)
+    }
+}
+
+/// Something a client sends to the server.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ClientMessage {
+    /// Always the first message on a connection, and only ever the first.
+    Handshake(Handshake),
+    /// The answer to a `Challenge`, from `auth::proof`.
+    Proof(Vec<u8>),
+    Packet(NetPacket),
+}
+
+/// Something the server sends to a client.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ServerMessage {
+    /// Sent before the reply when a client logs into an account.
+    Challenge(Challenge),
+    /// The answer to a `Handshake::Connect`.
+    Reply(HandshakeReply),
+    /// The answer to a `Handshake::Poll`.
+    Status(ServerStatus),
+    Packet(NetPacket),
+}
+
+/// What the server side of a connection expects to read next.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ServerState {
+    Handshake,
+    Proof,
+    Packets,
+}
+
+/// The server side of a connection: reads the handshake, the login proof if the client is
+/// logging into an account, then framed packets.
+#[derive(Debug, Clone)]
+pub struct ServerCodec {
+    state: ServerState,
+    limits: HandshakeLimits,
+    packets: NetCodec,
+}
+
+impl ServerCodec {
+    pub fn new(packets: NetCodec) -> ServerCodec {
+        ServerCodec::with_limits(packets, HandshakeLimits::default())
+    }
+
+    /// Create a codec that rejects handshakes over `limits`.
+    pub fn with_limits(packets: NetCodec, limits: HandshakeLimits) -> ServerCodec {
+        ServerCodec {
+            state: ServerState::Handshake,
+            limits: limits,
+            packets: packets,
+        }
+    }
+}
+
+impl Encoder for ServerCodec {
+    type Item = ServerMessage;
+    type Error = io::Error;
+
+    fn encode(&mut self, item: ServerMessage, dst: &mut BytesMut) -> Result<(), io::Error> {
+        let mut buf = Vec::new();
+        match item {
+            ServerMessage::Challenge(challenge) => challenge.write(&mut buf)?,
+            ServerMessage::Reply(reply) => reply.write(&mut buf)?,
+            ServerMessage::Status(status) => status.write(&mut buf)?,
+            ServerMessage::Packet(packet) => return self.packets.encode(packet, dst),
+        }
+        dst.extend_from_slice(&buf);
+        Ok(())
+    }
+}
+
+impl Decoder for ServerCodec {
+    type Item = ClientMessage;
+    type Error = io::Error;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<ClientMessage>, io::Error> {
+        match self.state {
+            ServerState::Handshake => {}
+            ServerState::Proof => {
+                let proof = read_partial(src, |cursor| read_bytes(cursor, DIGEST_SIZE))?;
+                if proof.is_some() {
+                    self.state = ServerState::Packets;
+                }
+                return Ok(proof.map(ClientMessage::Proof));
+            }
+            ServerState::Packets => {
+                return self.packets.decode(src).map(|x| x.map(ClientMessage::Packet));
+            }
+        }
+
+        let limits = self.limits;
+        match read_partial(src, |cursor| Handshake::read_limited(cursor, &limits))? {
+            Some(handshake) => {
+                self.state = match handshake {
+                    Handshake::Connect { login: Login::Account(_), .. } => ServerState::Proof,
+                    _ => ServerState::Packets,
+                };
+                Ok(Some(ClientMessage::Handshake(handshake)))
+            }
+            None => Ok(None),
+        }
+    }
+}
+
+/// Run a blocking reader over the buffered bytes, consuming them if it succeeds.
+///
+/// Returns `None` if the reader ran out of data, so it can be tried again once more arrives.
+/// This is only meant for the small, bounded messages of the handshake.
+fn read_partial<T, F>(src: &mut BytesMut, read: F) -> io::Result<Option<T>>
+    where F: FnOnce(&mut Cursor<&[u8]>) -> io::Result<T>
+{
+    let (result, used) = {
+        let mut cursor = Cursor::new(&src[..]);
+        let result = read(&mut cursor);
+        (result, cursor.position() as usize)
+    };
+
+    match result {
+        Ok(value) => {
+            src.split_to(used);
+            Ok(Some(value))
+        }
+        Err(ref error) if error.kind() == io::ErrorKind::UnexpectedEof => Ok(None),
+        Err(error) => Err(error),
+    }
+}
+
+/// The client side of a connection: writes the handshake, reads the reply, then framed packets.
+#[derive(Debug, Clone)]
+pub struct ClientCodec {
+    replied: bool,
+    packets: NetCodec,
+}
+
+impl ClientCodec {
+    pub fn new(packets: NetCodec) -> ClientCodec {
+        ClientCodec {
+            replied: false,
+            packets: packets,
+        }
+    }
+}
+
+impl Encoder for ClientCodec {
+    type Item = ClientMessage;
+    type Error = io::Error;
+
+    fn encode(&mut self, item: ClientMessage, dst: &mut BytesMut) -> Result<(), io::Error> {
+        match item {
+            ClientMessage::Handshake(handshake) => {
+                let mut buf = Vec::new();
+                handshake.write(&mut buf)?;
+                dst.extend_from_slice(&buf);
+                Ok(())
+            }
+            ClientMessage::Proof(proof) => {
+                let mut buf = Vec::new();
+                write_bytes(&mut buf, &proof)?;
+                dst.extend_from_slice(&buf);
+                Ok(())
+            }
+            ClientMessage::Packet(packet) => self.packets.encode(packet, dst),
+        }
+    }
+}
+
+impl Decoder for ClientCodec {
+    type Item = ServerMessage;
+    type Error = io::Error;
+
+    /// Decodes the handshake reply first, and any login challenge before it. Polls are answered
+    /// with a `ServerStatus` instead, use `status::query` for those.
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<ServerMessage>, io::Error> {
+        if self.replied {
+            return self.packets.decode(src).map(|x| x.map(ServerMessage::Packet));
+        }
+
+        if src.first() == Some(&CHALLENGE_CODE) {
+            let challenge = read_partial(src, |cursor| Challenge::read(cursor))?;
+            return Ok(challenge.map(ServerMessage::Challenge));
+        }
+
+        match read_partial(src, |cursor| HandshakeReply::read(cursor))? {
+            Some(reply) => {
+                self.replied = true;
+                Ok(Some(ServerMessage::Reply(reply)))
+            }
+            None => Ok(None),
+        }
+    }
+}
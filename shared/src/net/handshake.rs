@@ -0,0 +1,403 @@
+use byteorder::{NetworkEndian, ReadBytesExt, WriteBytesExt};
+use std::fmt;
+use std::io::{self, Read, Write};
+use super::{invalid_data, read_string, write_string};
+use super::auth::{Login, CHALLENGE_CODE, MAX_TOKEN_LENGTH};
+
+/// The protocol version spoken by this build.
+///
+/// Bump this whenever the handshake or packet formats change in a way older builds can't read.
+pub const PROTOCOL_VERSION: u32 = 6;
+
+/// The oldest protocol version this build can still talk to.
+pub const MIN_PROTOCOL_VERSION: u32 = 6;
+
+/// The longest nickname accepted during the handshake by default, in bytes.
+pub const MAX_NICKNAME_LENGTH: usize = 256;
+
+/// The longest version string accepted during the handshake by default, in bytes.
+///
+/// Its length is sent as an `u8`, so it can't be longer than this anyway.
+pub const MAX_VERSION_LENGTH: usize = 255;
+
+/// The longest rejection message accepted from a server, in bytes.
+pub const MAX_REJECTION_LENGTH: usize = 4096;
+
+/// Size limits for reading a handshake, checked before anything is allocated.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HandshakeLimits {
+    pub max_version_length: usize,
+    pub max_nickname_length: usize,
+}
+
+impl Default for HandshakeLimits {
+    fn default() -> HandshakeLimits {
+        HandshakeLimits {
+            max_version_length: MAX_VERSION_LENGTH,
+            max_nickname_length: MAX_NICKNAME_LENGTH,
+        }
+    }
+}
+
+/// An inclusive range of protocol versions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ProtocolRange {
+    pub min: u32,
+    pub max: u32,
+}
+
+impl ProtocolRange {
+    /// The range of protocol versions supported by this build.
+    pub fn supported() -> ProtocolRange {
+        ProtocolRange {
+            min: MIN_PROTOCOL_VERSION,
+            max: PROTOCOL_VERSION,
+        }
+    }
+
+    pub fn contains(&self, version: u32) -> bool {
+        version >= self.min && version <= self.max
+    }
+}
+
+impl fmt::Display for ProtocolRange {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.min == self.max {
+            write!(f, "{}", self.min)
+        } else {
+            write!(f, "{} to {}", self.min, self.max)
+        }
+    }
+}
+
+/// The first thing sent to the server on a new connection.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Handshake {
+    /// A game client connecting, sent with the `NT` ID bytes.
+    Connect {
+        /// The client software version, for logging only.
+        version: String,
+        protocol: u32,
+        nickname: String,
+        login: Login,
+    },
+
+    /// A request for server information, sent with the `SY` ID bytes.
+    Poll {
+        /// The polling software version, for logging only.
+        version: String,
+        protocol: u32,
+    },
+}
+
+impl Handshake {
+    /// Create a guest connection handshake for this protocol version.
+    pub fn connect(version: &str, nickname: &str) -> Handshake {
+        Handshake::connect_as(version, nickname, Login::Guest)
+    }
+
+    /// Create a connection handshake for this protocol version, logging in with `login`.
+    pub fn connect_as(version: &str, nickname: &str, login: Login) -> Handshake {
+        Handshake::Connect {
+            version: version.to_owned(),
+            protocol: PROTOCOL_VERSION,
+            nickname: nickname.to_owned(),
+            login: login,
+        }
+    }
+
+    /// Create a server polling handshake for this protocol version.
+    pub fn poll(version: &str) -> Handshake {
+        Handshake::Poll {
+            version: version.to_owned(),
+            protocol: PROTOCOL_VERSION,
+        }
+    }
+
+    pub fn get_version(&self) -> &str {
+        match *self {
+            Handshake::Connect { ref version, .. } |
+            Handshake::Poll { ref version, .. } => version,
+        }
+    }
+
+    pub fn get_protocol(&self) -> u32 {
+        match *self {
+            Handshake::Connect { protocol, .. } |
+            Handshake::Poll { protocol, .. } => protocol,
+        }
+    }
+
+    pub fn write<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        let id: &[u8] = match *self {
+            Handshake::Connect { .. } => b"NT",
+            Handshake::Poll { .. } => b"SY",
+        };
+        writer.write_all(id)?;
+
+        let version = self.get_version();
+        if version.len() > u8::max_value() as usize {
+            return Err(invalid_data("version string is too long"));
+        }
+        writer.write_u8(version.len() as u8)?;
+        writer.write_all(version.as_bytes())?;
+        writer.write_u32::<NetworkEndian>(self.get_protocol())?;
+
+        if let Handshake::Connect { ref nickname, ref login, .. } = *self {
+            write_string(writer, nickname)?;
+            login.write(writer)?;
+        }
+        writer.flush()
+    }
+
+    pub fn read<R: Read>(reader: &mut R) -> io::Result<Handshake> {
+        Handshake::read_limited(reader, &HandshakeLimits::default())
+    }
+
+    /// Read a handshake, failing with `InvalidData` if any of it is over `limits`.
+    pub fn read_limited<R: Read>(reader: &mut R,
+                                 limits: &HandshakeLimits)
+                                 -> io::Result<Handshake> {
+        let mut id = [0; 2];
+        reader.read_exact(&mut id)?;
+        let connect = match &id {
+            b"NT" => true,
+            b"SY" => false,
+            _ => return Err(invalid_data("ID bytes incorrect")),
+        };
+
+        let len = reader.read_u8()? as usize;
+        if len > limits.max_version_length {
+            return Err(invalid_data(format!("version of {} bytes is over the limit of {}",
+                                            len,
+                                            limits.max_version_length)));
+        }
+        let mut buf = vec![0; len];
+        reader.read_exact(&mut buf)?;
+        let version = String::from_utf8(buf)
+            .map_err(|_| invalid_data("version is not valid UTF-8"))?;
+        let protocol = reader.read_u32::<NetworkEndian>()?;
+
+        if !connect {
+            return Ok(Handshake::Poll {
+                version: version,
+                protocol: protocol,
+            });
+        }
+
+        Ok(Handshake::Connect {
+            version: version,
+            protocol: protocol,
+            nickname: read_string(reader, limits.max_nickname_length)?,
+            login: Login::read(reader)?,
+        })
+    }
+}
+
+/// Why the server refused a connection.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RejectReason {
+    /// The client's protocol version is outside of the server's supported range.
+    VersionMismatch,
+    /// The nickname is not allowed or already taken.
+    BadNickname,
+    /// The server has no free player slots.
+    ServerFull,
+    /// The client is banned from the server.
+    Banned,
+    /// The handshake could not be read.
+    Malformed,
+    /// The server only lets players with accounts in.
+    LoginRequired,
+    /// The account doesn't exist, the password is wrong or the session expired.
+    LoginFailed,
+    /// A code unknown to this build, sent by a newer server.
+    Unknown(u8),
+}
+
+impl RejectReason {
+    /// The code sent over the network. Zero is reserved for accepted connections.
+    pub fn code(&self) -> u8 {
+        match *self {
+            RejectReason::VersionMismatch => 1,
+            RejectReason::BadNickname => 2,
+            RejectReason::ServerFull => 3,
+            RejectReason::Banned => 4,
+            RejectReason::Malformed => 5,
+            RejectReason::LoginRequired => 6,
+            RejectReason::LoginFailed => 7,
+            RejectReason::Unknown(code) => code,
+        }
+    }
+
+    pub fn from_code(code: u8) -> RejectReason {
+        match code {
+            1 => RejectReason::VersionMismatch,
+            2 => RejectReason::BadNickname,
+            3 => RejectReason::ServerFull,
+            4 => RejectReason::Banned,
+            5 => RejectReason::Malformed,
+            6 => RejectReason::LoginRequired,
+            7 => RejectReason::LoginFailed,
+            code => RejectReason::Unknown(code),
+        }
+    }
+}
+
+impl fmt::Display for RejectReason {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            RejectReason::VersionMismatch => write!(f, "version mismatch"),
+            RejectReason::BadNickname => write!(f, "bad nickname"),
+            RejectReason::ServerFull => write!(f, "server full"),
+            RejectReason::Banned => write!(f, "banned"),
+            RejectReason::Malformed => write!(f, "malformed handshake"),
+            RejectReason::LoginRequired => write!(f, "login required"),
+            RejectReason::LoginFailed => write!(f, "login failed"),
+            RejectReason::Unknown(code) => write!(f, "unknown reason {}", code),
+        }
+    }
+}
+
+/// A refused connection, with a message to show to the player.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Rejection {
+    pub reason: RejectReason,
+    pub message: String,
+}
+
+impl Rejection {
+    pub fn new<S: Into<String>>(reason: RejectReason, message: S) -> Rejection {
+        Rejection {
+            reason: reason,
+            message: message.into(),
+        }
+    }
+}
+
+impl fmt::Display for Rejection {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {}", self.reason, self.message)
+    }
+}
+
+/// The server's answer to a `Handshake::Connect`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HandshakeReply {
+    /// Why the connection was refused, or `None` if it was accepted.
+    pub rejection: Option<Rejection>,
+
+    /// The protocol versions the server supports, so clients can explain a mismatch.
+    pub protocols: ProtocolRange,
+
+    /// The nickname the client is known by, which the server may have changed.
+    ///
+    /// Only sent for accepted connections, and empty otherwise.
+    pub nickname: String,
+
+    /// A token to log back into the same account with `Login::Session`.
+    ///
+    /// Only sent for accepted connections, and empty for guests.
+    pub session: String,
+}
+
+impl HandshakeReply {
+    /// Accept the connection under `nickname`, sending the protocol versions of this build.
+    pub fn accept<S: Into<String>>(nickname: S) -> HandshakeReply {
+        HandshakeReply {
+            rejection: None,
+            protocols: ProtocolRange::supported(),
+            nickname: nickname.into(),
+            session: String::new(),
+        }
+    }
+
+    /// Refuse the connection, sending the protocol versions of this build.
+    pub fn reject(rejection: Rejection) -> HandshakeReply {
+        HandshakeReply {
+            rejection: Some(rejection),
+            protocols: ProtocolRange::supported(),
+            nickname: String::new(),
+            session: String::new(),
+        }
+    }
+
+    pub fn is_accepted(&self) -> bool {
+        self.rejection.is_none()
+    }
+
+    pub fn write<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        let code = self.rejection.as_ref().map(|r| r.reason.code()).unwrap_or(0);
+        if code == 0 && self.rejection.is_some() {
+            return Err(invalid_data("rejection code 0 is reserved for accepted connections"));
+        }
+        if code == CHALLENGE_CODE {
+            return Err(invalid_data("rejection code 255 is reserved for login challenges"));
+        }
+        writer.write_u8(code)?;
+        writer.write_u32::<NetworkEndian>(self.protocols.min)?;
+        writer.write_u32::<NetworkEndian>(self.protocols.max)?;
+        match self.rejection {
+            Some(ref rejection) => write_string(writer, &rejection.message)?,
+            None => {
+                write_string(writer, &self.nickname)?;
+                write_string(writer, &self.session)?;
+            }
+        }
+        writer.flush()
+    }
+
+    pub fn read<R: Read>(reader: &mut R) -> io::Result<HandshakeReply> {
+        let code = reader.read_u8()?;
+        let protocols = ProtocolRange {
+            min: reader.read_u32::<NetworkEndian>()?,
+            max: reader.read_u32::<NetworkEndian>()?,
+        };
+
+        if code == 0 {
+            return Ok(HandshakeReply {
+                rejection: None,
+                protocols: protocols,
+                nickname: read_string(reader, MAX_NICKNAME_LENGTH)?,
+                session: read_string(reader, MAX_TOKEN_LENGTH)?,
+            });
+        }
+
+        Ok(HandshakeReply {
+            rejection: Some(Rejection {
+                reason: RejectReason::from_code(code),
+                message: read_string(reader, MAX_REJECTION_LENGTH)?,
+            }),
+            protocols: protocols,
+            nickname: String::new(),
+            session: String::new(),
+        })
+    }
+
+    /// Explains why a connection with this build's protocol version was rejected, if it was
+    /// because of a version mismatch.
+    pub fn explain_mismatch(&self) -> Option<String> {
+        if self.is_accepted() || self.protocols.contains(PROTOCOL_VERSION) {
+            return None;
+        }
+        Some(format!("The server supports protocol version {}, but this is version {}. {}",
+                     self.protocols,
+                     PROTOCOL_VERSION,
+                     if PROTOCOL_VERSION < self.protocols.min {
+                         "Update your client to connect."
+                     } else {
+                         "The server is running an older version."
+                     }))
+    }
+}
+
+/// Perform the client side of the handshake: send a connection handshake and wait for the reply.
+///
+/// `version` is the client software version, which the server only logs.
+pub fn connect<S: Read + Write>(stream: &mut S,
+                                version: &str,
+                                nickname: &str)
+                                -> io::Result<HandshakeReply> {
+    Handshake::connect(version, nickname).write(stream)?;
+    HandshakeReply::read(stream)
+}
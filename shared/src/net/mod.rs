@@ -0,0 +1,67 @@
+//! The network protocol shared by the client and server.
+//!
+//! See `docs/net/net.md` for the wire format.
+
+mod codec;
+mod packet;
+pub mod auth;
+pub mod handshake;
+pub mod nickname;
+pub mod status;
+
+pub use self::auth::{Challenge, Credentials, Login};
+pub use self::codec::{NetCodec, ClientMessage, ServerMessage, ClientCodec, ServerCodec,
+                      MAX_FRAME_SIZE};
+pub use self::handshake::{Handshake, HandshakeLimits, HandshakeReply, ProtocolRange,
+                          RejectReason, Rejection, PROTOCOL_VERSION};
+pub use self::nickname::{NicknameError, NicknameRules};
+pub use self::status::{RoundState, ServerStatus};
+pub use self::packet::{ComponentState, EntityState, NetPacket};
+
+use byteorder::{NetworkEndian, ReadBytesExt, WriteBytesExt};
+use std::io::{self, Read, Write};
+
+/// Create an `InvalidData` error, used for anything malformed coming off the wire.
+pub fn invalid_data<P: AsRef<str>>(msg: P) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg.as_ref())
+}
+
+/// Write a string prefixed with its length as an `u32`.
+pub fn write_string<W: Write>(writer: &mut W, string: &str) -> io::Result<()> {
+    writer.write_u32::<NetworkEndian>(string.len() as u32)?;
+    writer.write_all(string.as_bytes())
+}
+
+/// Read a string prefixed with its length as an `u32`.
+///
+/// Fails if the length is over `max` bytes, before anything is allocated.
+pub fn read_string<R: Read>(reader: &mut R, max: usize) -> io::Result<String> {
+    let len = reader.read_u32::<NetworkEndian>()? as usize;
+    if len > max {
+        return Err(invalid_data(format!("string of {} bytes is over the limit of {}", len, max)));
+    }
+
+    let mut buf = vec![0; len];
+    reader.read_exact(&mut buf)?;
+    String::from_utf8(buf).map_err(|_| invalid_data("string is not valid UTF-8"))
+}
+
+/// Write bytes prefixed with their length as an `u32`.
+pub fn write_bytes<W: Write>(writer: &mut W, bytes: &[u8]) -> io::Result<()> {
+    writer.write_u32::<NetworkEndian>(bytes.len() as u32)?;
+    writer.write_all(bytes)
+}
+
+/// Read bytes prefixed with their length as an `u32`.
+///
+/// Fails if the length is over `max` bytes, before anything is allocated.
+pub fn read_bytes<R: Read>(reader: &mut R, max: usize) -> io::Result<Vec<u8>> {
+    let len = reader.read_u32::<NetworkEndian>()? as usize;
+    if len > max {
+        return Err(invalid_data(format!("data of {} bytes is over the limit of {}", len, max)));
+    }
+
+    let mut buf = vec![0; len];
+    reader.read_exact(&mut buf)?;
+    Ok(buf)
+}
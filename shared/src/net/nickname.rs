@@ -0,0 +1,150 @@
+use std::fmt;
+
+/// Rules for which nicknames players may use.
+///
+/// Nicknames are compared case-insensitively, through `key`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NicknameRules {
+    /// The shortest allowed nickname, in characters.
+    pub min_length: usize,
+
+    /// The longest allowed nickname, in characters.
+    pub max_length: usize,
+
+    /// Names nobody may use, like ones admin tools use to refer to the server itself.
+    pub reserved: Vec<String>,
+
+    /// Allow letters and digits outside of ASCII.
+    ///
+    /// Off by default, since letters from different scripts can look the same, letting players
+    /// pass themselves off as someone else.
+    pub allow_unicode: bool,
+}
+
+impl Default for NicknameRules {
+    fn default() -> NicknameRules {
+        NicknameRules {
+            min_length: 2,
+            max_length: 32,
+            reserved: ["server", "admin", "console", "system"]
+                .iter()
+                .map(|x| x.to_string())
+                .collect(),
+            allow_unicode: false,
+        }
+    }
+}
+
+impl NicknameRules {
+    /// Check a nickname against the rules, ignoring whether it's taken.
+    ///
+    /// Nicknames are made of letters, digits, spaces and `_-.'`, and must start and end with a
+    /// letter or digit. Spaces can't be doubled up. Empty nicknames are always too short.
+    pub fn check(&self, nickname: &str) -> Result<(), NicknameError> {
+        let length = nickname.chars().count();
+        let min_length = self.min_length.max(1);
+        if length < min_length {
+            return Err(NicknameError::TooShort(min_length));
+        }
+        if length > self.max_length {
+            return Err(NicknameError::TooLong(self.max_length));
+        }
+
+        if let Some(invalid) = nickname.chars().find(|c| !self.is_allowed(*c)) {
+            return Err(NicknameError::InvalidCharacter(invalid));
+        }
+        let is_edge = |c: Option<char>| c.map(|c| self.is_alphanumeric(c)).unwrap_or(false);
+        if !is_edge(nickname.chars().next()) || !is_edge(nickname.chars().last()) {
+            return Err(NicknameError::BadEdges);
+        }
+        if nickname.contains("  ") {
+            return Err(NicknameError::DoubleSpace);
+        }
+
+        let nickname_key = key(nickname);
+        if self.reserved.iter().any(|reserved| key(reserved) == nickname_key) {
+            return Err(NicknameError::Reserved);
+        }
+        Ok(())
+    }
+
+    /// Returns `nickname`, or a numbered variant of it if `is_taken` says it's in use.
+    ///
+    /// The nickname is shortened if needed to keep variants within `max_length`, and variants
+    /// that break the rules are skipped. Returns `None` once the number alone wouldn't leave
+    /// room for any of the nickname. `is_taken` is given the candidate, not its key.
+    pub fn unique<F>(&self, nickname: &str, is_taken: F) -> Option<String>
+        where F: Fn(&str) -> bool
+    {
+        if !is_taken(nickname) {
+            return Some(nickname.to_owned());
+        }
+
+        let mut number = 2u32;
+        loop {
+            let suffix = number.to_string();
+            if suffix.len() >= self.max_length {
+                return None;
+            }
+            let kept = self.max_length - suffix.len();
+            let base: String = nickname.chars().take(kept).collect();
+            let candidate = base + &suffix;
+            if self.check(&candidate).is_ok() && !is_taken(&candidate) {
+                return Some(candidate);
+            }
+            number += 1;
+        }
+    }
+
+    fn is_alphanumeric(&self, c: char) -> bool {
+        if self.allow_unicode {
+            c.is_alphanumeric()
+        } else {
+            c.is_ascii_alphanumeric()
+        }
+    }
+
+    fn is_allowed(&self, c: char) -> bool {
+        self.is_alphanumeric(c) || c == ' ' || c == '_' || c == '-' || c == '.' || c == '\''
+    }
+}
+
+/// The form nicknames are compared in, so `Bob` and `bob` count as the same player.
+pub fn key(nickname: &str) -> String {
+    nickname.to_lowercase()
+}
+
+/// Why a nickname breaks the rules.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum NicknameError {
+    /// Shorter than the minimum length, in characters.
+    TooShort(usize),
+    /// Longer than the maximum length, in characters.
+    TooLong(usize),
+    InvalidCharacter(char),
+    /// Starts or ends with something other than a letter or digit.
+    BadEdges,
+    DoubleSpace,
+    Reserved,
+}
+
+impl fmt::Display for NicknameError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            NicknameError::TooShort(min) => {
+                write!(f, "Nicknames must be at least {} characters long.", min)
+            }
+            NicknameError::TooLong(max) => {
+                write!(f, "Nicknames can be at most {} characters long.", max)
+            }
+            NicknameError::InvalidCharacter(c) => {
+                write!(f, "Nicknames can't contain {:?}.", c)
+            }
+            NicknameError::BadEdges => {
+                write!(f, "Nicknames must start and end with a letter or digit.")
+            }
+            NicknameError::DoubleSpace => write!(f, "Nicknames can't contain double spaces."),
+            NicknameError::Reserved => write!(f, "That nickname is reserved."),
+        }
+    }
+}
@@ -0,0 +1,219 @@
+use byteorder::{NetworkEndian, ReadBytesExt, WriteBytesExt};
+use std::io::{self, Cursor, Read};
+use super::{invalid_data, read_bytes, read_string, write_bytes, write_string};
+
+/// A packet sent after the handshake, in either direction.
+#[derive(Debug, Clone, PartialEq)]
+pub enum NetPacket {
+    /// The connection is about to be closed.
+    Disconnect { reason: String },
+
+    /// A chat message. Clients leave `sender` empty, the server fills it in.
+    Chat { sender: String, message: String },
+
+    /// An entity moved.
+    EntityPosition {
+        entity: u64,
+        x: f64,
+        y: f64,
+        dimension: u16,
+    },
+
+    /// A keepalive check, answered with a `Pong` carrying the same token.
+    Ping { token: u32 },
+
+    /// The answer to a `Ping`.
+    Pong { token: u32 },
+
+    /// Replicated entities, replacing every entity the client knows about that isn't in it.
+    ///
+    /// Sent when a client joins. Large snapshots continue in `Delta` packets.
+    Snapshot { tick: u64, entities: Vec<EntityState> },
+
+    /// What happened to the replicated entities during a tick.
+    Delta {
+        tick: u64,
+        created: Vec<EntityState>,
+        changed: Vec<EntityState>,
+        removed: Vec<u64>,
+    },
+}
+
+/// The replicated components of an entity, see `entities::replication`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EntityState {
+    pub id: u64,
+    /// Sorted by kind.
+    pub components: Vec<ComponentState>,
+}
+
+impl EntityState {
+    /// The number of bytes `write` uses.
+    pub fn size(&self) -> usize {
+        8 + 2 + self.components.iter().map(|x| 2 + 4 + x.data.len()).sum::<usize>()
+    }
+
+    fn write(&self, buf: &mut Vec<u8>) -> io::Result<()> {
+        buf.write_u64::<NetworkEndian>(self.id)?;
+        buf.write_u16::<NetworkEndian>(self.components.len() as u16)?;
+        for component in &self.components {
+            buf.write_u16::<NetworkEndian>(component.kind)?;
+            write_bytes(buf, &component.data)?;
+        }
+        Ok(())
+    }
+
+    fn read<R: Read>(reader: &mut R, max: usize) -> io::Result<EntityState> {
+        let id = reader.read_u64::<NetworkEndian>()?;
+        let count = reader.read_u16::<NetworkEndian>()?;
+        let mut components = Vec::new();
+        for _ in 0..count {
+            components.push(ComponentState {
+                kind: reader.read_u16::<NetworkEndian>()?,
+                data: read_bytes(reader, max)?,
+            });
+        }
+        Ok(EntityState {
+            id: id,
+            components: components,
+        })
+    }
+}
+
+/// A single replicated component, encoded by its `Replicated` implementation.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ComponentState {
+    pub kind: u16,
+    pub data: Vec<u8>,
+}
+
+const DISCONNECT: u8 = 0;
+const CHAT: u8 = 1;
+const ENTITY_POSITION: u8 = 2;
+const PING: u8 = 3;
+const PONG: u8 = 4;
+const SNAPSHOT: u8 = 5;
+const DELTA: u8 = 6;
+
+impl NetPacket {
+    /// The ID byte written in front of the packet.
+    pub fn id(&self) -> u8 {
+        match *self {
+            NetPacket::Disconnect { .. } => DISCONNECT,
+            NetPacket::Chat { .. } => CHAT,
+            NetPacket::EntityPosition { .. } => ENTITY_POSITION,
+            NetPacket::Ping { .. } => PING,
+            NetPacket::Pong { .. } => PONG,
+            NetPacket::Snapshot { .. } => SNAPSHOT,
+            NetPacket::Delta { .. } => DELTA,
+        }
+    }
+
+    /// Write the ID byte and the payload.
+    pub fn write(&self, buf: &mut Vec<u8>) -> io::Result<()> {
+        buf.write_u8(self.id())?;
+        match *self {
+            NetPacket::Disconnect { ref reason } => write_string(buf, reason),
+            NetPacket::Chat { ref sender, ref message } => {
+                write_string(buf, sender)?;
+                write_string(buf, message)
+            }
+            NetPacket::EntityPosition { entity, x, y, dimension } => {
+                buf.write_u64::<NetworkEndian>(entity)?;
+                buf.write_f64::<NetworkEndian>(x)?;
+                buf.write_f64::<NetworkEndian>(y)?;
+                buf.write_u16::<NetworkEndian>(dimension)
+            }
+            NetPacket::Ping { token } |
+            NetPacket::Pong { token } => buf.write_u32::<NetworkEndian>(token),
+            NetPacket::Snapshot { tick, ref entities } => {
+                buf.write_u64::<NetworkEndian>(tick)?;
+                write_states(buf, entities)
+            }
+            NetPacket::Delta { tick, ref created, ref changed, ref removed } => {
+                buf.write_u64::<NetworkEndian>(tick)?;
+                write_states(buf, created)?;
+                write_states(buf, changed)?;
+                buf.write_u32::<NetworkEndian>(removed.len() as u32)?;
+                for id in removed {
+                    buf.write_u64::<NetworkEndian>(*id)?;
+                }
+                Ok(())
+            }
+        }
+    }
+
+    /// Read a packet from the contents of a single frame.
+    ///
+    /// The whole frame has to be used up by the packet.
+    pub fn read(frame: &[u8]) -> io::Result<NetPacket> {
+        let mut cursor = Cursor::new(frame);
+        // A string can never be longer than the frame it's in.
+        let max = frame.len();
+
+        let packet = match cursor.read_u8()? {
+            DISCONNECT => NetPacket::Disconnect { reason: read_string(&mut cursor, max)? },
+            CHAT => {
+                NetPacket::Chat {
+                    sender: read_string(&mut cursor, max)?,
+                    message: read_string(&mut cursor, max)?,
+                }
+            }
+            ENTITY_POSITION => {
+                NetPacket::EntityPosition {
+                    entity: cursor.read_u64::<NetworkEndian>()?,
+                    x: cursor.read_f64::<NetworkEndian>()?,
+                    y: cursor.read_f64::<NetworkEndian>()?,
+                    dimension: cursor.read_u16::<NetworkEndian>()?,
+                }
+            }
+            PING => NetPacket::Ping { token: cursor.read_u32::<NetworkEndian>()? },
+            PONG => NetPacket::Pong { token: cursor.read_u32::<NetworkEndian>()? },
+            SNAPSHOT => {
+                NetPacket::Snapshot {
+                    tick: cursor.read_u64::<NetworkEndian>()?,
+                    entities: read_states(&mut cursor, max)?,
+                }
+            }
+            DELTA => {
+                let tick = cursor.read_u64::<NetworkEndian>()?;
+                let created = read_states(&mut cursor, max)?;
+                let changed = read_states(&mut cursor, max)?;
+                let mut removed = Vec::new();
+                for _ in 0..cursor.read_u32::<NetworkEndian>()? {
+                    removed.push(cursor.read_u64::<NetworkEndian>()?);
+                }
+                NetPacket::Delta {
+                    tick: tick,
+                    created: created,
+                    changed: changed,
+                    removed: removed,
+                }
+            }
+            id => return Err(invalid_data(format!("unknown packet ID {}", id))),
+        };
+
+        if cursor.position() as usize != frame.len() {
+            return Err(invalid_data(format!("trailing bytes after packet {}", packet.id())));
+        }
+        Ok(packet)
+    }
+}
+
+fn write_states(buf: &mut Vec<u8>, states: &[EntityState]) -> io::Result<()> {
+    buf.write_u32::<NetworkEndian>(states.len() as u32)?;
+    for state in states {
+        state.write(buf)?;
+    }
+    Ok(())
+}
+
+/// Read a list of entity states. Counts aren't trusted for allocating, the frame runs out first.
+fn read_states<R: Read>(reader: &mut R, max: usize) -> io::Result<Vec<EntityState>> {
+    let count = reader.read_u32::<NetworkEndian>()?;
+    let mut states = Vec::new();
+    for _ in 0..count {
+        states.push(EntityState::read(reader, max)?);
+    }
+    Ok(states)
+}
@@ -0,0 +1,144 @@
+use byteorder::{NetworkEndian, ReadBytesExt, WriteBytesExt};
+use std::fmt;
+use std::io::{self, Cursor, Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::time::Duration;
+use super::{invalid_data, read_string, write_string};
+use super::handshake::{Handshake, ProtocolRange};
+
+/// The largest status payload accepted from a server, in bytes.
+pub const MAX_STATUS_SIZE: usize = 64 * 1024;
+
+/// The state of the current round.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RoundState {
+    /// Waiting for the round to start.
+    Lobby,
+    /// The round is in progress.
+    Playing,
+    /// The round is over and the server is about to restart it.
+    Ending,
+    /// A state unknown to this build, sent by a newer server.
+    Unknown(u8),
+}
+
+impl RoundState {
+    pub fn code(&self) -> u8 {
+        match *self {
+            RoundState::Lobby => 0,
+            RoundState::Playing => 1,
+            RoundState::Ending => 2,
+            RoundState::Unknown(code) => code,
+        }
+    }
+
+    pub fn from_code(code: u8) -> RoundState {
+        match code {
+            0 => RoundState::Lobby,
+            1 => RoundState::Playing,
+            2 => RoundState::Ending,
+            code => RoundState::Unknown(code),
+        }
+    }
+}
+
+impl fmt::Display for RoundState {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            RoundState::Lobby => write!(f, "lobby"),
+            RoundState::Playing => write!(f, "playing"),
+            RoundState::Ending => write!(f, "ending"),
+            RoundState::Unknown(code) => write!(f, "unknown state {}", code),
+        }
+    }
+}
+
+/// The server's answer to a `Handshake::Poll`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ServerStatus {
+    pub name: String,
+
+    /// The protocol versions the server supports.
+    pub protocols: ProtocolRange,
+    pub players: u32,
+    pub max_players: u32,
+
+    /// The name of the current map, empty if none is loaded.
+    pub map: String,
+    pub round: RoundState,
+
+    /// How long the server has been running, in seconds.
+    pub uptime: u64,
+}
+
+impl ServerStatus {
+    /// Write the status, prefixed with its length as an `u32`.
+    pub fn write<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        let mut payload = Vec::new();
+        write_string(&mut payload, &self.name)?;
+        payload.write_u32::<NetworkEndian>(self.protocols.min)?;
+        payload.write_u32::<NetworkEndian>(self.protocols.max)?;
+        payload.write_u32::<NetworkEndian>(self.players)?;
+        payload.write_u32::<NetworkEndian>(self.max_players)?;
+        write_string(&mut payload, &self.map)?;
+        payload.write_u8(self.round.code())?;
+        payload.write_u64::<NetworkEndian>(self.uptime)?;
+
+        if payload.len() > MAX_STATUS_SIZE {
+            return Err(invalid_data("server status is too large"));
+        }
+        writer.write_u32::<NetworkEndian>(payload.len() as u32)?;
+        writer.write_all(&payload)?;
+        writer.flush()
+    }
+
+    /// Read a length prefixed status.
+    ///
+    /// Bytes after the known fields are skipped, so newer servers can append fields.
+    pub fn read<R: Read>(reader: &mut R) -> io::Result<ServerStatus> {
+        let len = reader.read_u32::<NetworkEndian>()? as usize;
+        if len > MAX_STATUS_SIZE {
+            return Err(invalid_data(format!("server status of {} bytes is over the limit of {}",
+                                            len,
+                                            MAX_STATUS_SIZE)));
+        }
+        let mut payload = vec![0; len];
+        reader.read_exact(&mut payload)?;
+
+        let mut cursor = Cursor::new(&payload[..]);
+        Ok(ServerStatus {
+            name: read_string(&mut cursor, len)?,
+            protocols: ProtocolRange {
+                min: cursor.read_u32::<NetworkEndian>()?,
+                max: cursor.read_u32::<NetworkEndian>()?,
+            },
+            players: cursor.read_u32::<NetworkEndian>()?,
+            max_players: cursor.read_u32::<NetworkEndian>()?,
+            map: read_string(&mut cursor, len)?,
+            round: RoundState::from_code(cursor.read_u8()?),
+            uptime: cursor.read_u64::<NetworkEndian>()?,
+        })
+    }
+}
+
+/// Poll a server for its status without joining it.
+///
+/// `timeout` applies to connecting, and to reading and writing separately.
+pub fn query<A: ToSocketAddrs>(address: A, timeout: Duration) -> io::Result<ServerStatus> {
+    let mut last_error = None;
+    for address in address.to_socket_addrs()? {
+        match TcpStream::connect_timeout(&address, timeout) {
+            Ok(mut stream) => {
+                stream.set_read_timeout(Some(timeout))?;
+                stream.set_write_timeout(Some(timeout))?;
+                Handshake::poll(env!("CARGO_PKG_VERSION")).write(&mut stream)?;
+                return ServerStatus::read(&mut stream);
+            }
+            Err(error) => last_error = Some(error),
+        }
+    }
+
+    Err(last_error.unwrap_or_else(|| {
+        io::Error::new(io::ErrorKind::InvalidInput, "address resolved to nothing")
+    }))
+}
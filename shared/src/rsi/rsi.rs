@@ -69,6 +69,15 @@ impl Rsi {
         States { iter: self.states.values() }
     }
 
+    /// Returns roughly how many bytes of memory the icons of this RSI use,
+    /// assuming 4 bytes per pixel.
+    pub fn memory_size(&self) -> usize {
+        let icons: usize = self.iter_states()
+            .map(|state| state.get_icons_vec().iter().map(|x| x.len()).sum::<usize>())
+            .sum();
+        icons * (self.size.0 * self.size.1 * 4) as usize
+    }
+
     /// Checks whether two RSIs have equal metadata.
     /// This does **not** check equality of the images themselves!
     ///
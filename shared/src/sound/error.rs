@@ -0,0 +1,50 @@
+//! Various types related to errors when decoding sounds.
+use hound::Error as WavError;
+use lewton::VorbisError;
+use std::fmt;
+use std::io::Error as IOError;
+
+/// Represents an error while decoding a sound.
+#[derive(Debug)]
+pub enum SoundError {
+    /// If an error occured during the IO of a file.
+    IO(IOError),
+
+    /// If the WAV decoder failed.
+    Wav(WavError),
+
+    /// If the OGG Vorbis decoder failed.
+    Ogg(VorbisError),
+
+    /// If the file is in a format or layout that isn't supported.
+    Format(String),
+}
+
+impl From<IOError> for SoundError {
+    fn from(err: IOError) -> SoundError {
+        SoundError::IO(err)
+    }
+}
+
+impl From<WavError> for SoundError {
+    fn from(err: WavError) -> SoundError {
+        SoundError::Wav(err)
+    }
+}
+
+impl From<VorbisError> for SoundError {
+    fn from(err: VorbisError) -> SoundError {
+        SoundError::Ogg(err)
+    }
+}
+
+impl fmt::Display for SoundError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            SoundError::IO(ref err) => write!(f, "IO error: {}", err),
+            SoundError::Wav(ref err) => write!(f, "invalid WAV: {}", err),
+            SoundError::Ogg(ref err) => write!(f, "invalid OGG: {:?}", err),
+            SoundError::Format(ref msg) => write!(f, "unsupported sound: {}", msg),
+        }
+    }
+}
@@ -0,0 +1,243 @@
+use assets::Asset;
+use nalgebra::Vector2;
+use std::collections::HashMap;
+use std::sync::Arc;
+use super::Sound;
+
+/// Identifies a sound playing on a `Mixer`.
+pub type VoiceId = u64;
+
+/// A single playing sound.
+struct Voice {
+    asset: Arc<Asset>,
+
+    /// The position in the sound, in frames of the sound.
+    /// Fractional because the sound may be resampled.
+    cursor: f64,
+    volume: f32,
+    looping: bool,
+
+    /// Where in the world the sound is coming from, if anywhere.
+    origin: Option<Vector2<f64>>,
+}
+
+/// A software mixer, rendering playing sounds into interleaved stereo buffers.
+///
+/// The mixer doesn't talk to any audio device,
+/// whatever renders the buffers is responsible for getting them to the speakers.
+///
+/// Sounds with an origin are positional:
+/// they are panned and get quieter based on their distance to the listener.
+pub struct Mixer {
+    sample_rate: u32,
+    voices: HashMap<VoiceId, Voice>,
+    next_id: VoiceId,
+
+    listener: Vector2<f64>,
+
+    /// The distance at which positional sounds are silent.
+    range: f64,
+    volume: f32,
+}
+
+impl Mixer {
+    /// Creates a new mixer rendering at a certain sample rate.
+    pub fn new(sample_rate: u32) -> Mixer {
+        Mixer {
+            sample_rate: sample_rate,
+            voices: HashMap::new(),
+            next_id: 0,
+            listener: Vector2::new(0.0, 0.0),
+            range: 320.0,
+            volume: 1.0,
+        }
+    }
+
+    /// Starts playing a sound everywhere, at a volume between `0` and `1`.
+    ///
+    /// Returns `None` if the asset isn't a sound.
+    pub fn play(&mut self, asset: Arc<Asset>, volume: f32) -> Option<VoiceId> {
+        self.add_voice(asset, volume, None)
+    }
+
+    /// Starts playing a sound somewhere in the world, at a volume between `0` and `1`.
+    ///
+    /// Returns `None` if the asset isn't a sound.
+    pub fn play_at(&mut self,
+                   asset: Arc<Asset>,
+                   origin: Vector2<f64>,
+                   volume: f32)
+                   -> Option<VoiceId> {
+        self.add_voice(asset, volume, Some(origin))
+    }
+
+    fn add_voice(&mut self,
+                 asset: Arc<Asset>,
+                 volume: f32,
+                 origin: Option<Vector2<f64>>)
+                 -> Option<VoiceId> {
+        if !asset.is_sound() {
+            return None;
+        }
+
+        let id = self.next_id;
+        self.next_id += 1;
+        self.voices.insert(id,
+                           Voice {
+                               asset: asset,
+                               cursor: 0.0,
+                               volume: volume,
+                               looping: false,
+                               origin: origin,
+                           });
+        Some(id)
+    }
+
+    /// Stops a playing sound.
+    pub fn stop(&mut self, id: VoiceId) {
+        self.voices.remove(&id);
+    }
+
+    /// Stops all playing sounds.
+    pub fn stop_all(&mut self) {
+        self.voices.clear();
+    }
+
+    /// Returns `true` if a sound is still playing.
+    pub fn is_playing(&self, id: VoiceId) -> bool {
+        self.voices.contains_key(&id)
+    }
+
+    /// Returns the amount of sounds playing.
+    pub fn voice_count(&self) -> usize {
+        self.voices.len()
+    }
+
+    /// Sets whether a sound starts over when it ends.
+    pub fn set_looping(&mut self, id: VoiceId, looping: bool) {
+        if let Some(voice) = self.voices.get_mut(&id) {
+            voice.looping = looping;
+        }
+    }
+
+    /// Sets the volume of a playing sound.
+    pub fn set_volume(&mut self, id: VoiceId, volume: f32) {
+        if let Some(voice) = self.voices.get_mut(&id) {
+            voice.volume = volume;
+        }
+    }
+
+    /// Moves a positional sound.
+    pub fn set_origin(&mut self, id: VoiceId, origin: Vector2<f64>) {
+        if let Some(voice) = self.voices.get_mut(&id) {
+            voice.origin = Some(origin);
+        }
+    }
+
+    /// Sets where positional sounds are heard from.
+    pub fn set_listener(&mut self, listener: Vector2<f64>) {
+        self.listener = listener;
+    }
+
+    /// Sets the distance at which positional sounds become silent.
+    pub fn set_range(&mut self, range: f64) {
+        self.range = range;
+    }
+
+    /// Sets the volume of the mixer as a whole.
+    pub fn set_master_volume(&mut self, volume: f32) {
+        self.volume = volume;
+    }
+
+    pub fn get_sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    /// Mixes the next `out.len() / 2` frames of all playing sounds into `out`.
+    ///
+    /// `out` is interleaved stereo, left first, and is overwritten.
+    /// Samples are between `-1` and `1`.
+    /// Sounds that finish are removed from the mixer.
+    pub fn render(&mut self, out: &mut [f32]) {
+        for sample in out.iter_mut() {
+            *sample = 0.0;
+        }
+
+        let mut finished = Vec::new();
+        for (&id, voice) in self.voices.iter_mut() {
+            let (left_gain, right_gain) = gains(voice, self.listener, self.range, self.volume);
+            let sound = voice.asset.as_sound().unwrap();
+            let step = sound.get_sample_rate() as f64 / self.sample_rate as f64;
+            let frames = sound.get_frames() as f64;
+
+            for frame in out.chunks_mut(2) {
+                if voice.cursor >= frames {
+                    if !voice.looping || frames == 0.0 {
+                        break;
+                    }
+                    voice.cursor -= frames;
+                }
+
+                frame[0] += interpolate(sound, voice.cursor, 0, voice.looping) * left_gain;
+                if frame.len() > 1 {
+                    frame[1] += interpolate(sound, voice.cursor, 1, voice.looping) * right_gain;
+                }
+                voice.cursor += step;
+            }
+
+            if voice.cursor >= frames && !voice.looping {
+                finished.push(id);
+            }
+        }
+
+        for id in finished {
+            self.voices.remove(&id);
+        }
+
+        for sample in out.iter_mut() {
+            *sample = sample.max(-1.0).min(1.0);
+        }
+    }
+
+    /// Like `render`, but into 16-bit samples.
+    pub fn render_i16(&mut self, out: &mut [i16]) {
+        let mut buffer = vec![0.0; out.len()];
+        self.render(&mut buffer);
+        for (out, sample) in out.iter_mut().zip(buffer) {
+            *out = (sample * 32767.0) as i16;
+        }
+    }
+}
+
+/// Returns the left and right gain of a voice.
+fn gains(voice: &Voice, listener: Vector2<f64>, range: f64, master: f32) -> (f32, f32) {
+    let volume = voice.volume * master;
+    let origin = match voice.origin {
+        Some(origin) => origin,
+        None => return (volume, volume),
+    };
+
+    let offset = origin - listener;
+    let (x, y) = (offset[(0, 0)], offset[(1, 0)]);
+    let distance = (x * x + y * y).sqrt();
+    let attenuation = (1.0 - distance / range).max(0.0) as f32;
+    let pan = (x / range).max(-1.0).min(1.0) as f32;
+
+    let volume = volume * attenuation;
+    (volume * (1.0 - pan).min(1.0), volume * (1.0 + pan).min(1.0))
+}
+
+/// Reads a channel of a sound at a fractional frame, linearly interpolating between frames.
+fn interpolate(sound: &Sound, cursor: f64, channel: u16, looping: bool) -> f32 {
+    let frame = cursor as usize;
+    let fraction = (cursor - frame as f64) as f32;
+
+    let current = sound.get_sample(frame, channel).unwrap_or(0) as f32;
+    let next = match sound.get_sample(frame + 1, channel) {
+        Some(sample) => sample as f32,
+        None if looping => sound.get_sample(0, channel).unwrap_or(0) as f32,
+        None => current,
+    };
+
+    (current + (next - current) * fraction) / 32768.0
+}
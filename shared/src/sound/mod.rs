@@ -0,0 +1,9 @@
+//! Sounds decoded to PCM, and a software mixer to play them.
+
+pub mod error;
+pub mod mixer;
+pub mod sound;
+
+pub use self::error::SoundError;
+pub use self::mixer::{Mixer, VoiceId};
+pub use self::sound::Sound;
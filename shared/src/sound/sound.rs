@@ -0,0 +1,134 @@
+use hound::{SampleFormat, WavReader};
+use lewton::inside_ogg::OggStreamReader;
+use std::fs::File;
+use std::io::{BufReader, Read, Seek};
+use std::path::Path;
+use super::SoundError;
+
+/// A sound decoded to 16-bit PCM.
+///
+/// Samples are interleaved, so a stereo sound stores left and right samples alternately.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Sound {
+    sample_rate: u32,
+    channels: u16,
+    samples: Vec<i16>,
+}
+
+impl Sound {
+    /// Creates a sound out of already decoded samples.
+    ///
+    /// # Panics.
+    /// Panics if `channels` is zero.
+    pub fn new(sample_rate: u32, channels: u16, samples: Vec<i16>) -> Sound {
+        assert!(channels > 0, "Sounds must have at least one channel.");
+        Sound {
+            sample_rate: sample_rate,
+            channels: channels,
+            samples: samples,
+        }
+    }
+
+    /// Opens and decodes a sound file, picking the decoder by extension.
+    ///
+    /// Supported are `.wav` and `.ogg` (Vorbis).
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Sound, SoundError> {
+        let path = path.as_ref();
+        let file = BufReader::new(File::open(path)?);
+        match path.extension().and_then(|x| x.to_str()) {
+            Some("wav") => Sound::from_wav(file),
+            Some("ogg") => Sound::from_ogg(file),
+            _ => Err(SoundError::Format(format!("Unknown extension: {:?}", path))),
+        }
+    }
+
+    /// Decodes a WAV file.
+    ///
+    /// Integer samples of any bit depth and float samples are converted to 16-bit.
+    pub fn from_wav<R: Read>(reader: R) -> Result<Sound, SoundError> {
+        let mut reader = WavReader::new(reader)?;
+        let spec = reader.spec();
+        if spec.channels == 0 {
+            return Err(SoundError::Format("WAV has no channels.".to_string()));
+        }
+
+        let samples = match (spec.sample_format, spec.bits_per_sample) {
+            (SampleFormat::Float, _) => {
+                reader.samples::<f32>()
+                    .map(|x| x.map(|x| (x.max(-1.0).min(1.0) * 32767.0) as i16))
+                    .collect::<Result<Vec<i16>, _>>()?
+            }
+            (SampleFormat::Int, bits) if bits <= 16 => {
+                let shift = 16 - bits;
+                reader.samples::<i16>()
+                    .map(|x| x.map(|x| x << shift))
+                    .collect::<Result<Vec<i16>, _>>()?
+            }
+            (SampleFormat::Int, bits) => {
+                let shift = bits - 16;
+                reader.samples::<i32>()
+                    .map(|x| x.map(|x| (x >> shift) as i16))
+                    .collect::<Result<Vec<i16>, _>>()?
+            }
+        };
+
+        Ok(Sound::new(spec.sample_rate, spec.channels, samples))
+    }
+
+    /// Decodes an OGG Vorbis file.
+    pub fn from_ogg<R: Read + Seek>(reader: R) -> Result<Sound, SoundError> {
+        let mut reader = OggStreamReader::new(reader)?;
+        let channels = reader.ident_hdr.audio_channels as u16;
+        let sample_rate = reader.ident_hdr.audio_sample_rate;
+        if channels == 0 {
+            return Err(SoundError::Format("OGG has no channels.".to_string()));
+        }
+
+        let mut samples = Vec::new();
+        while let Some(packet) = reader.read_dec_packet_itl()? {
+            samples.extend_from_slice(&packet);
+        }
+
+        Ok(Sound::new(sample_rate, channels, samples))
+    }
+}
+
+impl Sound {
+    /// Returns the amount of frames per second.
+    pub fn get_sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    /// Returns the amount of channels, such as 1 for mono and 2 for stereo.
+    pub fn get_channels(&self) -> u16 {
+        self.channels
+    }
+
+    /// Returns the interleaved samples.
+    pub fn get_samples(&self) -> &[i16] {
+        &self.samples
+    }
+
+    /// Returns the amount of frames, a frame being one sample for every channel.
+    pub fn get_frames(&self) -> usize {
+        self.samples.len() / self.channels as usize
+    }
+
+    /// Returns the length of the sound in seconds.
+    pub fn get_duration(&self) -> f64 {
+        self.get_frames() as f64 / self.sample_rate as f64
+    }
+
+    /// Returns how many bytes of memory the samples use.
+    pub fn memory_size(&self) -> usize {
+        self.samples.len() * 2
+    }
+
+    /// Returns the sample of a channel at a frame, or `None` if out of range.
+    ///
+    /// Channels past the last one return the last channel.
+    pub fn get_sample(&self, frame: usize, channel: u16) -> Option<i16> {
+        let channel = channel.min(self.channels - 1) as usize;
+        self.samples.get(frame * self.channels as usize + channel).cloned()
+    }
+}
@@ -3,18 +3,23 @@ extern crate image;
 
 use image::DynamicImage;
 use shared::assets::*;
-use shared::rsi::Rsi;
+use shared::rsi::{Rsi, RsiError};
 use std::env;
+use std::path::PathBuf;
 
-#[test]
-fn test_binary() {
+fn test_dir() -> PathBuf {
     let mut asset_dir = env::current_dir().expect("Unable to find executable path.");
     asset_dir.push("tests");
     asset_dir.push("data");
+    asset_dir
+}
 
-    load_from_dir(asset_dir).unwrap();
+#[test]
+fn test_binary() {
+    let mut manager = AssetManager::new();
+    manager.load_from_dir(test_dir()).unwrap();
 
-    let asset = get_asset("test").expect("Unable to get test file.");
+    let asset = manager.get("test").expect("Unable to get test file.");
     if let Asset::Binary(ref vec) = *asset {
         assert_eq!(vec, &vec![104, 114, 114, 114, 114, 109]);
     } else {
@@ -24,13 +29,10 @@ fn test_binary() {
 
 #[test]
 fn test_rsi() {
-    let mut asset_dir = env::current_dir().expect("Unable to find executable path.");
-    asset_dir.push("tests");
-    asset_dir.push("data");
-
-    load_from_dir(asset_dir).unwrap();
+    let mut manager = AssetManager::new();
+    manager.load_from_dir(test_dir()).unwrap();
 
-    let asset = get_asset("testrsi.rs.rsi").expect("Unable to get test file.");
+    let asset = manager.get("testrsi.rs.rsi").expect("Unable to get test file.");
     let rsi = asset.as_rsi().expect("Asset is not an RSI.");
     assert_eq!(rsi.get_size(), (32, 32));
 
@@ -53,3 +55,108 @@ fn test_rsi() {
     }
 
 }
+
+#[test]
+fn test_separate_managers() {
+    let mut loaded = AssetManager::new();
+    loaded.load_from_dir(test_dir()).unwrap();
+    let empty = AssetManager::new();
+
+    assert_eq!(loaded.root(), test_dir().as_path());
+    assert!(loaded.get("test").is_some());
+    assert!(empty.get("test").is_none());
+    assert!(empty.is_empty());
+}
+
+#[test]
+fn test_global() {
+    load_from_dir(test_dir()).unwrap();
+
+    assert!(get_asset("test").is_some());
+    assert_eq!(ASSET_MANAGER.read().unwrap().root(), test_dir().as_path());
+}
+
+#[test]
+fn test_report() {
+    let mut manager = AssetManager::new();
+    let report = manager.load_from_dir(test_dir()).unwrap();
+
+    assert!(report.loaded.contains(&PathBuf::from("test")));
+    assert!(report.loaded.contains(&PathBuf::from("testrsi.rs.rsi")));
+    assert!(!report.is_ok());
+    assert_eq!(report.failed.len(), 1);
+    match report.failed[0] {
+        (ref path, AssetError::Rsi(RsiError::Version)) => {
+            assert_eq!(path, &PathBuf::from("broken.rsi"))
+        }
+        ref other => panic!("Unexpected failure: {:?}", other),
+    }
+    assert!(manager.get("broken.rsi").is_none());
+}
+
+#[test]
+fn test_strict() {
+    let mut manager = AssetManager::new();
+    manager.set_strict(true);
+
+    match manager.load_from_dir(test_dir()) {
+        Err(LoadError::Failed(report)) => assert_eq!(report.failed.len(), 1),
+        other => panic!("Strict load did not fail: {:?}", other),
+    }
+    assert!(manager.is_empty());
+}
+
+#[test]
+fn test_memory_stats() {
+    let mut manager = AssetManager::new();
+    manager.load_from_dir(test_dir()).unwrap();
+
+    let stats = manager.stats();
+    assert_eq!(stats.assets, manager.len());
+    assert_eq!(stats.resident_assets, stats.assets);
+    assert_eq!(stats.resident_bytes, stats.total_bytes);
+    assert_eq!(stats.budget, None);
+
+    assert_eq!(manager.get("test").unwrap().memory_size(), 6);
+    // 4 frames of 32 by 32 pixels.
+    assert_eq!(manager.get("testrsi.rs.rsi").unwrap().memory_size(), 4 * 32 * 32 * 4);
+}
+
+#[test]
+fn test_budget_eviction() {
+    let mut manager = AssetManager::new();
+    manager.set_budget(Some(0));
+    manager.load_from_dir(test_dir()).unwrap();
+
+    // Nothing evictable fits, so it's all left on disk. Everything else stays.
+    assert!(!manager.is_resident("testrsi.rs.rsi"));
+    assert!(!manager.is_resident("testfont.font"));
+    assert!(manager.is_resident("test"));
+
+    // Iterating only goes over what's in memory, without loading anything back in.
+    assert!(manager.iter().all(|(path, _)| manager.is_resident(path)));
+    assert!(manager.iter().count() < manager.len());
+    assert_eq!(manager.paths().count(), manager.len());
+    assert_eq!(manager.stats().reloads, 0);
+
+    let rsi = manager.get("testrsi.rs.rsi").expect("Unable to reload RSI.");
+    assert_eq!(rsi.as_rsi().unwrap().get_size(), (32, 32));
+    assert!(manager.is_resident("testrsi.rs.rsi"));
+
+    // Loading the font pushes out the least recently used RSI.
+    manager.get("testfont.font").expect("Unable to reload font.");
+    assert!(manager.is_resident("testfont.font"));
+    assert!(!manager.is_resident("testrsi.rs.rsi"));
+
+    let stats = manager.stats();
+    assert_eq!(stats.reloads, 2);
+    assert_eq!(stats.evictions, 1);
+    assert!(stats.resident_bytes < stats.total_bytes);
+
+    // Evicted assets are still usable by whoever held on to them.
+    assert_eq!(rsi.as_rsi().unwrap().get_size(), (32, 32));
+
+    manager.set_budget(None);
+    manager.get("testrsi.rs.rsi").unwrap();
+    assert_eq!(manager.stats().resident_assets, manager.len());
+}
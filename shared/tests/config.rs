@@ -0,0 +1,322 @@
+extern crate yasss13rtwcf_shared as shared;
+
+use shared::config::*;
+use std::env;
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+use std::process;
+
+#[derive(Debug, PartialEq)]
+struct Connection {
+    port: u16,
+    address: String,
+    timeout: f64,
+}
+
+impl Default for Connection {
+    fn default() -> Connection {
+        Connection {
+            port: 40000,
+            address: String::from("0.0.0.0"),
+            timeout: 5.0,
+        }
+    }
+}
+
+impl ConfigSection for Connection {
+    fn name() -> &'static str {
+        "connection"
+    }
+
+    fn read(&mut self, reader: &SectionReader) -> Result<(), ConfigError> {
+        reader.read_range("port", &mut self.port, 1, 65535)?;
+        reader.read("address", &mut self.address)?;
+        reader.read("timeout", &mut self.timeout)?;
+        Ok(())
+    }
+}
+
+fn config(data: &str) -> Config {
+    let mut config = Config::new();
+    config.load(data).unwrap();
+    config
+}
+
+fn invalid_key(error: ConfigError) -> String {
+    match error {
+        ConfigError::Invalid { key, .. } => key,
+        other => panic!("Unexpected error: {:?}", other),
+    }
+}
+
+#[test]
+fn test_section() {
+    let config = config("[connection]\nport = 1234\ntimeout = 2\n");
+    let section: Connection = config.section().unwrap();
+    assert_eq!(section,
+               Connection {
+                   port: 1234,
+                   address: String::from("0.0.0.0"),
+                   timeout: 2.0,
+               });
+}
+
+#[test]
+fn test_defaults() {
+    let config = config("");
+    let section: Connection = config.section().unwrap();
+    assert_eq!(section, Connection::default());
+}
+
+#[test]
+fn test_out_of_range() {
+    let error = config("[connection]\nport = 70000\n").section::<Connection>().unwrap_err();
+    let message = format!("{}", error);
+    assert!(message.contains("connection.port"), "{}", message);
+    assert!(message.contains("70000"), "{}", message);
+    assert_eq!(invalid_key(error), "connection.port");
+
+    let error = config("[connection]\nport = 0\n").section::<Connection>().unwrap_err();
+    assert_eq!(invalid_key(error), "connection.port");
+}
+
+#[test]
+fn test_wrong_type() {
+    let error = config("[connection]\naddress = 5\n").section::<Connection>().unwrap_err();
+    assert!(format!("{}", error).contains("expected a string, found integer"));
+    assert_eq!(invalid_key(error), "connection.address");
+
+    let error = config("connection = 5\n").section::<Connection>().unwrap_err();
+    assert_eq!(invalid_key(error), "connection");
+}
+
+fn args(args: &[&str]) -> Vec<String> {
+    args.iter().map(|x| x.to_string()).collect()
+}
+
+fn vars(vars: &[(&str, &str)]) -> Vec<(String, String)> {
+    vars.iter().map(|&(k, v)| (k.to_owned(), v.to_owned())).collect()
+}
+
+#[test]
+fn test_layers() {
+    let mut config = Config::new();
+    config.load_defaults("[connection]\nport = 1\naddress = \"localhost\"\ntimeout = 1.0\n")
+        .unwrap();
+    config.load("[connection]\nport = 2\n").unwrap();
+    config.load_layer(Layer::LocalFile, "[connection]\ntimeout = 3.0\n").unwrap();
+    config.load_env_from(vars(&[("YASSS_CONNECTION_PORT", "4"),
+                                ("YASSS_CONNECTION_ADDRESS", "example.com"),
+                                ("YASSS_UNKNOWN_KEY", "5")]));
+    config.load_args(&args(&["exe", "--set", "connection.port=5"])).unwrap();
+
+    let section: Connection = config.section().unwrap();
+    assert_eq!(section.port, 5);
+    assert_eq!(section.address, "example.com");
+    assert_eq!(section.timeout, 3.0);
+
+    assert_eq!(config.source("connection.port"), Some(Layer::CommandLine));
+    assert_eq!(config.source("connection.address"), Some(Layer::Environment));
+    assert_eq!(config.source("connection.timeout"), Some(Layer::LocalFile));
+    assert_eq!(config.source("unknown.key"), None);
+    assert_eq!(config.get("unknown.key"), None);
+
+    // Layers keep their precedence regardless of the order they are loaded in.
+    config.load("[connection]\nport = 6\naddress = \"file\"\n").unwrap();
+    assert_eq!(config.get("connection.port").and_then(|x| x.as_integer()), Some(5));
+    assert_eq!(config.source("connection.address"), Some(Layer::Environment));
+}
+
+#[test]
+fn test_override_errors() {
+    let mut config = Config::new();
+    config.load_args(&args(&["exe", "--set=connection.port=70000"])).unwrap();
+    let error = config.section::<Connection>().unwrap_err();
+    assert!(format!("{}", error).starts_with("command line: "));
+    match error {
+        ConfigError::Invalid { layer, .. } => assert_eq!(layer, Some(Layer::CommandLine)),
+        other => panic!("Unexpected error: {:?}", other),
+    }
+
+    let error = config.load_args(&args(&["exe", "--set", "connection.port"])).unwrap_err();
+    assert_eq!(invalid_key(error), "connection.port");
+
+    // Keys are parsed like in `get`, but single array elements can't be overridden.
+    let error = config.load_args(&args(&["exe", "--set", "jobs[1].name=x"])).unwrap_err();
+    assert_eq!(invalid_key(error), "jobs[1].name");
+    let error = config.load_args(&args(&["exe", "--set", "connection..port=1"])).unwrap_err();
+    assert_eq!(invalid_key(error), "connection..port");
+}
+
+#[test]
+fn test_parse_value() {
+    assert_eq!(parse_value("40001").as_integer(), Some(40001));
+    assert_eq!(parse_value("true").as_bool(), Some(true));
+    assert_eq!(parse_value("localhost").as_str(), Some("localhost"));
+    assert_eq!(parse_value("\"quoted\"").as_str(), Some("quoted"));
+    assert_eq!(env_name("connection.max-players"), "YASSS_CONNECTION_MAX_PLAYERS");
+}
+
+#[test]
+fn test_local_file() {
+    let path = Path::new("tests/data/config/config.toml");
+    assert_eq!(local_path(path), Path::new("tests/data/config/config.local.toml"));
+
+    let mut config = Config::new();
+    config.load_file(path).unwrap();
+    assert_eq!(config.get_local_path(), Some(local_path(path).as_path()));
+
+    let section: Connection = config.section().unwrap();
+    assert_eq!(section.port, 1234);
+    assert_eq!(section.address, "127.0.0.1");
+    assert_eq!(config.source("connection.port"), Some(Layer::File));
+    assert_eq!(config.source("connection.address"), Some(Layer::LocalFile));
+}
+
+#[test]
+fn test_runtime_set() {
+    let mut config = Config::new();
+    config.load("[connection]\nport = 1\n").unwrap();
+    let receiver = config.subscribe();
+
+    let changed = config.set("connection.port", parse_value("2")).unwrap();
+    assert_eq!(changed, vec!["connection.port"]);
+    assert_eq!(receiver.try_recv().unwrap(), vec!["connection.port"]);
+    assert_eq!(config.source("connection.port"), Some(Layer::Runtime));
+
+    // Setting the same value again changes nothing, and nobody gets notified.
+    assert!(config.set("connection.port", parse_value("2")).unwrap().is_empty());
+    assert!(receiver.try_recv().is_err());
+
+    // Runtime values win over every other layer.
+    config.load_args(&args(&["exe", "--set", "connection.port=3"])).unwrap();
+    assert_eq!(config.get("connection.port").and_then(|x| x.as_integer()), Some(2));
+    assert!(receiver.try_recv().is_err());
+
+    assert_eq!(config.reset("connection.port").unwrap(), vec!["connection.port"]);
+    assert_eq!(config.get("connection.port").and_then(|x| x.as_integer()), Some(3));
+    assert_eq!(receiver.try_recv().unwrap(), vec!["connection.port"]);
+
+    // Dropped receivers are unsubscribed without errors.
+    drop(receiver);
+    config.set("round.timer", parse_value("600")).unwrap();
+
+    // Array elements and malformed keys are refused without changing anything.
+    for key in &["jobs[0]", "jobs[1].name", "round.", "round[", ""] {
+        match config.set(key, parse_value("1")) {
+            Err(ConfigError::Invalid { layer, .. }) => assert_eq!(layer, Some(Layer::Runtime)),
+            other => panic!("Unexpected result for {:?}: {:?}", key, other),
+        }
+        assert!(config.reset(key).is_err(), "{}", key);
+    }
+    assert_eq!(config.get("round.timer").and_then(|x| x.as_integer()), Some(600));
+    assert!(!config.contains("jobs"));
+}
+
+#[test]
+fn test_reload_and_persist() {
+    let dir = env::temp_dir().join(format!("yasss_config_test_{}", process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("config.toml");
+    fs::File::create(&path).unwrap().write_all(b"[connection]\nport = 1\n").unwrap();
+    let _ = fs::remove_file(local_path(&path));
+
+    let mut config = Config::new();
+    config.load_file(&path).unwrap();
+    let receiver = config.subscribe();
+
+    fs::File::create(&path).unwrap().write_all(b"[connection]\nport = 2\ntimeout = 1.5\n").unwrap();
+    assert_eq!(config.reload().unwrap(), vec!["connection.port", "connection.timeout"]);
+    assert_eq!(receiver.try_recv().unwrap(), vec!["connection.port", "connection.timeout"]);
+
+    config.set("connection.address", parse_value("localhost")).unwrap();
+    config.persist().unwrap();
+    assert_eq!(config.source("connection.address"), Some(Layer::LocalFile));
+
+    // The persisted value survives a fresh load.
+    let mut fresh = Config::new();
+    fresh.load_file(&path).unwrap();
+    let section: Connection = fresh.section().unwrap();
+    assert_eq!(section.port, 2);
+    assert_eq!(section.address, "localhost");
+    assert_eq!(fresh.source("connection.address"), Some(Layer::LocalFile));
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_file_errors() {
+    let dir = env::temp_dir().join(format!("yasss_config_errors_test_{}", process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("config.toml");
+    let _ = fs::remove_file(&path);
+
+    let mut config = Config::new();
+    match config.load_file(&path) {
+        Err(ConfigError::Io { file, .. }) => assert_eq!(file, Some(path.clone())),
+        other => panic!("Unexpected result: {:?}", other),
+    }
+
+    // Errors name the file that failed, which isn't always the one that was loaded.
+    fs::File::create(&path).unwrap().write_all(b"[connection]\nport = 1\n").unwrap();
+    fs::File::create(local_path(&path))
+        .unwrap()
+        .write_all("# é\n[connection]\naddress = \n".as_bytes())
+        .unwrap();
+    let error = config.load_file(&path).unwrap_err();
+    match error {
+        ConfigError::Parse { ref file, ref errors } => {
+            assert_eq!(*file, local_path(&path));
+            assert_eq!((errors[0].line, errors[0].column), (3, 11));
+        }
+        ref other => panic!("Unexpected error: {:?}", other),
+    }
+    let message = format!("{}", error);
+    assert!(message.contains(&format!("{}:3:11: ", local_path(&path).display())),
+            "{}",
+            message);
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_key_paths() {
+    let config = config("motd = \"hello\"\n\
+                         [bans]\n\
+                         nicknames = [\"griefer\", \"spammer\"]\n\
+                         [[jobs]]\n\
+                         name = \"captain\"\n\
+                         slots = 1\n\
+                         [[jobs]]\n\
+                         name = \"assistant\"\n\
+                         slots = 8\n\
+                         ranks = [[1, 2], [3]]\n");
+
+    assert_eq!(config.get_str("motd"), Some("hello"));
+    assert_eq!(config.get_str("jobs[1].name"), Some("assistant"));
+    assert_eq!(config.get_int("jobs[0].slots"), Some(1));
+    assert_eq!(config.get_int("jobs[1].ranks[0][1]"), Some(2));
+    assert_eq!(config.get_int("jobs[1].ranks[1][0]"), Some(3));
+    assert_eq!(config.get_str("bans.nicknames[1]"), Some("spammer"));
+    assert_eq!(config.get_list("bans.nicknames").map(|x| x.len()), Some(2));
+    assert_eq!(config.get_list("jobs").map(|x| x.len()), Some(2));
+
+    // Sections are returned whole.
+    let bans = config.get("bans").unwrap();
+    assert!(bans.as_table().unwrap().contains_key("nicknames"));
+    assert_eq!(config.keys(""), vec!["bans", "jobs", "motd"]);
+    assert_eq!(config.keys("bans"), vec!["nicknames"]);
+    assert_eq!(config.keys("jobs[0]"), vec!["name", "slots"]);
+    assert!(config.keys("motd").is_empty());
+
+    assert!(config.contains("jobs[1]"));
+    assert!(!config.contains("jobs[2]"));
+    assert!(!config.contains("motd.nope"));
+    assert_eq!(config.get_int("motd"), None);
+
+    // Malformed paths are simply missing.
+    for key in &["", "jobs[", "jobs[x]", "jobs[0]]", ".motd", "jobs.[0]", "jobs[0]name"] {
+        assert!(!config.contains(key), "{}", key);
+    }
+}
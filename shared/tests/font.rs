@@ -0,0 +1,54 @@
+extern crate yasss13rtwcf_shared as shared;
+
+use shared::font::*;
+
+fn font() -> Font {
+    Font::open("tests/data/testfont.font").unwrap()
+}
+
+#[test]
+fn test_open() {
+    let font = font();
+    assert_eq!(font.get_line_height(), 5);
+    assert_eq!(font.get_rsi().get_size(), (4, 4));
+    assert_eq!(font.get_glyph('V'),
+               Some(&Glyph {
+                   frame: 1,
+                   advance: 4,
+               }));
+    assert_eq!(font.get_glyph(' ').unwrap().advance, 2);
+    assert_eq!(font.get_kerning('A', 'V'), -1);
+    assert_eq!(font.get_kerning('V', 'A'), 0);
+
+    // Unknown characters fall back to '?'.
+    assert_eq!(font.get_glyph('x'), font.get_glyph('?'));
+}
+
+#[test]
+fn test_layout() {
+    let font = font();
+    let layout = font.layout("AV A", None);
+
+    let positions: Vec<(char, i32, i32)> =
+        layout.glyphs.iter().map(|x| (x.character, x.x, x.y)).collect();
+    assert_eq!(positions, vec![('A', 0, 0), ('V', 3, 0), (' ', 7, 0), ('A', 9, 0)]);
+    assert_eq!(layout.glyphs[1].rsiref.frame, 1);
+    assert_eq!((layout.width, layout.height), (13, 5));
+    assert_eq!(font.measure("A\nAVAV", None), (14, 10));
+}
+
+#[test]
+fn test_wrap() {
+    let font = font();
+
+    // "AV" is 7 wide, "AV AV" 16.
+    let layout = font.layout("AV AV", Some(10));
+    assert_eq!(layout.lines, 2);
+    assert_eq!(layout.glyphs.last().unwrap().y, 5);
+    assert_eq!(font.measure("AV AV", Some(16)), (16, 5));
+
+    // Words that don't fit by themselves are broken up.
+    let layout = font.layout("AAAA", Some(8));
+    assert_eq!(layout.lines, 2);
+    assert_eq!((layout.width, layout.height), (8, 10));
+}
@@ -0,0 +1,72 @@
+extern crate yasss13rtwcf_shared as shared;
+
+use shared::locate::*;
+use std::env;
+
+fn args(args: &[&str]) -> Vec<String> {
+    args.iter().map(|x| x.to_string()).collect()
+}
+
+#[test]
+fn test_flag() {
+    let locator = Locator::new("test file", "nonexistent").with_flag("--test");
+    let expected = env::current_dir().unwrap().join("tests/data/test");
+
+    let located = locator.locate_with_args(&args(&["exe", "--test", "tests/data/test"])).unwrap();
+    assert_eq!(located.source, SearchSource::Flag);
+    assert_eq!(located.path, expected);
+
+    let located = locator.locate_with_args(&args(&["exe", "--test=tests/data/test"])).unwrap();
+    assert_eq!(located.path, expected);
+}
+
+#[test]
+fn test_env() {
+    let locator = Locator::new("test file", "nonexistent")
+        .with_flag("--test")
+        .with_env("YASSS_TEST_LOCATE_ENV");
+    env::set_var("YASSS_TEST_LOCATE_ENV", "tests/data/test");
+
+    let located = locator.locate_with_args(&args(&["exe"])).unwrap();
+    assert_eq!(located.source, SearchSource::Environment);
+
+    // Flags take precedence over the environment.
+    let located = locator.locate_with_args(&args(&["exe", "--test", "tests/data/testrsi.rs.rsi"]))
+        .unwrap();
+    assert_eq!(located.source, SearchSource::Flag);
+}
+
+#[test]
+fn test_working_directory() {
+    let locator = Locator::new("test file", "tests/data/test").with_repo_path("shared/tests/data/test");
+
+    let located = locator.locate_with_args(&args(&["exe"])).unwrap();
+    assert_eq!(located.source, SearchSource::WorkingDirectory);
+}
+
+#[test]
+fn test_repository() {
+    let locator = Locator::new("test file", "nonexistent").with_repo_path("shared/tests/data/test");
+
+    let located = locator.locate_with_args(&args(&["exe"])).unwrap();
+    assert_eq!(located.source, SearchSource::Repository);
+    assert!(located.path.ends_with("shared/tests/data/test"));
+}
+
+#[test]
+fn test_not_found() {
+    let locator = Locator::new("test file", "nonexistent").with_repo_path("nonexistent");
+
+    let error = locator.locate_with_args(&args(&["exe"])).unwrap_err();
+    let sources: Vec<SearchSource> = error.searched.iter().map(|x| x.0).collect();
+    assert_eq!(sources,
+               vec![SearchSource::Executable,
+                    SearchSource::WorkingDirectory,
+                    SearchSource::Repository]);
+
+    // An explicit path that doesn't exist is an error, instead of being skipped.
+    let locator = locator.with_flag("--test");
+    let error = locator.locate_with_args(&args(&["exe", "--test", "tests/data/nonexistent"]))
+        .unwrap_err();
+    assert_eq!(error.searched.len(), 1);
+}
@@ -0,0 +1,662 @@
+extern crate yasss13rtwcf_shared as shared;
+extern crate bytes;
+extern crate tokio_io;
+
+use shared::net::*;
+use bytes::BytesMut;
+use std::io;
+use std::net::TcpListener;
+use std::thread;
+use std::time::Duration;
+use tokio_io::codec::{Encoder, Decoder};
+
+fn packets() -> Vec<NetPacket> {
+    vec![NetPacket::Disconnect { reason: String::from("Round over.") },
+         NetPacket::Chat {
+             sender: String::from("Urist"),
+             message: String::from("Hello, 世界!"),
+         },
+         NetPacket::Chat {
+             sender: String::new(),
+             message: String::new(),
+         },
+         NetPacket::EntityPosition {
+             entity: 42,
+             x: -1.5,
+             y: 1e10,
+             dimension: 3,
+         },
+         NetPacket::Ping { token: 7 },
+         NetPacket::Pong { token: 0xdeadbeef },
+         NetPacket::Snapshot {
+             tick: 12,
+             entities: vec![EntityState {
+                                id: 3,
+                                components: vec![ComponentState {
+                                                     kind: 0,
+                                                     data: vec![1, 2, 3],
+                                                 }],
+                            },
+                            EntityState {
+                                id: 5,
+                                components: Vec::new(),
+                            }],
+         },
+         NetPacket::Snapshot {
+             tick: 0,
+             entities: Vec::new(),
+         },
+         NetPacket::Delta {
+             tick: 13,
+             created: vec![EntityState {
+                               id: 6,
+                               components: Vec::new(),
+                           }],
+             changed: vec![EntityState {
+                               id: 3,
+                               components: vec![ComponentState {
+                                                    kind: 0,
+                                                    data: vec![4, 5],
+                                                }],
+                           }],
+             removed: vec![5, 7],
+         }]
+}
+
+fn encode(codec: &mut NetCodec, packet: NetPacket) -> BytesMut {
+    let mut buf = BytesMut::new();
+    codec.encode(packet, &mut buf).unwrap();
+    buf
+}
+
+#[test]
+fn test_round_trip() {
+    let mut codec = NetCodec::new();
+    for packet in packets() {
+        let mut buf = encode(&mut codec, packet.clone());
+        assert_eq!(codec.decode(&mut buf).unwrap(), Some(packet));
+        assert!(buf.is_empty());
+    }
+}
+
+#[test]
+fn test_framing() {
+    let mut codec = NetCodec::new();
+    let mut buf = encode(&mut codec, NetPacket::Disconnect { reason: String::from("bye") });
+    // Length prefix, ID, then the reason with its own length prefix.
+    assert_eq!(&buf[..], &[0, 0, 0, 8, 0, 0, 0, 0, 3, b'b', b'y', b'e'][..]);
+
+    // Several frames in a row, fed one byte at a time.
+    let mut stream = BytesMut::new();
+    for packet in packets() {
+        stream.extend_from_slice(&encode(&mut codec, packet));
+    }
+    let mut decoded = Vec::new();
+    for byte in stream.iter() {
+        buf.extend_from_slice(&[*byte]);
+        if let Some(packet) = codec.decode(&mut buf).unwrap() {
+            decoded.push(packet);
+        }
+    }
+    // The first frame from above is still at the front.
+    assert_eq!(decoded.len(), packets().len() + 1);
+    assert_eq!(&decoded[1..], &packets()[..]);
+}
+
+fn decode_error(codec: &mut NetCodec, bytes: &[u8]) -> io::Error {
+    let mut buf = BytesMut::from(bytes);
+    codec.decode(&mut buf).unwrap_err()
+}
+
+#[test]
+fn test_oversized() {
+    let mut codec = NetCodec::with_max_frame_size(16);
+
+    // Rejected from the length prefix alone, without waiting for the data.
+    let error = decode_error(&mut codec, &[0, 0, 0, 17]);
+    assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+    decode_error(&mut codec, &[255, 255, 255, 255]);
+
+    let packet = NetPacket::Chat {
+        sender: String::from("someone"),
+        message: String::from("a message that is too long"),
+    };
+    let error = codec.encode(packet, &mut BytesMut::new()).unwrap_err();
+    assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+}
+
+#[test]
+fn test_malformed() {
+    let mut codec = NetCodec::new();
+    // Empty frame.
+    decode_error(&mut codec, &[0, 0, 0, 0]);
+    // Unknown packet ID.
+    decode_error(&mut codec, &[0, 0, 0, 1, 200]);
+    // Trailing bytes.
+    decode_error(&mut codec, &[0, 0, 0, 6, 0, 0, 0, 0, 0, 1]);
+    // String longer than the frame.
+    decode_error(&mut codec, &[0, 0, 0, 5, 0, 0, 0, 0, 9]);
+    // Invalid UTF-8.
+    decode_error(&mut codec, &[0, 0, 0, 6, 0, 0, 0, 0, 1, 0xff]);
+}
+
+#[test]
+fn test_handshake_round_trip() {
+    for handshake in vec![Handshake::connect("0.0.2", "Urist McTest"), Handshake::poll("0.0.2")] {
+        let mut buf = Vec::new();
+        handshake.write(&mut buf).unwrap();
+        let read = Handshake::read(&mut io::Cursor::new(&buf)).unwrap();
+        assert_eq!(read, handshake);
+        assert_eq!(read.get_version(), "0.0.2");
+        assert_eq!(read.get_protocol(), PROTOCOL_VERSION);
+    }
+
+    let replies = vec![HandshakeReply::accept("Urist McTest"),
+                       HandshakeReply::reject(Rejection::new(RejectReason::Banned, "Go away.")),
+                       HandshakeReply::reject(Rejection::new(RejectReason::Unknown(200), ""))];
+    for reply in replies {
+        let mut buf = Vec::new();
+        reply.write(&mut buf).unwrap();
+        assert_eq!(HandshakeReply::read(&mut io::Cursor::new(&buf)).unwrap(), reply);
+    }
+}
+
+#[test]
+fn test_handshake_format() {
+    let mut handshake = Handshake::Connect {
+        version: String::from("1.0"),
+        protocol: 7,
+        nickname: String::from("Bob"),
+        login: Login::Guest,
+    };
+    let mut buf = Vec::new();
+    handshake.write(&mut buf).unwrap();
+    assert_eq!(buf, b"NT\x031.0\x00\x00\x00\x07\x00\x00\x00\x03Bob\x00".to_vec());
+
+    if let Handshake::Connect { ref mut login, .. } = handshake {
+        *login = Login::Account(String::from("bob"));
+    }
+    let mut buf = Vec::new();
+    handshake.write(&mut buf).unwrap();
+    assert_eq!(buf,
+               b"NT\x031.0\x00\x00\x00\x07\x00\x00\x00\x03Bob\x01\x00\x00\x00\x03bob".to_vec());
+
+    let poll = Handshake::Poll {
+        version: String::from("1.0"),
+        protocol: 7,
+    };
+    let mut buf = Vec::new();
+    poll.write(&mut buf).unwrap();
+    assert_eq!(buf, b"SY\x031.0\x00\x00\x00\x07".to_vec());
+
+    let mut reply = HandshakeReply {
+        rejection: None,
+        protocols: ProtocolRange { min: 2, max: 3 },
+        nickname: String::from("Bob"),
+        session: String::from("ab"),
+    };
+    let mut buf = Vec::new();
+    reply.write(&mut buf).unwrap();
+    assert_eq!(buf,
+               b"\x00\x00\x00\x00\x02\x00\x00\x00\x03\x00\x00\x00\x03Bob\x00\x00\x00\x02ab"
+                   .to_vec());
+
+    // The nickname and session are only sent with acceptances.
+    reply.rejection = Some(Rejection::new(RejectReason::ServerFull, "Full"));
+    let mut buf = Vec::new();
+    reply.write(&mut buf).unwrap();
+    assert_eq!(buf,
+               b"\x03\x00\x00\x00\x02\x00\x00\x00\x03\x00\x00\x00\x04Full".to_vec());
+
+    // Code zero means accepted, so it can't be sent as a rejection.
+    reply.rejection = Some(Rejection::new(RejectReason::Unknown(0), ""));
+    assert!(reply.write(&mut Vec::new()).is_err());
+    // And 255 marks login challenges.
+    reply.rejection = Some(Rejection::new(RejectReason::Unknown(255), ""));
+    assert!(reply.write(&mut Vec::new()).is_err());
+}
+
+#[test]
+fn test_handshake_malformed() {
+    fn read(bytes: &[u8]) -> io::Result<Handshake> {
+        Handshake::read(&mut io::Cursor::new(bytes))
+    }
+
+    assert!(read(b"XX\x00").is_err());
+    // Cut off in the middle.
+    assert!(read(b"NT\x031.").is_err());
+    assert!(read(b"NT\x031.0\x00\x00").is_err());
+    assert!(read(b"NT\x031.0\x00\x00\x00\x01\x00\x00\x00\x05Bob").is_err());
+    // Nickname length over the limit, rejected before reading it.
+    assert!(read(b"NT\x031.0\x00\x00\x00\x01\xff\xff\xff\xff").is_err());
+    assert!(HandshakeReply::read(&mut io::Cursor::new(b"\x00\x00\x00")).is_err());
+    // A rejection without its message.
+    let reply = b"\x01\x00\x00\x00\x01\x00\x00\x00\x01";
+    assert!(HandshakeReply::read(&mut io::Cursor::new(&reply[..])).is_err());
+}
+
+#[test]
+fn test_handshake_limits() {
+    let limits = HandshakeLimits {
+        max_version_length: 3,
+        max_nickname_length: 5,
+    };
+    let read = |bytes: &[u8]| Handshake::read_limited(&mut io::Cursor::new(bytes), &limits);
+
+    assert!(read(b"NT\x031.0\x00\x00\x00\x01\x00\x00\x00\x05Alice\x00").is_ok());
+    let error = read(b"NT\x041.00\x00\x00\x00\x01\x00\x00\x00\x03Bob\x00").unwrap_err();
+    assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+    let error = read(b"NT\x031.0\x00\x00\x00\x01\x00\x00\x00\x06Alicia").unwrap_err();
+    assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+
+    // The length alone is enough to reject it, without waiting for the rest.
+    let mut codec = ServerCodec::with_limits(NetCodec::new(), limits);
+    let mut buf = BytesMut::from(&b"NT\x031.0\x00\x00\x00\x01\xff\xff\xff\xff"[..]);
+    assert_eq!(codec.decode(&mut buf).unwrap_err().kind(), io::ErrorKind::InvalidData);
+    let mut buf = BytesMut::from(&b"NT\xff"[..]);
+    assert!(codec.decode(&mut buf).is_err());
+}
+
+/// A small deterministic xorshift generator, so failures can be reproduced.
+struct Fuzzer(u64);
+
+impl Fuzzer {
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    fn bytes(&mut self, max_len: usize) -> Vec<u8> {
+        let len = self.next() as usize % (max_len + 1);
+        (0..len).map(|_| self.next() as u8).collect()
+    }
+}
+
+/// Decode `input` with a fresh server codec, fed in chunks. Returns whether it errored.
+fn decode_server(input: &[u8], chunk: usize) -> bool {
+    let mut codec = ServerCodec::new(NetCodec::with_max_frame_size(1024));
+    let mut buf = BytesMut::new();
+    for part in input.chunks(chunk) {
+        buf.extend_from_slice(part);
+        loop {
+            match codec.decode(&mut buf) {
+                Ok(Some(_)) => {}
+                Ok(None) => break,
+                Err(_) => return true,
+            }
+        }
+    }
+    false
+}
+
+#[test]
+fn test_handshake_fuzz() {
+    let mut valid = Vec::new();
+    Handshake::connect("0.0.2", "Bob").write(&mut valid).unwrap();
+    let mut fuzzer = Fuzzer(0x2545f4914f6cdd1d);
+
+    // Every truncation is incomplete: an error when read directly, and more data for the codec.
+    for len in 0..valid.len() {
+        assert!(Handshake::read(&mut io::Cursor::new(&valid[..len])).is_err());
+        assert!(!decode_server(&valid[..len], 1));
+    }
+
+    // Corrupting any byte must never panic, whatever it turns into.
+    for i in 0..valid.len() {
+        for value in &[0x00, 0x01, 0x7f, 0x80, 0xfe, 0xff] {
+            let mut corrupt = valid.clone();
+            corrupt[i] = *value;
+            let _ = Handshake::read(&mut io::Cursor::new(&corrupt[..]));
+            decode_server(&corrupt, 3);
+        }
+    }
+
+    // Random garbage, with and without a valid ID in front.
+    for _ in 0..2000 {
+        let mut input = fuzzer.bytes(64);
+        if fuzzer.next() % 2 == 0 {
+            let mut prefixed = b"NT".to_vec();
+            prefixed.append(&mut input);
+            input = prefixed;
+        }
+        let _ = Handshake::read(&mut io::Cursor::new(&input[..]));
+        let _ = HandshakeReply::read(&mut io::Cursor::new(&input[..]));
+        let _ = ServerStatus::read(&mut io::Cursor::new(&input[..]));
+        let _ = NetPacket::read(&input);
+        let chunk = 1 + fuzzer.next() as usize % 8;
+        decode_server(&input, chunk);
+    }
+}
+
+#[test]
+fn test_nickname_rules() {
+    let rules = NicknameRules::default();
+    for nickname in &["Bob", "Urist McTest", "O'Brien", "x_y-z.w", "R2"] {
+        assert_eq!(rules.check(nickname), Ok(()), "{}", nickname);
+    }
+
+    assert_eq!(rules.check(""), Err(NicknameError::TooShort(2)));
+    assert_eq!(rules.check("B"), Err(NicknameError::TooShort(2)));
+    assert_eq!(rules.check(&"a".repeat(33)), Err(NicknameError::TooLong(32)));
+    assert_eq!(rules.check("Bob\n"), Err(NicknameError::InvalidCharacter('\n')));
+    assert_eq!(rules.check("Bob\u{200b}"), Err(NicknameError::InvalidCharacter('\u{200b}')));
+    assert_eq!(rules.check("<Bob>"), Err(NicknameError::InvalidCharacter('<')));
+    assert_eq!(rules.check(" Bob"), Err(NicknameError::BadEdges));
+    assert_eq!(rules.check("Bob."), Err(NicknameError::BadEdges));
+    assert_eq!(rules.check("Bob  Smith"), Err(NicknameError::DoubleSpace));
+    assert_eq!(rules.check("ADMIN"), Err(NicknameError::Reserved));
+    assert_eq!(format!("{}", NicknameError::TooShort(2)),
+               "Nicknames must be at least 2 characters long.");
+
+    // Only ASCII by default, so Cyrillic lookalikes can't pass for someone else.
+    assert_eq!(rules.check("Zoë"), Err(NicknameError::InvalidCharacter('ë')));
+    assert_eq!(rules.check("\u{412}ob"), Err(NicknameError::InvalidCharacter('\u{412}')));
+    assert_eq!(rules.check("B\u{43e}b"), Err(NicknameError::InvalidCharacter('\u{43e}')));
+    let unicode = NicknameRules { allow_unicode: true, ..NicknameRules::default() };
+    assert_eq!(unicode.check("Zoë"), Ok(()));
+    assert_eq!(unicode.check("Bob\u{200b}"), Err(NicknameError::InvalidCharacter('\u{200b}')));
+    // Length is counted in characters, not bytes.
+    assert_eq!(unicode.check(&"ë".repeat(32)), Ok(()));
+
+    // Nicknames can't be empty, whatever the minimum length is.
+    let empty = NicknameRules { min_length: 0, ..NicknameRules::default() };
+    assert_eq!(empty.check(""), Err(NicknameError::TooShort(1)));
+    assert_eq!(empty.check("B"), Ok(()));
+}
+
+#[test]
+fn test_nickname_unique() {
+    let mut rules = NicknameRules::default();
+    let taken = vec!["bob", "bob2", "bob3"];
+    let is_taken = |nickname: &str| taken.contains(&nickname::key(nickname).as_str());
+
+    assert_eq!(rules.unique("Alice", &is_taken), Some(String::from("Alice")));
+    assert_eq!(rules.unique("BOB", &is_taken), Some(String::from("BOB4")));
+
+    // Variants that break the rules are skipped.
+    rules.reserved.push(String::from("bob4"));
+    assert_eq!(rules.unique("BOB", &is_taken), Some(String::from("BOB5")));
+
+    // Variants are shortened to fit, and never go over the limit.
+    rules.max_length = 3;
+    assert_eq!(rules.unique("Bob", &is_taken), Some(String::from("Bo2")));
+    assert_eq!(rules.unique("Bob", |nickname: &str| nickname != "B10"),
+               Some(String::from("B10")));
+    assert_eq!(rules.unique("Bob", |nickname: &str| nickname.len() <= 3), None);
+    rules.max_length = 1;
+    assert_eq!(rules.unique("B", |_: &str| true), None);
+}
+
+#[test]
+fn test_reject_reasons() {
+    for code in 1..256 {
+        let reason = RejectReason::from_code(code as u8);
+        assert_eq!(reason.code(), code as u8);
+    }
+    assert_eq!(RejectReason::from_code(4), RejectReason::Banned);
+    let rejection = Rejection::new(RejectReason::BadNickname, "Nickname is taken.");
+    assert_eq!(format!("{}", rejection), "bad nickname: Nickname is taken.");
+}
+
+#[test]
+fn test_protocol_range() {
+    let range = ProtocolRange { min: 2, max: 4 };
+    assert!(!range.contains(1));
+    assert!(range.contains(2));
+    assert!(range.contains(4));
+    assert!(!range.contains(5));
+    assert_eq!(format!("{}", range), "2 to 4");
+    assert_eq!(format!("{}", ProtocolRange { min: 3, max: 3 }), "3");
+    assert!(ProtocolRange::supported().contains(PROTOCOL_VERSION));
+
+    let rejection = Rejection::new(RejectReason::VersionMismatch, "Wrong version.");
+    assert_eq!(HandshakeReply::reject(rejection.clone()).explain_mismatch(), None);
+    assert_eq!(HandshakeReply::accept("Bob").explain_mismatch(), None);
+    let newer = HandshakeReply {
+        rejection: Some(rejection),
+        protocols: ProtocolRange {
+            min: PROTOCOL_VERSION + 1,
+            max: PROTOCOL_VERSION + 2,
+        },
+        nickname: String::new(),
+        session: String::new(),
+    };
+    assert!(newer.explain_mismatch().unwrap().contains("Update your client"));
+}
+
+/// An in-memory stream with separate input and output.
+struct Duplex {
+    input: io::Cursor<Vec<u8>>,
+    output: Vec<u8>,
+}
+
+impl io::Read for Duplex {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.input.read(buf)
+    }
+}
+
+impl io::Write for Duplex {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.output.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[test]
+fn test_client_connect() {
+    let reply = b"\x00\x00\x00\x00\x01\x00\x00\x00\x05\x00\x00\x00\x03Bob\x00\x00\x00\x00";
+    let mut stream = Duplex {
+        input: io::Cursor::new(reply.to_vec()),
+        output: Vec::new(),
+    };
+    let reply = handshake::connect(&mut stream, "0.0.2", "Bob").unwrap();
+    assert!(reply.is_accepted());
+    assert_eq!(reply.protocols, ProtocolRange { min: 1, max: 5 });
+    assert_eq!(reply.nickname, "Bob");
+
+    let sent = Handshake::read(&mut io::Cursor::new(&stream.output)).unwrap();
+    assert_eq!(sent, Handshake::connect("0.0.2", "Bob"));
+}
+
+fn status() -> ServerStatus {
+    ServerStatus {
+        name: String::from("Test Station"),
+        protocols: ProtocolRange::supported(),
+        players: 3,
+        max_players: 32,
+        map: String::from("boxstation"),
+        round: RoundState::Playing,
+        uptime: 3600,
+    }
+}
+
+#[test]
+fn test_status_round_trip() {
+    let mut buf = Vec::new();
+    status().write(&mut buf).unwrap();
+    assert_eq!(ServerStatus::read(&mut io::Cursor::new(&buf)).unwrap(), status());
+
+    // Fields appended by newer servers are skipped.
+    let mut payload = Vec::new();
+    status().write(&mut payload).unwrap();
+    let mut buf = vec![0, 0, 0, (payload.len() - 4 + 2) as u8];
+    buf.extend_from_slice(&payload[4..]);
+    buf.extend_from_slice(&[1, 2, 0xde, 0xad]);
+    let mut cursor = io::Cursor::new(&buf);
+    assert_eq!(ServerStatus::read(&mut cursor).unwrap(), status());
+    assert_eq!(cursor.position() as usize, buf.len() - 2);
+
+    // Too short, and too long.
+    assert!(ServerStatus::read(&mut io::Cursor::new(&[0, 0, 0, 4, 0, 0, 0, 0])).is_err());
+    assert!(ServerStatus::read(&mut io::Cursor::new(&[0xff, 0xff, 0xff, 0xff])).is_err());
+
+    assert_eq!(RoundState::from_code(7), RoundState::Unknown(7));
+    assert_eq!(RoundState::Unknown(7).code(), 7);
+}
+
+#[test]
+fn test_query() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap();
+    let server = thread::spawn(move || {
+        let (mut stream, _) = listener.accept().unwrap();
+        let handshake = Handshake::read(&mut stream).unwrap();
+        assert_eq!(handshake.get_protocol(), PROTOCOL_VERSION);
+        match handshake {
+            Handshake::Poll { .. } => status().write(&mut stream).unwrap(),
+            other => panic!("Unexpected handshake: {:?}", other),
+        }
+    });
+
+    let queried = status::query(address, Duration::from_secs(5)).unwrap();
+    assert_eq!(queried, status());
+    server.join().unwrap();
+}
+
+#[test]
+fn test_server_codec() {
+    let mut codec = ServerCodec::new(NetCodec::new());
+    let mut packets = NetCodec::new();
+
+    let mut input = Vec::new();
+    Handshake::connect("0.0.2", "Bob").write(&mut input).unwrap();
+    let packet = NetPacket::Chat {
+        sender: String::new(),
+        message: String::from("Hi"),
+    };
+    input.extend_from_slice(&encode(&mut packets, packet.clone()));
+
+    // Fed one byte at a time, the handshake is only decoded once it's complete.
+    let mut buf = BytesMut::new();
+    let mut decoded = Vec::new();
+    for byte in &input {
+        buf.extend_from_slice(&[*byte]);
+        while let Some(message) = codec.decode(&mut buf).unwrap() {
+            decoded.push(message);
+        }
+    }
+    assert_eq!(decoded,
+               vec![ClientMessage::Handshake(Handshake::connect("0.0.2", "Bob")),
+                    ClientMessage::Packet(packet.clone())]);
+
+    // Replies are written bare, packets framed.
+    let mut output = BytesMut::new();
+    codec.encode(ServerMessage::Reply(HandshakeReply::accept("Bob")), &mut output).unwrap();
+    codec.encode(ServerMessage::Packet(packet.clone()), &mut output).unwrap();
+    let mut cursor = io::Cursor::new(&output[..]);
+    assert_eq!(HandshakeReply::read(&mut cursor).unwrap(), HandshakeReply::accept("Bob"));
+    let mut rest = BytesMut::from(&output[cursor.position() as usize..]);
+    assert_eq!(packets.decode(&mut rest).unwrap(), Some(packet));
+
+    // A bad handshake is an error, not a request for more data.
+    let mut codec = ServerCodec::new(NetCodec::new());
+    assert!(codec.decode(&mut BytesMut::from(&b"GET / HTTP/1.1"[..])).is_err());
+}
+
+#[test]
+fn test_client_codec() {
+    let mut client = ClientCodec::new(NetCodec::new());
+    let mut server = ServerCodec::new(NetCodec::new());
+    let packet = NetPacket::Chat {
+        sender: String::from("Server"),
+        message: String::from("Welcome"),
+    };
+
+    let mut to_server = BytesMut::new();
+    let handshake = ClientMessage::Handshake(Handshake::connect("0.0.2", "Bob"));
+    client.encode(handshake.clone(), &mut to_server).unwrap();
+    client.encode(ClientMessage::Packet(packet.clone()), &mut to_server).unwrap();
+    assert_eq!(server.decode(&mut to_server).unwrap(), Some(handshake));
+    assert_eq!(server.decode(&mut to_server).unwrap(),
+               Some(ClientMessage::Packet(packet.clone())));
+
+    let rejection = Rejection::new(RejectReason::ServerFull, "Full.");
+    let replies = vec![ServerMessage::Reply(HandshakeReply::reject(rejection)),
+                       ServerMessage::Packet(packet.clone())];
+    let mut to_client = BytesMut::new();
+    for reply in &replies {
+        server.encode(reply.clone(), &mut to_client).unwrap();
+    }
+
+    // Fed one byte at a time.
+    let mut buf = BytesMut::new();
+    let mut decoded = Vec::new();
+    for byte in to_client.iter() {
+        buf.extend_from_slice(&[*byte]);
+        while let Some(message) = client.decode(&mut buf).unwrap() {
+            decoded.push(message);
+        }
+    }
+    assert_eq!(decoded, replies);
+}
+
+#[test]
+fn test_login_proof() {
+    let credentials = Credentials::new("hunter2", b"salt".to_vec(), 100);
+    // SHA256(HMAC(PBKDF2("hunter2", "salt", 100), "Client Key")), as stored in account files.
+    let stored: String = credentials.stored_key.iter().map(|x| format!("{:02x}", x)).collect();
+    assert_eq!(stored, "ff80a40fb0e23a6abf8367d3283b7162e12865427190b6d8a866d3f2bbc85860");
+    let challenge = credentials.challenge(b"nonce".to_vec());
+    let proof = auth::proof("hunter2", &challenge);
+
+    assert!(credentials.verify(b"nonce", &proof));
+    assert!(!credentials.verify(b"other nonce", &proof));
+    assert!(!credentials.verify(b"nonce", &auth::proof("hunter3", &challenge)));
+    assert!(!credentials.verify(b"nonce", &proof[1..]));
+    // The stored key can't be used to log in.
+    assert!(!credentials.verify(b"nonce", &credentials.stored_key));
+}
+
+#[test]
+fn test_login_codec() {
+    let mut client = ClientCodec::new(NetCodec::new());
+    let mut server = ServerCodec::new(NetCodec::new());
+    let packet = NetPacket::Ping { token: 1 };
+
+    let mut to_server = BytesMut::new();
+    let login = Login::Account(String::from("bob"));
+    let handshake = ClientMessage::Handshake(Handshake::connect_as("0.0.2", "Bob", login));
+    let proof = ClientMessage::Proof(vec![7; 32]);
+    for message in vec![handshake.clone(), proof.clone(), ClientMessage::Packet(packet.clone())] {
+        client.encode(message, &mut to_server).unwrap();
+    }
+    assert_eq!(server.decode(&mut to_server).unwrap(), Some(handshake));
+    assert_eq!(server.decode(&mut to_server).unwrap(), Some(proof));
+    assert_eq!(server.decode(&mut to_server).unwrap(),
+               Some(ClientMessage::Packet(packet.clone())));
+
+    let challenge = Challenge {
+        salt: vec![1, 2, 3],
+        iterations: 1000,
+        nonce: vec![4, 5, 6],
+    };
+    let mut reply = HandshakeReply::accept("Bob");
+    reply.session = String::from("token");
+    let replies = vec![ServerMessage::Challenge(challenge),
+                       ServerMessage::Reply(reply),
+                       ServerMessage::Packet(packet)];
+    let mut to_client = BytesMut::new();
+    for reply in &replies {
+        server.encode(reply.clone(), &mut to_client).unwrap();
+    }
+    let mut decoded = Vec::new();
+    while let Some(message) = client.decode(&mut to_client).unwrap() {
+        decoded.push(message);
+    }
+    assert_eq!(decoded, replies);
+
+    // Proofs are a fixed size.
+    let mut server = ServerCodec::new(NetCodec::new());
+    let mut to_server = BytesMut::new();
+    let handshake = Handshake::connect_as("0.0.2", "Bob", Login::Account(String::from("bob")));
+    client.encode(ClientMessage::Handshake(handshake), &mut to_server).unwrap();
+    client.encode(ClientMessage::Proof(vec![0; 1000]), &mut to_server).unwrap();
+    assert!(server.decode(&mut to_server).unwrap().is_some());
+    assert!(server.decode(&mut to_server).is_err());
+}
@@ -0,0 +1,93 @@
+extern crate yasss13rtwcf_shared as shared;
+extern crate nalgebra;
+
+use nalgebra::Vector2;
+use shared::assets::Asset;
+use shared::sound::*;
+use std::sync::Arc;
+
+#[test]
+fn test_wav() {
+    let sound = Sound::open("tests/data/beep.wav").unwrap();
+    assert_eq!(sound.get_sample_rate(), 8000);
+    assert_eq!(sound.get_channels(), 1);
+    assert_eq!(sound.get_samples(),
+               &[0, 1000, 2000, 3000, 4000, 3000, 2000, 1000]);
+    assert_eq!(sound.get_duration(), 0.001);
+}
+
+#[test]
+fn test_wav_8bit_stereo() {
+    let sound = Sound::open("tests/data/stereo.wav").unwrap();
+    assert_eq!(sound.get_sample_rate(), 4000);
+    assert_eq!(sound.get_channels(), 2);
+    assert_eq!(sound.get_frames(), 4);
+    assert_eq!(sound.get_sample(0, 0), Some(0));
+    assert_eq!(sound.get_sample(0, 1), Some(127 << 8));
+    assert_eq!(sound.get_sample(1, 1), Some(-128 << 8));
+    assert_eq!(sound.get_sample(2, 0), Some(64 << 8));
+    assert_eq!(sound.get_sample(4, 0), None);
+}
+
+fn constant(sample_rate: u32, frames: usize) -> Arc<Asset> {
+    Arc::new(Asset::Sound(Sound::new(sample_rate, 1, vec![16384; frames])))
+}
+
+#[test]
+fn test_mixer_render() {
+    let mut mixer = Mixer::new(100);
+    let id = mixer.play(constant(100, 4), 1.0).unwrap();
+
+    let mut buffer = [0.0; 12];
+    mixer.render(&mut buffer);
+    assert_eq!(&buffer[..8], &[0.5; 8]);
+    assert_eq!(&buffer[8..], &[0.0; 4]);
+    assert!(!mixer.is_playing(id));
+}
+
+#[test]
+fn test_mixer_not_sound() {
+    let mut mixer = Mixer::new(100);
+    assert!(mixer.play(Arc::new(Asset::Binary(vec![1, 2, 3])), 1.0).is_none());
+}
+
+#[test]
+fn test_mixer_resample() {
+    let mut mixer = Mixer::new(200);
+    mixer.play(constant(100, 4), 1.0).unwrap();
+
+    // Twice the sample rate, so twice as many frames come out.
+    let mut buffer = [0.0; 20];
+    mixer.render(&mut buffer);
+    assert_eq!(&buffer[..16], &[0.5; 16]);
+    assert_eq!(&buffer[16..], &[0.0; 4]);
+}
+
+#[test]
+fn test_mixer_looping() {
+    let mut mixer = Mixer::new(100);
+    let id = mixer.play(constant(100, 2), 0.5).unwrap();
+    mixer.set_looping(id, true);
+
+    let mut buffer = [0.0; 16];
+    mixer.render(&mut buffer);
+    assert_eq!(&buffer[..], &[0.25; 16]);
+    assert!(mixer.is_playing(id));
+}
+
+#[test]
+fn test_mixer_positional() {
+    let mut mixer = Mixer::new(100);
+    mixer.set_range(100.0);
+    mixer.set_listener(Vector2::new(10.0, 10.0));
+    mixer.play_at(constant(100, 1), Vector2::new(-40.0, 10.0), 1.0).unwrap();
+
+    // Halfway out of range on the left: half as loud, and panned halfway to the left.
+    let mut buffer = [0.0; 2];
+    mixer.render(&mut buffer);
+    assert_eq!(buffer, [0.25, 0.125]);
+
+    mixer.play_at(constant(100, 1), Vector2::new(10.0, 500.0), 1.0).unwrap();
+    mixer.render(&mut buffer);
+    assert_eq!(buffer, [0.0, 0.0]);
+}